@@ -1,16 +1,16 @@
 use std::cmp::{min, max};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use color_eyre::eyre::Result;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind, MouseButton};
 use std::env;
-use std::fs::{
-    File,
-    read_dir,
-};
+use std::io;
+use std::fs::File;
+use regex::Regex;
 use ratatui::{
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     widgets::{Block, Padding, Paragraph},
-    // prelude::{Span, Line},
+    prelude::{Span, Line},
 };
 /*
 use tree_sitter_rust;
@@ -27,30 +27,37 @@ use crate::word::{
     find_word_start_backward
 };
 use ropey::Rope;
+use crate::comment;
+use crate::completion::is_word_char;
+use crate::diagnostic::{Diagnostic, Diagnostics};
+use crate::explorer::{diff_listing, list_dir, Change, Entry, EntryKind};
+use crate::filetype::{self, FileType, Options};
+use crate::format;
+use crate::file::{display_name, is_read_only, normalize, resolve_link, write_atomic};
+use crate::history::{History, Revision};
+use crate::indent;
+use crate::pair;
+use crate::quickfix::Severity;
+use crate::snippet::{self, Edit, Session};
+use crate::terminal::Terminal;
+use crate::text::{self, Case};
 use crate::X_OFFSET;
 
 /*
 
 =============================
  1. syntax highlighting with tree sitter
- 2. opening symlinks
 =============================
 
 */
 
-/*
-#[warn(dead_code)]
-enum FileType {
-    Rust
-}
-*/
-
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Mode{
     Insert, 
     Command,
     Normal,
-    Visual{ start: u16, end: u16 },
+    Replace,
+    Visual{ start: usize, col: usize, block: bool }, // from where it started to the cursor, whole lines or a block
 }
 
 pub struct Cursor{
@@ -58,6 +65,12 @@ pub struct Cursor{
     pub possible: (usize, usize)
 }
 
+impl Default for Cursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Cursor{
     pub fn new() -> Cursor{
         Cursor { current: (0,0), possible: (0,0) }
@@ -69,7 +82,8 @@ impl Cursor{
 pub enum BufferType {
     Empty,
     Directory,
-    File
+    File,
+    Terminal,
 }
 
 pub struct Lines{
@@ -79,6 +93,7 @@ pub struct Lines{
 // TODO: might need to add a variable for pathing
 // idea: replace file with path
 pub struct Buffer {
+    pub id: usize,
     pub buffer_type: BufferType,
     pub lines: Lines,
     pub size: (u16, u16),
@@ -88,18 +103,57 @@ pub struct Buffer {
     pub file: Option<PathBuf>,
     pub parent_dir: Option<PathBuf>,
     pub mode: Mode,
+    pub show_hidden: bool,
+    pub entries: Vec<Entry>, // what is in a directory, in listing order
+    pub link_target: Option<PathBuf>, // where the opened path points when it is a symlink
+    empty_file: bool, // the file had no text, its one empty line is written as nothing
+    pub error: Option<String>, // why the contents couldn't be read
+    pub marks: BTreeMap<char, (usize, usize)>, // line and column set with m
+    pub diagnostics: Diagnostics, // shown in the gutter, under the text and after the line
+    pub snippet: Option<Session>, // tabstops of the snippet being filled in
+    edit: Option<Edit>, // what the key being typed in insert mode changed, for the snippet
+    replaced: Vec<Option<char>>, // what each char typed in replace mode went over, for backspace
+    pub terminal: Option<Terminal>,
+    pub file_type: Option<&'static FileType>,
+    pub options: Options,
+    pub history: History,
+    pub revision: usize,
+    pub saved_revision: usize,
+    editing: bool,
 }
 
 
 impl Buffer {
     // TODO: rework to incorporate open
     pub fn new(path: &String, window_size: (u16, u16)) -> Result<Buffer> {
+        let mut buffer = Buffer::new_empty(window_size)?;
+
+        buffer.open(path)?;
+
+        Ok(buffer)
+    }
+
+    // shell or command running in a pty, starts out taking keys
+    pub fn new_terminal(cmd: Option<&str>, window_size: (u16, u16)) -> Result<Buffer> {
+        let mut buffer = Buffer::new_empty(window_size)?;
+        let dir = buffer.parent_dir.clone().unwrap_or_default();
+
+        buffer.terminal = Some(Terminal::spawn(cmd, &dir, terminal_size(window_size))?);
+        buffer.buffer_type = BufferType::Terminal;
+        buffer.mode = Mode::Insert;
+
+        Ok(buffer)
+    }
+
+    // buffer without a file, it gets one when written with a name
+    pub fn new_empty(window_size: (u16, u16)) -> Result<Buffer> {
         let parent_dir = env::current_dir()?;
 
-        let mut buffer = Buffer {
+        Ok(Buffer {
+            id: 0,
             buffer_type: BufferType::Empty,
             lines: Lines {
-                rope: Rope::new(),
+                rope: Rope::from("\n"),
             },
             size: window_size,
             ptr_y: 0,
@@ -108,33 +162,46 @@ impl Buffer {
             file: None,
             parent_dir: Some(parent_dir),
             mode: Mode::Normal,
-        };
-
-        buffer.open(path)?;
-
-        Ok(buffer)
+            show_hidden: false,
+            entries: vec![],
+            link_target: None,
+            empty_file: true,
+            error: None,
+            marks: BTreeMap::new(),
+            diagnostics: Diagnostics::default(),
+            snippet: None,
+            edit: None,
+            replaced: vec![],
+            terminal: None,
+            file_type: None,
+            options: Options::default(),
+            history: History::new(),
+            revision: 0,
+            saved_revision: 0,
+            editing: false,
+        })
     }
 
     // NOTE: mode change functions
 
-    pub fn change_mode(&mut self, mut mode: Mode) {
+    pub fn change_mode(&mut self, mode: Mode) {
         match mode {
             Mode::Insert => {
-                match &self.buffer_type {
-                    BufferType::Directory => {},
-                    _ => {
-                        execute!(std::io::stderr(), cursor::SetCursorStyle::SteadyBar).unwrap();
-                        self.mode = mode;
-                    }
-                }
+                execute!(std::io::stderr(), cursor::SetCursorStyle::SteadyBar).unwrap();
+                self.mode = mode;
             },
             Mode::Command => {
                 self.mode = mode;
             },
+            Mode::Replace => {
+                execute!(std::io::stderr(), cursor::SetCursorStyle::SteadyUnderScore).unwrap();
+                self.mode = mode;
+                self.replaced.clear();
+            },
             Mode::Normal => {
                 // recalc cursor pos
                 // get current pos, compare to line length
-                let slice = self.lines.rope.get_line(usize::from(self.cursor.current.1 + self.ptr_y));
+                let slice = self.lines.rope.get_line(self.cursor.current.1 + self.ptr_y);
 
                 if let Some(slice) = slice {
                     let line_len = slice.len_chars();
@@ -149,73 +216,86 @@ impl Buffer {
 
                     execute!(std::io::stderr(), cursor::SetCursorStyle::SteadyBlock).unwrap();
                     self.mode = mode;
+                    self.snippet = None;
+                    self.end_edit();
                 }
             },
-            Mode::Visual{ref mut start, ref mut end} => {
-                *start = 0;
-                *end = 0;
+            Mode::Visual{..} => {
                 self.mode = mode;
             }
         }
     }
 
     // functions for handling inputs for different types
-    pub fn insert_key_dir(&mut self, _key: KeyEvent) {
-        // TODO: add functionality here
+    // listings are edited as plain text, writing turns the edits into file operations
+    pub fn insert_key_dir(&mut self, key: KeyEvent) {
+        let size = self.size;
+        self.insert_key_file(key, size);
     }
 
     pub fn insert_key_file(&mut self, key: KeyEvent, size: (u16, u16)) {
+        self.edit = None;
         match key.code {
             KeyCode::Char(value) => {
                 if value == 'c' && key.modifiers == KeyModifiers::CONTROL {
                     self.change_mode(Mode::Normal);
                 } else {
+                    self.clear_placeholder();
+                    if self.insert_pair(value) {
+                        return;
+                    }
                     // inserting char into line
                     let line_idx = self.lines.rope.try_line_to_byte(self.ptr_y + self.cursor.current.1);
 
                     if let Ok(line_idx) = line_idx {
+                        let at = self.cursor_char();
+                        let before = self.snapshot();
                         let res = self.lines.rope.try_insert_char(line_idx + self.ptr_x + self.cursor.current.0, value);
-                        if let Ok(_) = res {
+                        if res.is_ok() {
+                            self.commit_edit(before);
+                            self.edited(at, 0, 1);
                             self.move_right();
+                            if indent::is_closer(value) {
+                                self.reindent_closer();
+                            }
                         }
                     }
                 }
             },
             KeyCode::Enter => {
-                // get current line index, append new line after
-
-                // FIX: this breaks
-                let line_idx = self.lines.rope.line_to_byte(self.ptr_y + self.cursor.current.1);
-                self.lines.rope.insert_char(line_idx + self.cursor.current.0 + self.ptr_y, '\n');
-                self.move_down(size);
-                self.cursor.current.0 = 0;
-                self.cursor.possible.0 = 0;
+                self.insert_newline();
+                self.refresh_view(size);
             },
             KeyCode::Backspace => {
+                if self.clear_placeholder() || self.remove_soft_tab() || self.remove_pair() {
+                    return;
+                }
                 let line_idx = self.lines.rope.try_line_to_byte(self.ptr_y + self.cursor.current.1);
 
                 if let Ok(line_idx) = line_idx {
                     let local_idx = self.cursor.current.0 + self.ptr_x;
                     let curr_idx = line_idx + local_idx;
 
-                    if curr_idx <= 0 {
+                    if curr_idx == 0 {
                         return;
                     }
-                    
+
+                    let before = self.snapshot();
+                    if self.lines.rope.try_remove(curr_idx-1..curr_idx).is_err() {
+                        return;
+                    }
+                    self.commit_edit(before);
+                    self.edited(curr_idx - 1, 1, 0);
+
                     if local_idx == 0 {
-                        // move up and end of line
-                        self.move_up();
-                        self.move_end_of_line()
+                        // move up and end of line, which now has the one below on it
+                        self.move_to_char(curr_idx - 1);
                     } else {
                         self.move_left();
                     }
-                    
-                    let _ = self.lines.rope.try_remove(curr_idx-1..curr_idx);
                 }
             },
-            KeyCode::Tab => {
-                // TODO: append tab
-            },
+            KeyCode::Tab => self.insert_tab(),
             KeyCode::Esc => {
                 self.change_mode(Mode::Normal);
             },
@@ -223,24 +303,254 @@ impl Buffer {
         }
     }
 
+    // R types over the chars from the cursor, backspace puts back what was there before
+    pub fn replace_key(&mut self, key: KeyEvent, size: (u16, u16)) {
+        self.edit = None;
+        match key.code {
+            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => self.change_mode(Mode::Normal),
+            KeyCode::Char(value) => {
+                let idx = self.cursor_char();
+                let old = self.lines.rope.get_char(idx).filter(|c| *c != '\n');
+                let removed = old.is_some() as usize;
+
+                let before = self.snapshot();
+                if self.lines.rope.try_remove(idx..idx + removed).is_err() || self.lines.rope.try_insert_char(idx, value).is_err() {
+                    self.restore(before);
+                    return;
+                }
+                self.commit_edit(before);
+                self.edited(idx, removed, 1);
+                self.replaced.push(old);
+                self.move_right();
+            },
+            KeyCode::Enter => {
+                // a line break goes in without taking a char
+                self.begin_edit();
+                self.insert_newline();
+                self.replaced.clear();
+                self.refresh_view(size);
+            },
+            KeyCode::Backspace => {
+                if self.cursor.current.0 + self.ptr_x == 0 {
+                    return;
+                }
+                let before = self.snapshot();
+                self.move_left();
+                let idx = self.cursor_char();
+                let res = match self.replaced.pop() {
+                    Some(Some(old)) => self.lines.rope.try_remove(idx..idx + 1)
+                        .and_then(|_| self.lines.rope.try_insert_char(idx, old))
+                        .map(|_| 1),
+                    Some(None) => self.lines.rope.try_remove(idx..idx + 1).map(|_| 0),
+                    None => return,
+                };
+                match res {
+                    Ok(inserted) => {
+                        self.commit_edit(before);
+                        self.edited(idx, 1, inserted);
+                    },
+                    Err(_) => self.restore(before),
+                }
+            },
+            KeyCode::Esc => self.change_mode(Mode::Normal),
+            _ => {},
+        }
+    }
+
+    // NOTE: pair functions
+
+    // the chars on each side of the cursor
+    fn around_cursor(&self) -> (Option<char>, Option<char>) {
+        let text = self.current_line_text();
+        let col = self.cursor.current.0 + self.ptr_x;
+        let prev = col.checked_sub(1).and_then(|i| text.chars().nth(i));
+        let next = text.chars().nth(col).filter(|c| *c != '\n');
+        (prev, next)
+    }
+
+    // a closer typed in front of the same one goes over it and an opener gets its closer put
+    // after the cursor, true when the char was taken care of
+    fn insert_pair(&mut self, c: char) -> bool {
+        if !self.options.auto_pairs {
+            return false;
+        }
+        let (open, close) = match self.options.pairs.iter().find(|(open, close)| *open == c || *close == c) {
+            Some(pair) => *pair,
+            None => return false,
+        };
+        let (prev, next) = self.around_cursor();
+        let idx = self.cursor_char();
+
+        if c == close && next == Some(c) {
+            self.move_to_char(idx + 1);
+            return true;
+        }
+
+        // only closed where the text after is done with, and quotes not in the middle of a word
+        let free = next.is_none_or(|n| n.is_whitespace() || self.options.pairs.iter().any(|(_, close)| *close == n));
+        let in_word = prev.is_some_and(|p| p.is_alphanumeric() || p == '_');
+        if c != open || !free || (open == close && in_word) {
+            return false;
+        }
+
+        self.begin_edit();
+        self.lines.rope.insert(idx, &format!("{}{}", open, close));
+        self.edited(idx, 0, 2);
+        self.move_to_char(idx + 1);
+        true
+    }
+
+    // backspace between an opener and its closer takes out both, false when it isn't
+    fn remove_pair(&mut self) -> bool {
+        let (prev, next) = self.around_cursor();
+        let paired = match (prev, next) {
+            (Some(prev), Some(next)) => self.options.pairs.contains(&(prev, next)),
+            _ => false,
+        };
+        if !self.options.auto_pairs || !paired {
+            return false;
+        }
+
+        let idx = self.cursor_char();
+        self.begin_edit();
+        self.lines.rope.remove(idx - 1..idx + 1);
+        self.edited(idx - 1, 2, 0);
+        self.move_to_char(idx - 1);
+        true
+    }
+
+    // a tab char, or whitespace to the next soft tab stop, blanks right before the cursor are
+    // redone so they take as few chars as they can
+    fn insert_tab(&mut self) {
+        let line = self.current_line_text();
+        let col = self.cursor.current.0 + self.ptr_x;
+        let before: String = line.chars().take(col).collect();
+        let blanks = before.len() - before.trim_end_matches([' ', '\t']).len();
+        let from = indent::display_col(&line, col - blanks, self.options.tab_width);
+        let at = indent::display_col(&line, col, self.options.tab_width);
+
+        let text = match (self.options.soft_tab, self.options.expand_tab) {
+            (0, false) => format!("{}\t", &before[before.len() - blanks..]),
+            (0, true) => indent::fill(from, indent::next_tab_stop(at, self.options.tab_width), &self.options),
+            (width, _) => indent::fill(from, indent::next_tab_stop(at, width), &self.options),
+        };
+        let idx = self.cursor_char();
+        self.replace_before_cursor(idx - blanks, &text);
+    }
+
+    // backspace over spaces goes back to the soft tab stop before, false when it is a plain
+    // backspace
+    fn remove_soft_tab(&mut self) -> bool {
+        let line = self.current_line_text();
+        let col = self.cursor.current.0 + self.ptr_x;
+        let before: String = line.chars().take(col).collect();
+        if self.options.soft_tab == 0 || !before.ends_with(' ') {
+            return false;
+        }
+
+        let blanks = before.len() - before.trim_end_matches([' ', '\t']).len();
+        let from = indent::display_col(&line, col - blanks, self.options.tab_width);
+        let at = indent::display_col(&line, col, self.options.tab_width);
+        let to = max(from, (at - 1) / self.options.soft_tab * self.options.soft_tab);
+        if at - to <= 1 {
+            return false;
+        }
+
+        let idx = self.cursor_char();
+        self.replace_before_cursor(idx - blanks, &indent::fill(from, to, &self.options));
+        true
+    }
+
     pub fn paste(&mut self, text: String) {
         let m = self.mode;
 
         self.change_mode(Mode::Insert);
         let char_idx = self.cursor.current.0 + self.lines.rope.line_to_byte(self.cursor.current.1);
 
-        if let Err(_) = self.lines.rope.try_insert(char_idx, &text) {
-            println!("error when pasting {text} at {char_idx}");
+        let before = self.snapshot();
+        match self.lines.rope.try_insert(char_idx, &text) {
+            Ok(()) => self.commit_edit(before),
+            Err(_) => println!("error when pasting {text} at {char_idx}"),
         }
 
-        self.change_mode(m)
+        self.change_mode(m);
+        self.end_edit();
+    }
+
+    // NOTE: undo functions
+
+    fn snapshot(&self) -> Revision {
+        Revision {
+            id: self.revision,
+            rope: self.lines.rope.clone(),
+            cursor: self.cursor.current,
+            ptr_y: self.ptr_y,
+        }
+    }
+
+    // records the text before a change, everything until end_edit is one undo step.
+    // each change is a new revision, even inside of one step
+    pub fn begin_edit(&mut self) {
+        let before = self.snapshot();
+        self.commit_edit(before);
+    }
+
+    // the same for a change that can fail, called once it went in with the text from before it
+    fn commit_edit(&mut self, before: Revision) {
+        if !self.editing {
+            self.history.push(before);
+            self.editing = true;
+        }
+        self.revision = self.history.new_id();
+    }
+
+    pub fn end_edit(&mut self) {
+        self.editing = false;
+    }
+
+    fn restore(&mut self, revision: Revision) {
+        self.lines.rope = revision.rope;
+        self.revision = revision.id;
+        self.ptr_y = min(revision.ptr_y, self.lines.rope.len_lines().saturating_sub(1));
+        self.set_cursor(revision.cursor.0, revision.cursor.1);
+    }
+
+    // modified when the text differs from the last saved revision, undoing back to it clears this
+    pub fn is_modified(&self) -> bool {
+        self.buffer_type != BufferType::Terminal && self.revision != self.saved_revision
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.end_edit();
+        let current = self.snapshot();
+
+        match self.history.undo(current) {
+            Some(revision) => {
+                self.restore(revision);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.end_edit();
+        let current = self.snapshot();
+
+        match self.history.redo(current) {
+            Some(revision) => {
+                self.restore(revision);
+                true
+            },
+            None => false,
+        }
     }
 
     pub fn move_down(&mut self, size: (u16, u16)) {
         // next logical y
         let y = self.cursor.current.1.checked_add(1).unwrap_or(self.cursor.current.1);
 
-        if y > size.1.checked_sub(1).unwrap_or(0).into() {
+        if y > size.1.saturating_sub(1).into() {
             if usize::from(size.1) + self.ptr_y < self.lines.rope.len_lines() - 1 {
                 self.ptr_y += 1;
             }
@@ -248,7 +558,7 @@ impl Buffer {
             // max lines in file
             let line_nums = self.lines.rope.len_lines() - 2;
             let cap = min(line_nums, usize::from(size.1 - 1) + self.ptr_y);
-            let y = min(y, cap.try_into().unwrap());
+            let y = min(y, cap);
             self.cursor.current.1 = y;
         }
 
@@ -292,21 +602,20 @@ impl Buffer {
             self.cursor.current.0 = 0;
         } else{
             match &self.mode {
-                Mode::Normal => {
+                Mode::Normal | Mode::Visual{..} => {
                     let x = self.cursor.current.0.checked_add(1).unwrap_or(self.cursor.current.0);
                     let x = min(x, line_len - 1);
 
                     self.cursor.current.0 = x;
                     self.cursor.possible.0 = x;
                 },
-                Mode::Insert => {
+                Mode::Insert | Mode::Replace => {
                     let x = self.cursor.current.0.checked_add(1).unwrap_or(self.cursor.current.0);
                     let x = min(x, line_len);
 
                     self.cursor.current.0 = x;
                     self.cursor.possible.0 = x;
                 },
-                Mode::Visual{..} => {},
                 Mode::Command => {
                     todo!()
                 },
@@ -331,15 +640,14 @@ impl Buffer {
             }
 
             match &self.mode {
-                Mode::Normal => {
+                Mode::Normal | Mode::Visual{..} => {
                     self.cursor.current.0 = line_len - 1;
                     self.cursor.possible.0 = line_len - 1;
                 },
-                Mode::Insert => {
+                Mode::Insert | Mode::Replace => {
                     self.cursor.current.0 = line_len;
                     self.cursor.possible.0 = line_len;
                 },
-                Mode::Visual{..} => {}
                 Mode::Command => {}
             }
         }
@@ -426,26 +734,496 @@ impl Buffer {
         self.refresh_view(size);
     }
 
+    // opens a line above the cursor's with the indent of the one it goes in front of,
+    // a level more when that one closes a block
     pub fn new_line_above(&mut self, size: (u16, u16)) {
-        self.cursor.current.0 = 0;
-        self.cursor.possible.0 = 0;
+        let line = self.ptr_y + self.cursor.current.1;
+        let text = self.current_line_text();
+        let width = match text.trim().is_empty() {
+            true => self.indent_for_line(line, ""),
+            false if indent::leading_closers(&text) > 0 => indent::indent_of(&text, self.options.tab_width) + self.options.indent_width,
+            false => indent::indent_of(&text, self.options.tab_width),
+        };
 
-        let line_idx = self.lines.rope.try_line_to_byte(self.ptr_y + self.cursor.current.1);
+        self.open_line(line, width);
+        self.refresh_view(size);
+    }
 
-        if let Ok(idx) = line_idx {
-            let _ = self.lines.rope.try_insert_char(idx, '\n');
-            self.change_mode(Mode::Insert);
+    // removes the line under the cursor as its own undo step
+    pub fn delete_line(&mut self) {
+        let line = self.ptr_y + self.cursor.current.1;
+        let len_lines = self.lines.rope.len_lines();
+
+        // the empty line after the final newline isn't a real line
+        if line + 1 >= len_lines {
+            return;
         }
 
-        self.refresh_view(size);
+        self.begin_edit();
+        let start = self.lines.rope.line_to_char(line);
+        let end = self.lines.rope.line_to_char(line + 1);
+        self.lines.rope.remove(start..end);
+
+        if self.lines.rope.len_chars() == 0 {
+            self.lines.rope.insert(0, "\n");
+        }
+        self.end_edit();
+
+        if self.ptr_y > 0 && self.ptr_y + 1 >= self.lines.rope.len_lines() {
+            self.ptr_y -= 1;
+        }
+        self.set_cursor(self.cursor.current.0, self.cursor.current.1);
+    }
+
+    // replaces pattern in the line under the cursor, every match when global,
+    // false when nothing matched
+    pub fn substitute(&mut self, pattern: &Regex, replacement: &str, global: bool) -> bool {
+        let line = self.ptr_y + self.cursor.current.1;
+        let text = match self.lines.rope.get_line(line) {
+            Some(text) => text.to_string(),
+            None => return false,
+        };
+
+        // the newline is kept out of reach of the pattern
+        let content = text.strip_suffix('\n').unwrap_or(&text);
+        if !pattern.is_match(content) {
+            return false;
+        }
+
+        let limit = if global { 0 } else { 1 };
+        let replaced = pattern.replacen(content, limit, replacement);
+        if replaced == content {
+            return true;
+        }
+
+        self.begin_edit();
+        let start = self.lines.rope.line_to_char(line);
+        self.lines.rope.remove(start..start + content.chars().count());
+        self.lines.rope.insert(start, &replaced);
+        self.end_edit();
+
+        self.set_cursor(self.cursor.current.0, self.cursor.current.1);
+        true
+    }
+
+    // number of real lines, the empty one after the final newline doesn't count
+    pub fn line_count(&self) -> usize {
+        self.lines.rope.len_lines().saturating_sub(1).max(1)
+    }
+
+    // text of lines start through end, with their newlines
+    pub fn lines_text(&self, start: usize, end: usize) -> String {
+        let rope = &self.lines.rope;
+        let from = rope.line_to_char(min(start, rope.len_lines()));
+        let to = rope.line_to_char(min(end + 1, rope.len_lines()));
+        rope.slice(from..to).to_string()
+    }
+
+    // swaps lines start through end for text as one undo step, the cursor goes to the first of them
+    pub fn replace_lines(&mut self, start: usize, end: usize, text: &str) {
+        let rope_lines = self.lines.rope.len_lines();
+        let from = self.lines.rope.line_to_char(min(start, rope_lines));
+        let to = self.lines.rope.line_to_char(min(end + 1, rope_lines));
+
+        self.begin_edit();
+        self.lines.rope.remove(from..to);
+        if !text.is_empty() {
+            self.lines.rope.insert(from, text);
+            if !text.ends_with('\n') {
+                self.lines.rope.insert(from + text.chars().count(), "\n");
+            }
+        }
+        if self.lines.rope.len_chars() == 0 {
+            self.lines.rope.insert(0, "\n");
+        }
+        self.end_edit();
+
+        self.jump_to(start, 0);
+    }
+
+    // replaces char ranges of the text as one undo step, the ranges are all in the text
+    // as it was, inserts at the same place end up in the order given
+    pub fn apply_edits(&mut self, edits: Vec<(usize, usize, String)>) {
+        if edits.is_empty() {
+            return;
+        }
+
+        let (line, col) = (self.ptr_y + self.cursor.current.1, self.cursor.current.0);
+        let mut edits: Vec<(usize, (usize, usize, String))> = edits.into_iter().enumerate().collect();
+        edits.sort_by(|(i, a), (j, b)| (b.0, j).cmp(&(a.0, i)));
+
+        self.begin_edit();
+        for (_, (start, end, text)) in edits {
+            let len = self.lines.rope.len_chars();
+            let (start, end) = (min(start, len), min(end, len));
+            self.lines.rope.remove(start..max(start, end));
+            self.lines.rope.insert(start, &text);
+        }
+        // every line is expected to end in a newline
+        let len = self.lines.rope.len_chars();
+        if len == 0 || self.lines.rope.char(len - 1) != '\n' {
+            self.lines.rope.insert(len, "\n");
+        }
+        self.end_edit();
+
+        self.jump_to(line, col);
+    }
+
+    // rewrites lines start through end as one undo step, the cursor goes to the start of the
+    // first one's text
+    pub fn map_lines(&mut self, start: usize, end: usize, f: impl Fn(&str) -> String) {
+        let end = min(end, self.line_count() - 1);
+
+        self.begin_edit();
+        for line in start..=end {
+            let text = self.lines.rope.line(line).to_string();
+            self.set_line(line, &f(&text));
+        }
+        self.end_edit();
+
+        let first = self.lines.rope.line(start).to_string();
+        self.jump_to(start, indent::leading(&first).chars().count());
+    }
+
+    // the line's text with its newline, swapped for text
+    fn set_line(&mut self, line: usize, text: &str) {
+        let old = self.lines.rope.line(line);
+        if old == text {
+            return;
+        }
+        let from = self.lines.rope.line_to_char(line);
+        self.lines.rope.remove(from..from + old.len_chars());
+        self.lines.rope.insert(from, text);
+    }
+
+    // moves lines a number of shift widths right, or left when negative
+    pub fn shift_lines(&mut self, start: usize, end: usize, by: isize) {
+        let options = self.options.clone();
+        self.map_lines(start, end, |line| indent::shift(line, by, &options));
+    }
+
+    // NOTE: case, number and join functions
+
+    // the chars from start until end put in a case as one undo step
+    pub fn change_case(&mut self, start: usize, end: usize, case: Case) {
+        let end = min(end, self.lines.rope.len_chars());
+        let text = self.lines.rope.slice(start..end).to_string();
+        let changed = case.apply(&text);
+        if changed == text {
+            return;
+        }
+
+        self.begin_edit();
+        self.lines.rope.remove(start..end);
+        self.lines.rope.insert(start, &changed);
+        self.end_edit();
+    }
+
+    // ~ switches the case of count chars from the cursor and goes past them
+    pub fn toggle_case(&mut self, count: usize) {
+        let line = self.ptr_y + self.cursor.current.1;
+        let col = self.cursor.current.0 + self.ptr_x;
+        let len = self.current_line_text().trim_end_matches('\n').chars().count();
+        if col >= len {
+            return;
+        }
+
+        let start = self.cursor_char();
+        self.change_case(start, start + min(count, len - col), Case::Toggle);
+        self.jump_to(line, min(col + count, len - 1));
+    }
+
+    // ctrl-a and ctrl-x add to the number under the cursor or after it on the line
+    // ctrl-a on lines start through end, only looking at the chars in cols. with steps the
+    // number on the nth line that has one goes n times as far, like g ctrl-a
+    pub fn increment_lines(&mut self, start: usize, end: usize, cols: (usize, usize), by: i64, steps: bool) -> bool {
+        let mut changed = 0;
+        for line in start..=min(end, self.line_count() - 1) {
+            let text = self.lines.rope.line(line).to_string();
+            let within: String = text.chars().take(cols.1.saturating_add(1)).collect();
+            let rest: String = text.chars().skip(within.chars().count()).collect();
+
+            let step = if steps { changed + 1 } else { 1 };
+            if let Some((within, _)) = text::increment(&within, cols.0, by * step) {
+                self.begin_edit();
+                self.set_line(line, &format!("{}{}", within, rest));
+                changed += 1;
+            }
+        }
+        self.end_edit();
+
+        self.jump_to(start, cols.0);
+        changed > 0
+    }
+
+    pub fn increment(&mut self, by: i64) -> bool {
+        let line = self.ptr_y + self.cursor.current.1;
+        let col = self.cursor.current.0 + self.ptr_x;
+        let (text, end) = match text::increment(&self.current_line_text(), col, by) {
+            Some(changed) => changed,
+            None => return false,
+        };
+
+        self.begin_edit();
+        self.set_line(line, &text);
+        self.end_edit();
+        self.jump_to(line, end);
+        true
+    }
+
+    // J joins count lines from the cursor, at least two, gJ without touching the whitespace
+    pub fn join_lines(&mut self, count: usize, spaces: bool) -> bool {
+        let line = self.ptr_y + self.cursor.current.1;
+        let joins = min(max(count, 2) - 1, self.line_count() - 1 - min(line, self.line_count() - 1));
+        if joins == 0 {
+            return false;
+        }
+
+        let mut col = 0;
+        self.begin_edit();
+        for _ in 0..joins {
+            let first = self.lines.rope.line(line).to_string();
+            let next = self.lines.rope.line(line + 1).to_string();
+            let (joined, at) = text::join(&first, &next, spaces);
+
+            let start = self.lines.rope.line_to_char(line);
+            let end = self.lines.rope.line_to_char(line + 2);
+            self.lines.rope.remove(start..end);
+            self.lines.rope.insert(start, &joined);
+            col = at;
+        }
+        self.end_edit();
+
+        let len = self.lines.rope.line(line).len_chars().saturating_sub(1);
+        self.jump_to(line, min(col, len.saturating_sub(1)));
+        true
+    }
+
+    // r puts count copies of c over the chars from the cursor, when the line has that many
+    pub fn replace_chars(&mut self, c: char, count: usize) -> bool {
+        let line = self.ptr_y + self.cursor.current.1;
+        let col = self.cursor.current.0 + self.ptr_x;
+        let len = self.current_line_text().trim_end_matches('\n').chars().count();
+        if col + count > len {
+            return false;
+        }
+
+        let start = self.cursor_char();
+        self.begin_edit();
+        self.lines.rope.remove(start..start + count);
+        self.lines.rope.insert(start, &c.to_string().repeat(count));
+        self.end_edit();
+        self.jump_to(line, col + count - 1);
+        true
+    }
+
+    // NOTE: comment functions
+
+    // comments lines start through end in or out together as one undo step, gives whether
+    // they were commented
+    pub fn toggle_comment(&mut self, start: usize, end: usize) -> Result<bool, String> {
+        let options = self.options.clone();
+        let marker = match (&options.comment, &options.block_comment) {
+            (Some(start), _) => comment::Marker { start, end: None },
+            (None, Some((start, end))) => comment::Marker { start, end: Some(end) },
+            (None, None) => return Err(String::from("No comment string for this file type")),
+        };
+
+        let end = min(end, self.line_count() - 1);
+        let lines: Vec<String> = (start..=end).map(|l| self.lines.rope.line(l).to_string()).collect();
+        let commented = !comment::all_commented(&lines, &marker);
+        let toggled = comment::toggle(&lines, &marker, options.tab_width);
+
+        self.begin_edit();
+        for (line, text) in (start..=end).zip(toggled) {
+            self.set_line(line, &text);
+        }
+        self.end_edit();
+
+        let first = self.lines.rope.line(start).to_string();
+        self.jump_to(start, indent::leading(&first).chars().count());
+        Ok(commented)
+    }
+
+    // NOTE: indent functions
+
+    // the lines of the paragraph around a line, the blank lines around it when it is blank,
+    // around takes in the blank lines after it too
+    pub fn paragraph(&self, line: usize, around: bool) -> (usize, usize) {
+        let last = self.line_count() - 1;
+        let blank = |l: usize| self.lines.rope.line(l).chars().all(char::is_whitespace);
+        let kind = blank(line);
+
+        let mut start = line;
+        while start > 0 && blank(start - 1) == kind {
+            start -= 1;
+        }
+        let mut end = line;
+        while end < last && blank(end + 1) == kind {
+            end += 1;
+        }
+        if around && !kind {
+            while end < last && blank(end + 1) {
+                end += 1;
+            }
+        }
+        (start, end)
+    }
+
+    fn indent_openers(&self) -> &'static [&'static str] {
+        self.file_type.map_or(&[], |ft| ft.indent_after)
+    }
+
+    // the indent the code above a line calls for, text being what the line starts with
+    pub fn indent_for_line(&self, line: usize, text: &str) -> usize {
+        let prev = (0..min(line, self.lines.rope.len_lines()))
+            .rev()
+            .map(|l| self.lines.rope.line(l).to_string())
+            .find(|l| !l.trim().is_empty());
+        indent::indent_for(prev.as_deref(), text, &self.options, self.indent_openers())
+    }
+
+    // redoes the indent of lines start through end as one undo step, each one after the
+    // line above it
+    pub fn reindent_lines(&mut self, start: usize, end: usize) {
+        let end = min(end, self.line_count() - 1);
+
+        self.begin_edit();
+        for line in start..=end {
+            let text = self.lines.rope.line(line).to_string();
+            let width = self.indent_for_line(line, &text);
+            self.set_line(line, &indent::with_indent(&text, width, &self.options));
+        }
+        self.end_edit();
+
+        let first = self.lines.rope.line(start).to_string();
+        self.jump_to(start, indent::leading(&first).chars().count());
+    }
+
+    // a closing bracket typed at the start of a line takes it back to the block's indent
+    fn reindent_closer(&mut self) {
+        let line = self.ptr_y + self.cursor.current.1;
+        let text = self.current_line_text();
+        let col = self.cursor.current.0 + self.ptr_x;
+        let before: String = text.chars().take(col.saturating_sub(1)).collect();
+        if !before.trim().is_empty() {
+            return;
+        }
+
+        let width = self.indent_for_line(line, &text);
+        let indented = indent::with_indent(&text, width, &self.options);
+        let moved = indented.chars().count() as isize - text.chars().count() as isize;
+        self.set_line(line, &indented);
+        self.edited(self.lines.rope.line_to_char(line), text.chars().count(), indented.chars().count());
+        self.jump_to(line, col.saturating_add_signed(moved));
+    }
+
+    // splits the line at the cursor with the new line indented for the code before it, between
+    // a pair of brackets the closer goes on a line of its own
+    fn insert_newline(&mut self) {
+        let line = self.ptr_y + self.cursor.current.1;
+        let text = self.current_line_text();
+        let col = min(self.cursor.current.0 + self.ptr_x, text.chars().count().saturating_sub(1));
+        let before: String = text.chars().take(col).collect();
+        let after: String = text.chars().skip(col).collect();
+        let rest = after.trim_start_matches([' ', '\t']);
+        let openers = self.indent_openers();
+
+        let inner = indent::indent_for(Some(&before), "", &self.options, openers);
+        let outer = indent::indent_for(Some(&before), rest, &self.options, openers);
+        let paired = before.trim_end().chars().last().and_then(indent::closer_for).is_some_and(|c| rest.starts_with(c));
+
+        // a line with only its indent on it is left empty, its indent goes down with the cursor
+        let (cut, inserted, end_text) = if before.trim().is_empty() {
+            (before.chars().count(), format!("\n{}", before), String::new())
+        } else if paired {
+            (0, format!("\n{}", indent::fill(0, inner, &self.options)), format!("\n{}", indent::fill(0, outer, &self.options)))
+        } else {
+            (0, format!("\n{}", indent::fill(0, outer, &self.options)), String::new())
+        };
+
+        let start = self.lines.rope.line_to_char(line) + col;
+        let end = start + after.chars().count() - rest.chars().count();
+
+        self.begin_edit();
+        self.lines.rope.remove(start - cut..end);
+        self.lines.rope.insert(start - cut, &format!("{}{}", inserted, end_text));
+        self.edited(start - cut, end - (start - cut), inserted.chars().count() + end_text.chars().count());
+        self.move_to_char(start - cut + inserted.chars().count());
+    }
+
+    // puts text in place of what is between start and the cursor, leaving the cursor after it,
+    // part of the edit insert mode is making
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        self.replace_around_cursor(start, 0, text);
+    }
+
+    // the same, also taking the given number of chars after the cursor, up to the end of its line
+    pub fn replace_around_cursor(&mut self, start: usize, after: usize, text: &str) {
+        let cursor = self.cursor_char();
+        let start = min(start, cursor);
+        let end = self.after_cursor(after);
+
+        self.begin_edit();
+        self.lines.rope.remove(start..end);
+        self.lines.rope.insert(start, text);
+        self.edited(start, end - start, text.chars().count());
+
+        let idx = start + text.chars().count();
+        let line = self.lines.rope.char_to_line(idx);
+        self.jump_to(line, idx - self.lines.rope.line_to_char(line));
+    }
+
+    // the char index the given number of chars after the cursor, kept on the cursor's line
+    fn after_cursor(&self, after: usize) -> usize {
+        let rope = &self.lines.rope;
+        let line = rope.char_to_line(self.cursor_char());
+        let line_end = rope.line_to_char(line) + rope.line(line).chars().take_while(|c| *c != '\n' && *c != '\r').count();
+        min(self.cursor_char() + after, max(line_end, self.cursor_char()))
+    }
+
+    // the line under the cursor with its newline
+    pub fn current_line_text(&self) -> String {
+        let line = self.ptr_y + self.cursor.current.1;
+        self.lines.rope.get_line(line).map_or(String::from("\n"), |l| l.to_string())
     }
 
+    // inserts whole lines under the cursor as one undo step and moves onto them
+    pub fn put_below(&mut self, text: &str) {
+        let line = self.ptr_y + self.cursor.current.1;
+        let idx = self.lines.rope.try_line_to_char(line + 1).unwrap_or(self.lines.rope.len_chars());
+
+        self.begin_edit();
+        if text.ends_with('\n') {
+            self.lines.rope.insert(idx, text);
+        } else {
+            self.lines.rope.insert(idx, &format!("{}\n", text));
+        }
+        self.end_edit();
+
+        let indent = text.chars().take_while(|c| c.is_whitespace() && *c != '\n').count();
+        self.jump_to(line + 1, indent);
+    }
+
+    // opens a line below the cursor's indented for what comes after it
     pub fn new_line_below(&mut self, size: (u16, u16)) {
-        self.move_down(size);
-        self.new_line_above(size);
+        let line = self.ptr_y + self.cursor.current.1 + 1;
+        let width = self.indent_for_line(line, "");
+
+        self.open_line(line, width);
         self.refresh_view(size);
     }
 
+    fn open_line(&mut self, line: usize, width: usize) {
+        let indent = indent::fill(0, width, &self.options);
+        let idx = self.lines.rope.line_to_char(min(line, self.lines.rope.len_lines()));
+
+        self.begin_edit();
+        self.lines.rope.insert(idx, &format!("{}\n", indent));
+        self.change_mode(Mode::Insert);
+        self.jump_to(line, indent.chars().count());
+    }
+
     pub fn get_hover_file(&mut self) -> String {
         let line_idx = self.ptr_y + self.cursor.current.1;
         let current_line = self.lines.rope.get_line(line_idx);
@@ -459,71 +1237,506 @@ impl Buffer {
                 result.push(c);
             }
 
-            self.refresh_buffer();
             return result;
         }
 
-        return "could not open file".to_string();
+        "could not open file".to_string()
     }
 
     // TODO: impl in new buffer
+    //
+    // errors leave the buffer as it was, except for unreadable directories
+    // which open as a listing with only . and .. and the reason in error
     pub fn open(&mut self, name: &String) -> std::io::Result<()>{
-        let path = Path::new(name);
-        if let Some(parent_dir) = &self.parent_dir {
-            let path = parent_dir.join(path);
-
-            // TODO: impl for empty
-            if !path.exists() {
-                self.buffer_type = BufferType::Empty;
-                self.lines.rope = Rope::new();
-                self.file = None;
-                return Ok(())
+        let parent_dir = match &self.parent_dir {
+            Some(parent_dir) => parent_dir,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "no directory to open from")),
+        };
+        let path = normalize(&parent_dir.join(Path::new(name)));
+        let previous = self.file.clone();
+
+        // links are kept as the buffer's path, the target is shown next to it
+        let link_target = if path.is_symlink() {
+            let target = resolve_link(&path)?;
+            if !target.exists() {
+                let message = format!("broken symbolic link to {}", display_name(&target));
+                return Err(io::Error::new(io::ErrorKind::NotFound, message));
             }
+            Some(target)
+        } else {
+            None
+        };
 
-            if path.is_file() {
-                let rope = Rope::from_reader(
-                    File::open(&path)?
-                    )?;
+        if !path.exists() {
+            // new file, created on disk when it is first written
+            self.lines.rope = Rope::from("\n");
+            self.empty_file = true;
+            self.file = Some(path.to_owned());
+            self.buffer_type = BufferType::Empty;
+            self.error = None;
+        } else if path.is_file() {
+            let mut rope = Rope::from_reader(
+                File::open(&path)?
+                )?;
+
+            // every line is expected to end in a newline
+            self.empty_file = rope.len_chars() == 0;
+            if self.empty_file {
+                rope = Rope::from("\n");
+            }
 
-                self.lines.rope = rope;
-                self.file = Some(path.to_owned());
-                self.buffer_type = BufferType::File;
-            } else if path.is_dir() {
-                // children to lines in rope
-                let mut rope = Rope::new();
+            self.lines.rope = rope;
+            self.file = Some(path.to_owned());
+            self.buffer_type = BufferType::File;
+            self.error = None;
+        } else if path.is_dir() {
+            // children to lines in rope
+            let mut rope = Rope::new();
+
+            rope.append("./\n".into());
+            rope.append("../\n".into());
+
+            match list_dir(&path, self.show_hidden) {
+                Ok(entries) => {
+                    for entry in &entries {
+                        rope.append(format!("{}\n", entry.display()).into());
+                    }
+                    self.entries = entries;
+                    self.error = None;
+                },
+                Err(err) => {
+                    self.entries = vec![];
+                    self.error = Some(err.kind().to_string());
+                },
+            }
 
-                rope.append(".\n".into());
-                rope.append("..\n".into());
+            self.lines.rope = rope;
+            self.file = None;
+            self.parent_dir = Some(path);
+            self.buffer_type = BufferType::Directory;
+        } else {
+            // sockets, fifos and devices
+            let message = format!("\"{}\" is not a file or directory", display_name(&path));
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
 
-                let reader = read_dir(path.clone()).unwrap();
+        self.link_target = link_target;
 
-                for path in reader {
-                    if let Ok(name) = path {
-                        let path_name = name.file_name();
+        // options set by hand stay with the file they were set for
+        if self.file != previous {
+            self.options.explicit.clear();
+        }
+        self.detect_file_type();
 
-                        if let Ok(path_string) = path_name.into_string() {
-                            let mut path = String::from(path_string);
-                            path.push_str("\n");
-                            rope.append(path.into());
-                        }
-                    }
-                }
+        // new contents start a new history
+        self.history.clear();
+        self.revision = self.history.new_id();
+        self.saved_revision = self.revision;
 
-                self.lines.rope = rope;
-                self.file = None;
-                self.parent_dir = Some(path);
-                self.buffer_type = BufferType::Directory;
-            } else if path.is_symlink() {
-                todo!();
-            } else {
-                panic!("no thank you");
-            }
+        self.refresh_buffer();
+        Ok(())
+    }
 
-            self.refresh_buffer();
-            Ok(())
-        } else {
-            panic!("how did you manage to break this");
+    // picks the file type from the name and contents and resets the options to its own
+    pub fn detect_file_type(&mut self) {
+        if self.buffer_type == BufferType::Directory {
+            self.set_file_type(None);
+            return;
         }
+
+        let text = self.lines.rope.to_string();
+        self.set_file_type(filetype::detect(self.file.as_deref(), &text));
+
+        if let Some(modeline) = filetype::modeline(&text) {
+            modeline.apply(&mut self.options);
+        }
+    }
+
+    pub fn set_file_type(&mut self, file_type: Option<&'static FileType>) {
+        self.file_type = file_type;
+        self.options = self.options.for_new_type(file_type);
+    }
+
+    // NOTE: directory functions
+
+    // entry under the cursor in a directory listing, without the trailing /
+    pub fn hovered_entry(&self) -> Option<String> {
+        if self.buffer_type != BufferType::Directory {
+            return None;
+        }
+
+        let line = self.lines.rope.get_line(self.ptr_y + self.cursor.current.1)?;
+        let name = line.to_string();
+        let name = name.trim_end_matches('\n').trim_end_matches('/');
+
+        match name {
+            "" | "." | ".." => None,
+            name => Some(name.to_string()),
+        }
+    }
+
+    // filesystem operations that the edits to the listing stand for
+    pub fn listing_changes(&self) -> Vec<Change> {
+        let original: Vec<String> = self.entries.iter().map(|e| e.display()).collect();
+        let edited: Vec<String> = self.lines.rope
+            .lines()
+            .map(|l| l.to_string().trim_end_matches('\n').to_string())
+            .collect();
+
+        diff_listing(&original, &edited)
+    }
+
+    // reads the directory again, putting the cursor on select when it is listed
+    // and otherwise leaving it on the same line
+    pub fn refresh_listing(&mut self, select: Option<&str>) -> io::Result<()> {
+        let line = self.ptr_y + self.cursor.current.1;
+        let dir = match &self.parent_dir {
+            Some(dir) => dir.to_string_lossy().to_string(),
+            None => return Ok(()),
+        };
+
+        self.open(&dir)?;
+        self.jump_to(line, 0);
+
+        if let Some(name) = select {
+            self.select_entry(name);
+        }
+        Ok(())
+    }
+
+    // puts the cursor on the entry called name, returns false when it isn't listed
+    pub fn select_entry(&mut self, name: &str) -> bool {
+        let name = name.trim_end_matches('/');
+        let line = self.lines.rope.lines().position(|l| {
+            let l = l.to_string();
+            let l = l.trim_end_matches('\n');
+            l == name || l.strip_suffix('/') == Some(name)
+        });
+
+        match line {
+            Some(line) => {
+                self.jump_to(line, 0);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn toggle_hidden(&mut self) -> io::Result<()> {
+        let hovered = self.hovered_entry();
+        self.show_hidden = !self.show_hidden;
+        self.refresh_listing(hovered.as_deref())
+    }
+
+    // moves the cursor to a line of the file, scrolling only when it is off screen
+    pub fn jump_to(&mut self, line: usize, col: usize) {
+        let last = self.lines.rope.len_lines().saturating_sub(2);
+        let line = min(line, last);
+        let height = max(usize::from(self.size.1), 1);
+
+        if line < self.ptr_y || line >= self.ptr_y + height {
+            self.ptr_y = line.saturating_sub(height / 2);
+        }
+
+        self.set_cursor(col, line - self.ptr_y);
+    }
+
+    // NOTE: mark functions
+
+    pub fn set_mark(&mut self, name: char) {
+        let line = self.ptr_y + self.cursor.current.1;
+        self.marks.insert(name, (line, self.cursor.current.0));
+    }
+
+    // moves to a mark, exact keeps its column instead of going to the start of the line
+    pub fn jump_to_mark(&mut self, name: char, exact: bool) -> bool {
+        match self.marks.get(&name) {
+            Some(&(line, col)) => {
+                self.jump_to(line, if exact { col } else { 0 });
+                true
+            },
+            None => false,
+        }
+    }
+
+    // the first and last line selected in visual mode
+    pub fn visual_lines(&self) -> Option<(usize, usize)> {
+        match self.mode {
+            Mode::Visual { start, .. } => {
+                let line = self.ptr_y + self.cursor.current.1;
+                Some((min(start, line), max(start, line)))
+            },
+            _ => None,
+        }
+    }
+
+    // the first and last column of a visual block
+    pub fn visual_cols(&self) -> Option<(usize, usize)> {
+        match self.mode {
+            Mode::Visual { col, block: true, .. } => {
+                let at = self.cursor.current.0 + self.ptr_x;
+                Some((min(col, at), max(col, at)))
+            },
+            _ => None,
+        }
+    }
+
+    // NOTE: match functions
+
+    // % goes to the other half of the pair under the cursor or after it on the line,
+    // #if lines only pair up where # doesn't start a comment
+    pub fn jump_to_match(&mut self) -> bool {
+        let directives = self.options.comment.as_deref() != Some("#");
+        match pair::find_match(&self.lines.rope, self.cursor_char(), directives) {
+            Some(idx) => {
+                self.move_to_char(idx);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // [( and [{ go back to the bracket left open around the cursor, ]) and ]} on to the one closing it
+    pub fn jump_to_enclosing(&mut self, bracket: char) -> bool {
+        match pair::enclosing(&self.lines.rope, self.cursor_char(), bracket) {
+            Some(idx) => {
+                self.move_to_char(idx);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // the bracket under the cursor, or before it while inserting, and its match when that is on screen
+    pub fn bracket_pair(&self) -> Option<(usize, usize)> {
+        if matches!(self.buffer_type, BufferType::Directory | BufferType::Terminal) {
+            return None;
+        }
+
+        let rope = &self.lines.rope;
+        let (top, bottom) = (self.ptr_y, self.ptr_y + usize::from(self.size.1) + 1);
+        let end = if bottom >= rope.len_lines() { rope.len_chars() } else { rope.line_to_char(bottom) };
+        let shown = rope.line_to_char(min(top, rope.len_lines() - 1))..end;
+        let idx = self.cursor_char();
+        let before = idx.checked_sub(1).filter(|_| self.mode == Mode::Insert);
+
+        [Some(idx), before].into_iter()
+            .flatten()
+            .filter(|i| rope.get_char(*i).is_some_and(pair::is_bracket))
+            .find_map(|i| pair::bracket_match(rope, i, shown.clone()).map(|found| (i, found)))
+    }
+
+    // NOTE: diagnostic functions
+
+    // moves the diagnostics over the edits made since they were last moved
+    pub fn sync_diagnostics(&mut self) {
+        self.diagnostics.update(&self.lines.rope, self.revision);
+    }
+
+    // replaces what provider reported before, the ranges are for the text as it is now
+    pub fn set_diagnostics(&mut self, provider: &str, found: Vec<Diagnostic>) {
+        self.diagnostics.set(provider, &self.lines.rope, self.revision, found);
+    }
+
+    // moves to where the next diagnostic starts, or the one before, and gives its full message
+    pub fn jump_to_diagnostic(&mut self, forward: bool) -> Option<String> {
+        self.sync_diagnostics();
+        let idx = self.cursor_char();
+        let target = match forward {
+            true => self.diagnostics.next(idx),
+            false => self.diagnostics.prev(idx),
+        }?;
+
+        let line = self.lines.rope.char_to_line(target);
+        self.jump_to(line, target - self.lines.rope.line_to_char(line));
+        self.diagnostic_message()
+    }
+
+    // everything reported where the cursor is
+    pub fn diagnostic_message(&self) -> Option<String> {
+        let found = self.diagnostics.at(self.cursor_char());
+        match found.is_empty() {
+            true => None,
+            false => Some(found.iter().map(|d| d.display()).collect::<Vec<_>>().join("\n")),
+        }
+    }
+
+    // NOTE: snippet functions
+
+    // puts a snippet in place of what is between start and the cursor and moves to its first
+    // tabstop, its lines get the indent of the current one and its tabs the buffer's indent
+    pub fn insert_snippet(&mut self, start: usize, after: usize, body: &str) {
+        let line = self.current_line_text();
+        let indent: String = line.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+        let body = body.replace('\t', &indent::fill(0, self.options.indent_width, &self.options)).replace('\n', &format!("\n{}", indent));
+
+        let expansion = snippet::expand(&body, &|name| self.snippet_variable(name));
+        let start = min(start, self.cursor_char());
+        let end = self.after_cursor(after);
+
+        self.begin_edit();
+        self.lines.rope.remove(start..end);
+        self.lines.rope.insert(start, &expansion.text);
+        self.edit = None;
+        self.snippet = Some(Session::new(expansion, start));
+        self.snippet_stop(0);
+    }
+
+    // values for $TM_ variables, the names are the ones other editors use
+    fn snippet_variable(&self, name: &str) -> Option<String> {
+        let path = self.file.as_ref();
+        let line = self.current_line_text();
+        let col = self.cursor.current.0 + self.ptr_x;
+
+        match name {
+            "TM_FILENAME" => path?.file_name().map(|n| n.to_string_lossy().to_string()),
+            "TM_FILENAME_BASE" => path?.file_stem().map(|n| n.to_string_lossy().to_string()),
+            "TM_DIRECTORY" => path?.parent().map(|p| p.to_string_lossy().to_string()),
+            "TM_FILEPATH" => Some(path?.to_string_lossy().to_string()),
+            "TM_LINE_INDEX" => Some((self.cursor_line() - 1).to_string()),
+            "TM_LINE_NUMBER" => Some(self.cursor_line().to_string()),
+            "TM_CURRENT_LINE" => Some(line.trim_end_matches('\n').to_string()),
+            "TM_CURRENT_WORD" => {
+                let chars: Vec<char> = line.chars().collect();
+                let start = chars[..min(col, chars.len())].iter().rposition(|c| !is_word_char(*c)).map_or(0, |i| i + 1);
+                let end = chars[start..].iter().position(|c| !is_word_char(*c)).map_or(chars.len(), |i| start + i);
+                Some(chars[start..end].iter().collect())
+            },
+            _ => None,
+        }
+    }
+
+    // moves to the next tabstop or the one before, false when there is no snippet
+    pub fn snippet_jump(&mut self, forward: bool) -> bool {
+        let idx = match &self.snippet {
+            Some(session) if forward => session.current + 1,
+            Some(session) => session.current.saturating_sub(1),
+            None => return false,
+        };
+        self.snippet_stop(idx);
+        true
+    }
+
+    // reaching the last tabstop ends the snippet
+    fn snippet_stop(&mut self, idx: usize) {
+        let session = match self.snippet.as_mut() {
+            Some(session) => session,
+            None => return,
+        };
+        let target = session.jump_to(idx);
+        if session.is_last() {
+            self.snippet = None;
+        }
+        if let Some(target) = target {
+            self.move_to_char(target);
+        }
+    }
+
+    // where the tabstop at the cursor is and what it can be, when it is a choice
+    pub fn snippet_choices(&self) -> Option<(usize, Vec<String>)> {
+        let session = self.snippet.as_ref()?;
+        let stop = session.stop()?;
+        match stop.choices.is_empty() {
+            true => None,
+            false => Some((stop.ranges[0].0, stop.choices.clone())),
+        }
+    }
+
+    // typing over a placeholder removes it first, true when there was one
+    fn clear_placeholder(&mut self) -> bool {
+        let range = match self.snippet.as_mut() {
+            Some(session) if session.fresh => {
+                session.fresh = false;
+                session.range()
+            },
+            _ => None,
+        };
+        let (start, end) = match range {
+            Some(range) => range,
+            None => return false,
+        };
+
+        self.begin_edit();
+        self.lines.rope.remove(start..end);
+        self.edited(start, end - start, 0);
+        self.move_to_char(start);
+        true
+    }
+
+    // notes a change made by the key being typed, a key can make a few
+    fn edited(&mut self, at: usize, removed: usize, inserted: usize) {
+        let edit = Edit { at, removed, inserted };
+        self.edit = Some(self.edit.map_or(edit, |last| last.then(edit)));
+    }
+
+    // moves the tabstops over the last edit and copies the one typed in to its mirrors,
+    // an edit outside of it ends the snippet
+    pub fn sync_snippet(&mut self) {
+        let mut cursor = self.cursor_char();
+        let synced = match (self.snippet.as_mut(), self.edit.take()) {
+            (Some(session), Some(edit)) => session.sync(&mut self.lines.rope, &mut cursor, edit),
+            _ => return,
+        };
+
+        if !synced {
+            self.snippet = None;
+        } else if cursor != self.cursor_char() {
+            self.move_to_char(cursor);
+        }
+    }
+
+    pub fn move_to_char(&mut self, idx: usize) {
+        let line = self.lines.rope.char_to_line(idx);
+        self.jump_to(line, idx - self.lines.rope.line_to_char(line));
+    }
+
+    // the screen column the cursor is at, on a tab it sits at the end of it unless inserting
+    pub fn cursor_col(&self) -> usize {
+        let line = self.current_line_text();
+        let col = self.cursor.current.0 + self.ptr_x;
+        let tab_width = self.options.tab_width;
+
+        match line.chars().nth(col) {
+            Some('\t') if !matches!(self.mode, Mode::Insert | Mode::Replace) => indent::next_tab_stop(indent::display_col(&line, col, tab_width), tab_width) - 1,
+            _ => indent::display_col(&line, col, tab_width),
+        }
+    }
+
+    pub fn cursor_char(&self) -> usize {
+        let rope = &self.lines.rope;
+        let line = min(self.ptr_y + self.cursor.current.1, rope.len_lines() - 1);
+        let start = rope.line_to_char(line);
+        min(start + self.cursor.current.0, start + rope.line(line).len_chars())
+    }
+
+    // columns taken up before the text, directories have an icon in front of each entry
+    pub fn text_offset(&self) -> usize {
+        match self.buffer_type {
+            BufferType::Directory => 2,
+            _ => 0,
+        }
+    }
+
+    // name shown in buffer lists, relative to the working directory when possible
+    pub fn name(&self) -> String {
+        if let Some(terminal) = &self.terminal {
+            return format!("term://{}", terminal.command);
+        }
+
+        let path = match self.buffer_type {
+            BufferType::Directory => self.parent_dir.as_ref(),
+            _ => self.file.as_ref(),
+        };
+
+        match path {
+            Some(path) => display_name(path),
+            None => String::from("[No Name]"),
+        }
+    }
+
+    // line the cursor is on, 1 indexed
+    pub fn cursor_line(&self) -> usize {
+        self.ptr_y + self.cursor.current.1 + 1
     }
 
     pub fn refresh_buffer(&mut self) {
@@ -544,27 +1757,153 @@ impl Buffer {
         }
     }
 
-    // TODO: check file permissions
-    pub fn save(&self) -> String {
-        if self.buffer_type == BufferType::File {
-            if let Some(file) = &self.file {
-                let str = self.lines.rope.to_string();
-                let status = std::fs::write(file, str.as_bytes());
+    pub fn save(&mut self) -> Result<String, String> {
+        self.write(false)
+    }
 
-                return match status {
-                    Ok(_) => format!("Wrote {} bytes", str.len()),
-                    Err(_) => String::from("Writing to file didn't work")
-                };
-            } else {
-                return String::from("No file found");
-            }
+    // writes the buffer to its own file, read only files are only replaced when forced
+    pub fn write(&mut self, force: bool) -> Result<String, String> {
+        match self.buffer_type {
+            BufferType::Directory => return Err(String::from("Can't write to directory")),
+            BufferType::Terminal => return Err(String::from("Can't write a terminal, give it a file name")),
+            _ => {},
+        }
+
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => return Err(String::from("No file name")),
+        };
+
+        if !force && is_read_only(&file) {
+            return Err(format!("\"{}\" is read-only (add ! to override)", display_name(&file)));
+        }
+
+        let len = self.write_contents(&file)?;
+        self.saved_revision = self.revision;
+        self.buffer_type = BufferType::File;
+
+        Ok(format!("Wrote {} bytes", len))
+    }
+
+    // the formatter's command and the text it gets, None when the file type has none
+    pub fn formatter(&self) -> Option<(String, String)> {
+        let cmd = self.options.formatter.clone()?;
+        Some((cmd, self.lines.rope.to_string()))
+    }
+
+    // puts in what the formatter made of revision as one undo step, false when the
+    // buffer changed in the meantime or there was nothing to change
+    pub fn apply_format(&mut self, revision: usize, formatted: String) -> bool {
+        let text = self.lines.rope.to_string();
+        if revision != self.revision || formatted == text || formatted.is_empty() {
+            return false;
+        }
+
+        let line = self.ptr_y + self.cursor.current.1;
+        let offset = self.lines.rope.line_to_char(line) + self.cursor.current.0;
+        let offset = format::map_offset(&text, offset, &formatted);
+
+        // the screen stays where it was unless the cursor moved off of it
+        let ptr_y = self.ptr_y;
+        self.begin_edit();
+        self.lines.rope = Rope::from(formatted);
+        self.end_edit();
+
+        let line = self.lines.rope.char_to_line(offset);
+        let col = offset - self.lines.rope.line_to_char(line);
+        self.ptr_y = min(ptr_y, self.lines.rope.len_lines().saturating_sub(2));
+        self.jump_to(line, col);
+
+        true
+    }
+
+    // directory that has to be created before the buffer's file can be written
+    pub fn missing_parent(&self) -> Option<PathBuf> {
+        let parent = self.file.as_ref()?.parent()?;
+
+        if parent.as_os_str().is_empty() || parent.exists() {
+            None
+        } else {
+            Some(parent.to_path_buf())
+        }
+    }
+
+    pub fn create_parent_dirs(&self) -> Result<(), String> {
+        match self.missing_parent() {
+            Some(parent) => std::fs::create_dir_all(&parent)
+                .map_err(|err| format!("Can't create \"{}\": {}", display_name(&parent), err.kind())),
+            None => Ok(()),
+        }
+    }
+
+    // writes the contents to another file, the buffer keeps pointing at its own
+    pub fn write_to(&self, name: &str, force: bool) -> Result<String, String> {
+        let path = self.resolve_path(name);
+
+        if self.file.as_ref() != Some(&path) && path.exists() && !force {
+            return Err(format!("\"{}\" exists (add ! to override)", display_name(&path)));
+        }
+
+        let len = self.write_contents(&path)?;
+        Ok(format!("Wrote {} bytes to {}", len, display_name(&path)))
+    }
+
+    // writes the contents to another file and makes it the buffer's file
+    pub fn save_as(&mut self, name: &str, force: bool) -> Result<String, String> {
+        let message = self.write_to(name, force)?;
+
+        // a terminal only has its output copied out
+        if self.buffer_type == BufferType::Terminal {
+            return Ok(message);
+        }
+
+        self.file = Some(self.resolve_path(name));
+        self.buffer_type = BufferType::File;
+        self.saved_revision = self.revision;
+        self.detect_file_type();
+
+        Ok(message)
+    }
+
+    fn write_contents(&self, path: &Path) -> Result<usize, String> {
+        let mut str = self.lines.rope.to_string();
+        if self.empty_file && str == "\n" {
+            str.clear();
+        }
+
+        match write_atomic(path, str.as_bytes()) {
+            Ok(_) => Ok(str.len()),
+            Err(err) => Err(format!("Can't write \"{}\": {}", display_name(path), err.kind())),
+        }
+    }
+
+    // paths typed by the user are relative to the buffer, same as open
+    pub fn resolve_path(&self, name: &str) -> PathBuf {
+        match &self.parent_dir {
+            Some(parent_dir) => parent_dir.join(name),
+            None => PathBuf::from(name),
         }
-        return String::from("Can't write to directory")
     }
 
     #[warn(dead_code)]
     pub fn resize(&mut self, new_size: (u16, u16)) {
         self.size = new_size;
+
+        if let Some(terminal) = &mut self.terminal {
+            terminal.resize(terminal_size(new_size));
+        }
+    }
+
+    // copies what the terminal shows into the text, so normal mode can move through it
+    pub fn sync_terminal(&mut self) {
+        let (text, line) = match &self.terminal {
+            Some(terminal) => terminal.text(),
+            None => return,
+        };
+
+        self.lines.rope = Rope::from(text);
+        self.ptr_y = 0;
+        self.jump_to(line, 0);
     }
 
 
@@ -573,16 +1912,12 @@ impl Buffer {
         // FIX: set minimum, double check y value
         
         let y = min(y,
-                    self.lines.rope.len_lines()
-                        .checked_sub(2)
-                            .unwrap_or(0)
-                        .checked_sub(self.ptr_y)
-                            .unwrap_or(0)
+                    self.lines.rope.len_lines().saturating_sub(2).saturating_sub(self.ptr_y)
                 );
         self.cursor.current.1 = y;
         self.cursor.possible.1 = y;
 
-        let x = min(x, self.lines.rope.get_line(self.cursor.current.1 + self.ptr_y).unwrap().len_chars().checked_sub(1).unwrap_or(0)); // unchecked sub
+        let x = min(x, self.lines.rope.get_line(self.cursor.current.1 + self.ptr_y).unwrap().len_chars().saturating_sub(1)); // unchecked sub
         self.cursor.current.0 = x;
         self.cursor.possible.0 = x;
     }
@@ -598,13 +1933,15 @@ impl Buffer {
                     MouseButton::Left => {
 
                         if self.mode != Mode::Command {
+                            let offset = X_OFFSET + self.text_offset();
                             let mut x = click.column as usize;
-                            x = if x <= X_OFFSET { 0 } else { click.column as usize - X_OFFSET };
+                            x = if x <= offset { 0 } else { click.column as usize - offset };
 
                             // let y = max(click.column as usize + X_OFFSET, X_OFFSET);
                             let y = click.row as usize;
+                            let line = self.lines.rope.get_line(self.ptr_y + y).map_or(String::new(), |l| l.to_string());
 
-                            self.set_cursor(x, y);
+                            self.set_cursor(indent::char_col(&line, x, self.options.tab_width), y);
                         }
                     },
                 }
@@ -676,15 +2013,23 @@ impl <'a>Buffer {
         }
         */
 
-        let (line_string, text_string) = self.basic_text();
+        let (line_string, text_lines) = self.basic_text();
 
         let line_par = Paragraph::new(line_string)
                         .alignment(ratatui::layout::Alignment::Right)
                         .style(Style::default().fg(Color::DarkGray));
 
-        let text_par = Paragraph::new(text_string)
-                        .block(Block::default()
-                               .padding(Padding::new(1, 0, 0, 0)));
+        let text_par = match (&self.terminal, self.buffer_type) {
+            // the live screen while it takes keys
+            (Some(terminal), _) if self.mode == Mode::Insert => {
+                return (Paragraph::new(""), Paragraph::new(terminal.lines())
+                    .block(Block::default().padding(Padding::new(1, 0, 0, 0))));
+            },
+            (_, BufferType::Directory) => Paragraph::new(self.directory_text())
+                .block(Block::default().padding(Padding::new(1, 0, 0, 0))),
+            // the first column of each line is the sign column
+            _ => Paragraph::new(text_lines),
+        };
 
         (line_par, text_par) 
     }
@@ -747,9 +2092,51 @@ impl <'a>Buffer {
     }
     */
 
-    fn basic_text(&self) -> (String, String) {
-        let mut line_nums = "".to_string();
-        let mut text_string = "".to_string();
+    // listing with an icon in front of each entry, coloured by type
+    fn directory_text(&self) -> Vec<Line<'a>> {
+        // the rope ends in a newline, so its last line is always empty
+        let count = self.lines.rope.len_lines().saturating_sub(1 + self.ptr_y);
+        let height = usize::from(self.size.1);
+
+        self.lines.rope
+            .lines()
+            .skip(self.ptr_y)
+            .take(min(count, height + 1))
+            .map(|line| {
+                let line = line.to_string();
+                let name = line.trim_end_matches('\n').to_string();
+                let entry = self.entries.iter().find(|e| e.display() == name);
+
+                let (icon, color) = match entry {
+                    Some(entry) => {
+                        let color = match entry.kind {
+                            EntryKind::Directory => Color::Blue,
+                            EntryKind::Executable => Color::Green,
+                            EntryKind::Symlink => Color::Cyan,
+                            EntryKind::Other => Color::Yellow,
+                            EntryKind::File => Color::Reset,
+                        };
+                        (entry.icon(), color)
+                    },
+                    None if name == "./" || name == "../" => ('▸', Color::Blue),
+                    None => (' ', Color::Reset),
+                };
+
+                Line::from(vec![
+                    Span::styled(format!("{} ", icon), Style::default().fg(color)),
+                    Span::styled(name, Style::default().fg(color)),
+                ])
+            })
+            .collect()
+    }
+
+    fn basic_text(&self) -> (Vec<Line<'a>>, Vec<Line<'a>>) {
+        let mut line_nums = vec![];
+        let mut text_lines = vec![];
+        let signs = self.diagnostics.signs();
+        let pair = self.bracket_pair().map_or(vec![], |(a, b)| vec![a, b]);
+        let lines = self.visual_lines();
+        let cols = self.visual_cols().unwrap_or((0, usize::MAX));
 
         for (i, line) in self.lines.rope.lines().skip(self.ptr_y).enumerate() {
             if i > self.ptr_y + usize::from(self.size.1) ||
@@ -758,7 +2145,7 @@ impl <'a>Buffer {
             }
 
             let mut i_str: String;
-            let current_line = usize::from(self.cursor.current.1);
+            let current_line = self.cursor.current.1;
 
             if current_line != i {
                 if current_line > i {
@@ -774,15 +2161,110 @@ impl <'a>Buffer {
                 }
             }
 
-            i_str.push_str("\n\r");
+            // lines with diagnostics get their marker in the column between the number and the text
+            let sign = match signs.get(&(self.ptr_y + i)) {
+                Some(severity) => {
+                    let color = severity_color(*severity);
+                    line_nums.push(Line::styled(i_str, Style::default().fg(color)));
+                    Span::styled(severity.marker().to_string(), Style::default().fg(color))
+                },
+                None => {
+                    line_nums.push(Line::from(i_str));
+                    Span::raw(" ")
+                },
+            };
 
-            for char in i_str.chars() {
-                line_nums.push(char);
-            }
+            let selected = lines.filter(|(start, end)| (*start..=*end).contains(&(self.ptr_y + i))).map(|_| cols);
+            let mut text = self.diagnostic_line(self.ptr_y + i, line.to_string(), &pair, selected);
+            text.spans.insert(0, sign);
+            text_lines.push(text);
+        }
+
+        (line_nums, text_lines)
+    }
 
-            text_string.push_str(&line.to_string());
+    // a line with the diagnostics on it underlined and the worst one's message after it,
+    // the brackets in pair and the columns selected in visual mode are marked
+    fn diagnostic_line(&self, line: usize, mut text: String, pair: &[usize], selected: Option<(usize, usize)>) -> Line<'a> {
+        if text.ends_with('\n') {
+            text.pop();
         }
 
-        (line_nums, text_string)
+        let tab_width = self.options.tab_width;
+        let start = self.lines.rope.line_to_char(line);
+        let end = start + text.chars().count();
+        let found = self.diagnostics.on_line(line);
+        if found.is_empty() && selected.is_none() && !pair.iter().any(|i| (start..end).contains(i)) {
+            return Line::from(indent::expand_tabs(&text, tab_width));
+        }
+
+        let mut spans = vec![];
+        let mut run = String::new();
+        let mut run_style = (None, false, false);
+        let mut shown = 0;
+
+        for (col, c) in text.chars().enumerate() {
+            let severity = found.iter()
+                .filter(|d| d.covers(start + col))
+                .map(|d| d.severity)
+                .max();
+            let chosen = selected.is_some_and(|(from, to)| (from..=to).contains(&col));
+            let style = (severity, pair.contains(&(start + col)), chosen);
+            if style != run_style && !run.is_empty() {
+                spans.push(styled(std::mem::take(&mut run), run_style));
+            }
+            run_style = style;
+            match c {
+                '\t' => {
+                    let next = indent::next_tab_stop(shown, tab_width);
+                    run.push_str(&" ".repeat(next - shown));
+                    shown = next;
+                },
+                c => {
+                    run.push(c);
+                    shown += 1;
+                },
+            }
+        }
+        if !run.is_empty() {
+            spans.push(styled(run, run_style));
+        }
+
+        let worst = match found.first() {
+            Some(worst) => *worst,
+            None => return Line::from(spans),
+        };
+        let message = worst.message.lines().next().unwrap_or("");
+        spans.push(Span::styled(
+            format!("   {} {}", worst.severity.marker(), message),
+            Style::default().fg(severity_color(worst.severity)).add_modifier(Modifier::DIM),
+        ));
+        Line::from(spans)
+    }
+}
+
+pub fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Error => Color::Red,
+        Severity::Warning => Color::Yellow,
+        Severity::Info => Color::Blue,
     }
 }
+
+// text underlined in the colour of a diagnostic's severity, and marked when it is a bracket of the pair
+fn styled<'a>(text: String, (severity, paired, selected): (Option<Severity>, bool, bool)) -> Span<'a> {
+    let style = match severity {
+        Some(severity) => Style::default().fg(severity_color(severity)).add_modifier(Modifier::UNDERLINED),
+        None => Style::default(),
+    };
+    let style = if selected { style.bg(Color::Blue) } else { style };
+    match paired {
+        true => Span::styled(text, style.bg(Color::DarkGray).add_modifier(Modifier::BOLD)),
+        false => Span::styled(text, style),
+    }
+}
+
+// the terminal gets the window less the padding in front of the text
+fn terminal_size(window_size: (u16, u16)) -> (u16, u16) {
+    (window_size.0.saturating_sub(1), window_size.1)
+}
@@ -1,8 +1,34 @@
+// a line given to a command, like 12, ., $, 'a or .+2
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineRef {
+    Number(usize), // from 1
+    Current,
+    Last,
+    Mark(char),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Address {
+    pub line: LineRef,
+    pub offset: isize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Range {
+    Whole,
+    Lines(Address, Address),
+}
+
 pub enum CommandKey {
-    Save,
-    Quit,
+    Save { path: Option<String>, force: bool },
+    SaveAs { path: String, force: bool },
+    WriteCommand(String),
+    Edit { path: Option<String>, force: bool },
+    EditNew,
+    Quit { force: bool },
+    QuitAll { force: bool },
     Line(usize),
-    SaveAndQuit,
+    SaveAndQuit { force: bool },
     Logger,
     Send(String),
     History,
@@ -16,34 +42,127 @@ pub enum CommandKey {
     WinCount,
     */
     BufCount,
+    ListBuffers,
+    Buffer(String),
+    DeleteBuf { target: Option<String>, force: bool },
+    // only for directory buffers
+    Create(String),
+    Rename(String),
+    Copy(String),
+    Delete,
+    Chmod(String),
+    ToggleTree,
+    RevealInTree,
+    Find(String),
+    FindBuffer,
+    FindHistory,
+    Marks,
+    Grep { pattern: String, path: Option<String> },
+    QuickfixNext,
+    QuickfixPrev,
+    QuickfixOpen,
+    QuickfixClose,
+    QuickfixDo(String),
+    Make(String),
+    Terminal(String),
+    Shell(String),
+    // below the last line of the range, or the cursor's
+    ReadCommand { range: Option<Range>, cmd: String },
+    ReadFile { range: Option<Range>, path: String },
+    Filter { range: Range, cmd: String },
+    MakePrg(String),
+    ErrorFormat(String),
+    Format,
+    FormatPrg(String),
+    FileType(String),
+    Set(String),
+    Hover,
+    Definition,
+    References,
+    LspRename(String),
+    CodeAction(String),
+    Diagnostics,
+    LspInfo,
+    // on the line under the cursor
+    Substitute { pattern: String, replacement: String, global: bool },
 }
 
 pub struct Command {
     pub text: String,
     pub history: Vec<String>,
-    pub ptr: usize
+    pub ptr: usize,
+    completions: Vec<String>,
+    completion_ptr: usize,
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Command {
     pub fn new() -> Self {
-        Command { text: String::new(), history: vec![], ptr: 0 }
+        Command { text: String::new(), history: vec![], ptr: 0, completions: vec![], completion_ptr: 0 }
     }
 
     pub fn confirm(&mut self) -> Option<CommandKey>{
         let command = self.text.clone();
-        self.text.clear();
+        self.clear();
+
+        let ck = Command::parse(&command);
+        self.history.push(command);
 
+        ck
+    }
+
+    // what a line of command text means, without touching the history
+    pub fn parse(command: &str) -> Option<CommandKey> {
         let ck: Option<CommandKey>;
 
         if let Ok(number) = command.parse::<usize>() {
             ck = Some(CommandKey::Line(number));
+        } else if let Some(cmd) = command.strip_prefix('!') {
+            ck = Some(CommandKey::Shell(cmd.trim().to_string()));
+        } else if let Some((range, rest)) = parse_range(command) {
+            // only filters and reads take a range for now
+            ck = match rest.strip_prefix('!') {
+                Some(cmd) => Some(CommandKey::Filter { range, cmd: cmd.trim().to_string() }),
+                None => parse_read(rest, Some(range)),
+            };
         } else {
             let args: Vec<&str> = command.split(' ').collect();
+            let name = *args.first().expect("");
+            let (name, force) = match name.strip_suffix('!') {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+
+            ck = match name {
+                "wq" | "x" => Some(CommandKey::SaveAndQuit { force }),
+                "q" | "quit" => Some(CommandKey::Quit { force }),
+                "qa" | "qall" | "quitall" => Some(CommandKey::QuitAll { force }),
+                "w" | "write" => {
+                    let arg = args[1..].join(" ");
 
-            ck = match *args.get(0).expect("") {
-                "wq" | "x" => Some(CommandKey::SaveAndQuit),
-                "q" => Some(CommandKey::Quit),
-                "w" => Some(CommandKey::Save),
+                    if let Some(cmd) = arg.strip_prefix('!') {
+                        Some(CommandKey::WriteCommand(cmd.to_string()))
+                    } else if arg.is_empty() {
+                        Some(CommandKey::Save { path: None, force })
+                    } else {
+                        Some(CommandKey::Save { path: Some(arg), force })
+                    }
+                },
+                "e" | "edit" => {
+                    let path = args[1..].join(" ");
+                    let path = if path.is_empty() { None } else { Some(path) };
+                    Some(CommandKey::Edit { path, force })
+                },
+                "ene" | "enew" => Some(CommandKey::EditNew),
+                "sav" | "saveas" => {
+                    let path = args[1..].join(" ");
+                    Some(CommandKey::SaveAs { path, force })
+                },
                 "history" => Some(CommandKey::History),
                 "logger" => Some(CommandKey::Logger),
                 "send" => {
@@ -54,6 +173,62 @@ impl Command {
                 "bufp" => Some(CommandKey::PrevBuf),
                 "newbuf" => Some(CommandKey::NewBuf),
                 "bufcount" => Some(CommandKey::BufCount),
+                "ls" | "buffers" | "files" => Some(CommandKey::ListBuffers),
+                "b" | "buffer" => Some(CommandKey::Buffer(args[1..].join(" "))),
+                "bd" | "bdelete" | "bw" | "bwipeout" => {
+                    let target = args[1..].join(" ");
+                    let target = if target.is_empty() { None } else { Some(target) };
+                    Some(CommandKey::DeleteBuf { target, force })
+                },
+                "create" | "touch" => Some(CommandKey::Create(args[1..].join(" "))),
+                "mkdir" => {
+                    let name = args[1..].join(" ");
+                    let name = if name.is_empty() || name.ends_with('/') { name } else { format!("{}/", name) };
+                    Some(CommandKey::Create(name))
+                },
+                "rename" | "mv" => Some(CommandKey::Rename(args[1..].join(" "))),
+                "copy" | "cp" => Some(CommandKey::Copy(args[1..].join(" "))),
+                "delete" | "rm" => Some(CommandKey::Delete),
+                "chmod" => Some(CommandKey::Chmod(args[1..].join(" "))),
+                "tree" => Some(CommandKey::ToggleTree),
+                "treefind" => Some(CommandKey::RevealInTree),
+                "find" => Some(CommandKey::Find(args[1..].join(" "))),
+                "findbuf" => Some(CommandKey::FindBuffer),
+                "findhist" => Some(CommandKey::FindHistory),
+                "marks" => Some(CommandKey::Marks),
+                "gr" | "grep" => {
+                    let (pattern, path) = split_pattern(&args[1..].join(" "));
+                    if pattern.is_empty() {
+                        None
+                    } else {
+                        Some(CommandKey::Grep { pattern, path })
+                    }
+                },
+                "cn" | "cnext" => Some(CommandKey::QuickfixNext),
+                "cN" | "cprev" | "cprevious" => Some(CommandKey::QuickfixPrev),
+                "cope" | "copen" => Some(CommandKey::QuickfixOpen),
+                "ccl" | "cclose" => Some(CommandKey::QuickfixClose),
+                "r" | "read" => parse_read(command, None),
+                "cdo" => Some(CommandKey::QuickfixDo(args[1..].join(" "))),
+                "term" | "terminal" => Some(CommandKey::Terminal(args[1..].join(" "))),
+                "mak" | "make" => Some(CommandKey::Make(args[1..].join(" "))),
+                "makeprg" => Some(CommandKey::MakePrg(args[1..].join(" "))),
+                "efm" | "errorformat" => Some(CommandKey::ErrorFormat(args[1..].join(" "))),
+                "format" => Some(CommandKey::Format),
+                "formatprg" => Some(CommandKey::FormatPrg(args[1..].join(" "))),
+                "ft" | "filetype" | "setf" | "setfiletype" => Some(CommandKey::FileType(args[1..].join(" "))),
+                "se" | "set" => Some(CommandKey::Set(args[1..].join(" "))),
+                "hover" => Some(CommandKey::Hover),
+                "def" | "definition" => Some(CommandKey::Definition),
+                "refs" | "references" => Some(CommandKey::References),
+                "lsprename" => Some(CommandKey::LspRename(args[1..].join(" "))),
+                "ca" | "codeaction" => Some(CommandKey::CodeAction(args[1..].join(" "))),
+                "diagnostics" => Some(CommandKey::Diagnostics),
+                "lsp" => Some(CommandKey::LspInfo),
+                // the pattern follows the name straight away, like s/a/b/
+                _ if matches!(name.split(|c: char| !c.is_ascii_alphabetic()).next(), Some("s" | "substitute")) => {
+                    parse_substitute(command)
+                },
                 /*
                 "winn" => Some(CommandKey::NextWin),
                 "winp" => Some(CommandKey::PrevWin),
@@ -63,12 +238,383 @@ impl Command {
                 _ => None
             };
         }
-        self.history.push(command);
 
         ck
     }
 
     pub fn clear(&mut self) {
         self.text.clear();
+        self.reset_completion();
+    }
+
+    // name of the command being typed, without a trailing !
+    pub fn name(&self) -> &str {
+        let name = self.text.split(' ').next().unwrap_or("");
+        name.strip_suffix('!').unwrap_or(name)
+    }
+
+    // cycles the argument of the command through the candidates that match it,
+    // prefix matches come before matches anywhere in the candidate
+    pub fn complete(&mut self, candidates: &[String]) {
+        let (head, arg) = match self.text.split_once(' ') {
+            Some((head, arg)) => (head.to_string(), arg.to_string()),
+            None => return,
+        };
+
+        if self.completions.is_empty() {
+            let mut matches: Vec<String> = candidates.iter()
+                .filter(|c| c.starts_with(&arg))
+                .cloned()
+                .collect();
+            matches.extend(candidates.iter()
+                .filter(|c| !c.starts_with(&arg) && c.contains(&arg))
+                .cloned());
+
+            if matches.is_empty() {
+                return;
+            }
+
+            self.completions = matches;
+            self.completion_ptr = 0;
+        } else {
+            self.completion_ptr = (self.completion_ptr + 1) % self.completions.len();
+        }
+
+        self.text = format!("{} {}", head, self.completions[self.completion_ptr]);
+    }
+
+    pub fn reset_completion(&mut self) {
+        self.completions.clear();
+        self.completion_ptr = 0;
+    }
+}
+
+// a line number, ., $ or 'a, followed by any number of +n and -n,
+// a bare offset is from the current line
+fn parse_address(text: &str) -> Option<(Address, &str)> {
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let (line, mut rest) = if digits > 0 {
+        (LineRef::Number(text[..digits].parse().ok()?), &text[digits..])
+    } else if let Some(rest) = text.strip_prefix('.') {
+        (LineRef::Current, rest)
+    } else if let Some(rest) = text.strip_prefix('$') {
+        (LineRef::Last, rest)
+    } else if let Some(rest) = text.strip_prefix('\'') {
+        let mark = rest.chars().next().filter(|c| c.is_ascii_alphabetic())?;
+        (LineRef::Mark(mark), &rest[1..])
+    } else if text.starts_with(['+', '-']) {
+        (LineRef::Current, text)
+    } else {
+        return None;
+    };
+
+    let mut offset = 0;
+    while let Some(sign) = rest.chars().next().filter(|c| *c == '+' || *c == '-') {
+        let number = &rest[1..];
+        let digits = number.len() - number.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let n: isize = if digits == 0 { 1 } else { number[..digits].parse().ok()? };
+
+        offset += if sign == '+' { n } else { -n };
+        rest = &number[digits..];
+    }
+
+    Some((Address { line, offset }, rest))
+}
+
+// :r !cmd or :r file
+fn parse_read(command: &str, range: Option<Range>) -> Option<CommandKey> {
+    let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+    if name != "r" && name != "read" {
+        return None;
+    }
+
+    match arg.strip_prefix('!') {
+        Some(cmd) => Some(CommandKey::ReadCommand { range, cmd: cmd.trim().to_string() }),
+        None => Some(CommandKey::ReadFile { range, path: arg.to_string() }),
+    }
+}
+
+// % or one or two addresses split by a comma, with what comes after them
+fn parse_range(text: &str) -> Option<(Range, &str)> {
+    if let Some(rest) = text.strip_prefix('%') {
+        return Some((Range::Whole, rest));
+    }
+
+    let (start, rest) = parse_address(text)?;
+    match rest.strip_prefix(',') {
+        Some(rest) => {
+            let (end, rest) = parse_address(rest)?;
+            Some((Range::Lines(start, end), rest))
+        },
+        None => Some((Range::Lines(start, start), rest)),
+    }
+}
+
+// splits a pattern from the path after it, the pattern can be quoted to hold spaces
+fn split_pattern(arg: &str) -> (String, Option<String>) {
+    let arg = arg.trim();
+
+    let (pattern, rest) = match arg.chars().next() {
+        Some(quote @ ('"' | '\'')) => match arg[1..].find(quote) {
+            Some(end) => (&arg[1..end + 1], &arg[end + 2..]),
+            None => (&arg[1..], ""),
+        },
+        _ => arg.split_once(' ').unwrap_or((arg, "")),
+    };
+
+    let rest = rest.trim();
+    (pattern.to_string(), if rest.is_empty() { None } else { Some(rest.to_string()) })
+}
+
+// s/pattern/replacement/g, any punctuation can stand in for the slashes,
+// \1 in the replacement is the first group
+fn parse_substitute(command: &str) -> Option<CommandKey> {
+    let command = command.strip_prefix("substitute").or_else(|| command.strip_prefix('s'))?;
+    let delim = command.chars().next().filter(|c| c.is_ascii_punctuation() && *c != '\\')?;
+
+    let mut parts = vec![String::new()];
+    let mut chars = command[1..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c) if c == delim => parts.last_mut()?.push(c),
+                Some(c) => {
+                    parts.last_mut()?.push('\\');
+                    parts.last_mut()?.push(c);
+                },
+                None => parts.last_mut()?.push('\\'),
+            },
+            c if c == delim && parts.len() < 3 => parts.push(String::new()),
+            c => parts.last_mut()?.push(c),
+        }
+    }
+
+    let pattern = parts.first().filter(|p| !p.is_empty())?.clone();
+    let replacement = parts.get(1).cloned().unwrap_or_default();
+    let flags = parts.get(2).cloned().unwrap_or_default();
+    if flags.chars().any(|c| c != 'g') {
+        return None;
+    }
+
+    // \1 becomes ${1} for the regex crate, a bare $ stays as it is
+    let mut rep = String::new();
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                rep.push_str(&format!("${{{}}}", chars.next()?));
+            },
+            '\\' if chars.peek() == Some(&'&') => {
+                chars.next();
+                rep.push('&');
+            },
+            '&' => rep.push_str("${0}"),
+            '$' => rep.push_str("$$"),
+            c => rep.push(c),
+        }
+    }
+
+    Some(CommandKey::Substitute { pattern, replacement: rep, global: flags.contains('g') })
+}
+
+
+#[test]
+fn test_buffer_commands() {
+    let mut command = Command::new();
+
+    command.text = String::from("ls");
+    assert!(matches!(command.confirm(), Some(CommandKey::ListBuffers)));
+
+    command.text = String::from("b 2");
+    assert!(matches!(command.confirm(), Some(CommandKey::Buffer(target)) if target == "2"));
+
+    command.text = String::from("bd!");
+    assert!(matches!(command.confirm(), Some(CommandKey::DeleteBuf { target: None, force: true })));
+
+    command.text = String::from("bw main");
+    assert!(matches!(command.confirm(), Some(CommandKey::DeleteBuf { target: Some(t), force: false }) if t == "main"));
+}
+
+#[test]
+fn test_quit_commands() {
+    let mut command = Command::new();
+
+    command.text = String::from("q");
+    assert!(matches!(command.confirm(), Some(CommandKey::Quit { force: false })));
+
+    command.text = String::from("q!");
+    assert!(matches!(command.confirm(), Some(CommandKey::Quit { force: true })));
+
+    command.text = String::from("qa!");
+    assert!(matches!(command.confirm(), Some(CommandKey::QuitAll { force: true })));
+}
+
+#[test]
+fn test_write_commands() {
+    let mut command = Command::new();
+
+    command.text = String::from("w");
+    assert!(matches!(command.confirm(), Some(CommandKey::Save { path: None, force: false })));
+
+    command.text = String::from("w! other.rs");
+    assert!(matches!(command.confirm(), Some(CommandKey::Save { path: Some(p), force: true }) if p == "other.rs"));
+
+    command.text = String::from("w !wc -l");
+    assert!(matches!(command.confirm(), Some(CommandKey::WriteCommand(cmd)) if cmd == "wc -l"));
+
+    command.text = String::from("saveas new.rs");
+    assert!(matches!(command.confirm(), Some(CommandKey::SaveAs { path, force: false }) if path == "new.rs"));
+}
+
+#[test]
+fn test_edit_commands() {
+    let mut command = Command::new();
+
+    command.text = String::from("e src/new.rs");
+    assert!(matches!(command.confirm(), Some(CommandKey::Edit { path: Some(p), force: false }) if p == "src/new.rs"));
+
+    command.text = String::from("e!");
+    assert!(matches!(command.confirm(), Some(CommandKey::Edit { path: None, force: true })));
+
+    command.text = String::from("enew");
+    assert!(matches!(command.confirm(), Some(CommandKey::EditNew)));
+}
+
+#[test]
+fn test_complete() {
+    let candidates = vec![
+        "src/main.rs".to_string(),
+        "main.rs".to_string(),
+        "README.md".to_string(),
+    ];
+    let mut command = Command::new();
+
+    command.text = String::from("b main");
+    command.complete(&candidates);
+    assert_eq!(command.text, "b main.rs");
+    command.complete(&candidates);
+    assert_eq!(command.text, "b src/main.rs");
+    command.complete(&candidates);
+    assert_eq!(command.text, "b main.rs");
+
+    command.reset_completion();
+    command.text = String::from("b nothing");
+    command.complete(&candidates);
+    assert_eq!(command.text, "b nothing");
+}
+
+#[test]
+fn test_explorer_commands() {
+    let mut command = Command::new();
+
+    command.text = String::from("create src/main.rs");
+    assert!(matches!(command.confirm(), Some(CommandKey::Create(name)) if name == "src/main.rs"));
+
+    command.text = String::from("mkdir docs");
+    assert!(matches!(command.confirm(), Some(CommandKey::Create(name)) if name == "docs/"));
+
+    command.text = String::from("rename new name.txt");
+    assert!(matches!(command.confirm(), Some(CommandKey::Rename(name)) if name == "new name.txt"));
+
+    // the symbol rename has its own name so :rename stays the explorer's
+    command.text = String::from("lsprename assist");
+    assert!(matches!(command.confirm(), Some(CommandKey::LspRename(name)) if name == "assist"));
+
+    command.text = String::from("rm");
+    assert!(matches!(command.confirm(), Some(CommandKey::Delete)));
+
+    command.text = String::from("chmod 755");
+    assert!(matches!(command.confirm(), Some(CommandKey::Chmod(mode)) if mode == "755"));
+
+    command.text = String::from("find main rs");
+    assert!(matches!(command.confirm(), Some(CommandKey::Find(query)) if query == "main rs"));
+
+    command.text = String::from("treefind");
+    assert!(matches!(command.confirm(), Some(CommandKey::RevealInTree)));
+}
+
+#[test]
+fn test_quickfix_commands() {
+    let mut command = Command::new();
+
+    command.text = String::from("grep fn\\s+main src");
+    assert!(matches!(command.confirm(), Some(CommandKey::Grep { pattern, path: Some(p) }) if pattern == "fn\\s+main" && p == "src"));
+
+    command.text = String::from("grep \"two words\"");
+    assert!(matches!(command.confirm(), Some(CommandKey::Grep { pattern, path: None }) if pattern == "two words"));
+
+    command.text = String::from("grep");
+    assert!(command.confirm().is_none());
+
+    command.text = String::from("cn");
+    assert!(matches!(command.confirm(), Some(CommandKey::QuickfixNext)));
+
+    command.text = String::from("make --release");
+    assert!(matches!(command.confirm(), Some(CommandKey::Make(args)) if args == "--release"));
+
+    command.text = String::from("makeprg cargo check --message-format=json");
+    assert!(matches!(command.confirm(), Some(CommandKey::MakePrg(cmd)) if cmd == "cargo check --message-format=json"));
+
+    command.text = String::from("formatprg prettier --stdin-filepath %");
+    assert!(matches!(command.confirm(), Some(CommandKey::FormatPrg(cmd)) if cmd == "prettier --stdin-filepath %"));
+
+    command.text = String::from("cdo s/a/b/");
+    assert!(matches!(command.confirm(), Some(CommandKey::QuickfixDo(cmd)) if cmd == "s/a/b/"));
+    assert_eq!(command.history.last().unwrap(), "cdo s/a/b/");
+}
+
+#[test]
+fn test_substitute_command() {
+    let parse = |text| match Command::parse(text) {
+        Some(CommandKey::Substitute { pattern, replacement, global }) => Some((pattern, replacement, global)),
+        _ => None,
+    };
+
+    assert_eq!(parse("s/foo/bar/"), Some(("foo".into(), "bar".into(), false)));
+    assert_eq!(parse("s#a/b#c#g"), Some(("a/b".into(), "c".into(), true)));
+    assert_eq!(parse("s/(\\w+) (\\w+)/\\2 \\1"), Some(("(\\w+) (\\w+)".into(), "${2} ${1}".into(), false)));
+    assert_eq!(parse("s/a\\/b/$&/"), Some(("a/b".into(), "$$${0}".into(), false)));
+    assert_eq!(parse("s//x/"), None);
+    assert_eq!(parse("sfoo"), None);
+    assert_eq!(parse("substitute/a/b/"), Some(("a".into(), "b".into(), false)));
+    assert!(Command::parse("sort/a/").is_none());
+    assert!(Command::parse("set/x/").is_none());
+}
+
+#[test]
+fn test_shell_commands() {
+    let address = |line, offset| Address { line, offset };
+
+    assert!(matches!(Command::parse("!ls -la"), Some(CommandKey::Shell(cmd)) if cmd == "ls -la"));
+    assert!(matches!(Command::parse("r !date"), Some(CommandKey::ReadCommand { range: None, cmd }) if cmd == "date"));
+    assert!(matches!(Command::parse("read notes.txt"), Some(CommandKey::ReadFile { range: None, path }) if path == "notes.txt"));
+    let last = Range::Lines(address(LineRef::Last, 0), address(LineRef::Last, 0));
+    assert!(matches!(Command::parse("$r !date"), Some(CommandKey::ReadCommand { range: Some(r), cmd }) if r == last && cmd == "date"));
+    assert!(matches!(Command::parse("$read a.txt"), Some(CommandKey::ReadFile { range: Some(r), .. }) if r == last));
+    assert!(Command::parse("$rx").is_none());
+
+    assert!(matches!(Command::parse("%!sort"), Some(CommandKey::Filter { range: Range::Whole, cmd }) if cmd == "sort"));
+    match Command::parse(".,.+2!jq .") {
+        Some(CommandKey::Filter { range, cmd }) => {
+            assert_eq!(range, Range::Lines(address(LineRef::Current, 0), address(LineRef::Current, 2)));
+            assert_eq!(cmd, "jq .");
+        },
+        _ => panic!("not a filter"),
     }
+    match Command::parse("3,$-1! sort -r") {
+        Some(CommandKey::Filter { range, cmd }) => {
+            assert_eq!(range, Range::Lines(address(LineRef::Number(3), 0), address(LineRef::Last, -1)));
+            assert_eq!(cmd, "sort -r");
+        },
+        _ => panic!("not a filter"),
+    }
+    assert!(matches!(
+        Command::parse("'a,'b!tac"),
+        Some(CommandKey::Filter { range: Range::Lines(Address { line: LineRef::Mark('a'), .. }, Address { line: LineRef::Mark('b'), .. }), .. })
+    ));
+
+    // plain numbers still go to a line
+    assert!(matches!(Command::parse("12"), Some(CommandKey::Line(12))));
+    assert!(Command::parse("1,2").is_none());
 }
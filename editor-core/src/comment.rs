@@ -0,0 +1,109 @@
+use crate::indent;
+
+/*
+ * commenting lines in and out for gc
+ *
+ * a file type with a line comment gets it in front of each line, one without
+ * gets its block comment around each line instead. the comment goes at the
+ * indent of the least indented line, so a block stays lined up when some lines
+ * are indented with tabs and some with spaces
+ *
+ * when every line with text on it is commented already they are all taken out
+ * instead, blank lines are left alone either way
+ */
+
+// how the file type comments, the block comment's end is None for a line comment
+pub struct Marker<'a> {
+    pub start: &'a str,
+    pub end: Option<&'a str>,
+}
+
+impl Marker<'_> {
+    fn is_commented(&self, line: &str) -> bool {
+        let text = line.trim();
+        text.starts_with(self.start) && self.end.is_none_or(|end| text.len() >= self.start.len() + end.len() && text.ends_with(end))
+    }
+
+    fn uncomment(&self, line: &str) -> String {
+        let indent = indent::leading(line);
+        let newline = if line.ends_with('\n') { "\n" } else { "" };
+        let text = line[indent.len()..].trim_end_matches('\n');
+
+        let text = text.strip_prefix(self.start).unwrap_or(text);
+        let text = text.strip_prefix(' ').unwrap_or(text);
+        let text = match self.end {
+            Some(end) => {
+                let text = text.trim_end().strip_suffix(end).unwrap_or(text);
+                text.strip_suffix(' ').unwrap_or(text)
+            },
+            None => text,
+        };
+        format!("{}{}{}", indent, text, newline)
+    }
+
+    // the comment goes in at the first char that is at least width columns in
+    fn comment(&self, line: &str, width: usize, tab_width: usize) -> String {
+        let indent = indent::leading(line);
+        let at = (0..=indent.chars().count())
+            .find(|i| indent::display_col(indent, *i, tab_width) >= width)
+            .unwrap_or(indent.chars().count());
+        let at = indent.char_indices().nth(at).map_or(indent.len(), |(i, _)| i);
+
+        let newline = if line.ends_with('\n') { "\n" } else { "" };
+        let text = line[at..].trim_end_matches('\n');
+        match self.end {
+            Some(end) => format!("{}{} {} {}{}", &line[..at], self.start, text, end, newline),
+            None => format!("{}{} {}{}", &line[..at], self.start, text, newline),
+        }
+    }
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+// whether the lines with text on them are all commented
+pub fn all_commented(lines: &[String], marker: &Marker) -> bool {
+    lines.iter().filter(|l| !is_blank(l)).all(|l| marker.is_commented(l))
+}
+
+// the lines commented, or uncommented when they all are already
+pub fn toggle(lines: &[String], marker: &Marker, tab_width: usize) -> Vec<String> {
+    if all_commented(lines, marker) {
+        return lines.iter()
+            .map(|l| if is_blank(l) { l.clone() } else { marker.uncomment(l) })
+            .collect();
+    }
+
+    let width = lines.iter()
+        .filter(|l| !is_blank(l))
+        .map(|l| indent::indent_of(l, tab_width))
+        .min()
+        .unwrap_or(0);
+    lines.iter()
+        .map(|l| if is_blank(l) { l.clone() } else { marker.comment(l, width, tab_width) })
+        .collect()
+}
+
+
+#[test]
+fn test_toggle() {
+    let lines = |text: &str| text.split_inclusive('\n').map(String::from).collect::<Vec<String>>();
+    let slashes = Marker { start: "//", end: None };
+
+    let code = lines("    if x {\n\t\ty();\n\n    }\n");
+    let commented = toggle(&code, &slashes, 4);
+    assert_eq!(commented.concat(), "    // if x {\n\t// \ty();\n\n    // }\n");
+    assert_eq!(toggle(&commented, &slashes, 4), code);
+
+    // one line without a comment gets the whole block commented again
+    let mixed = lines("// a\nb\n");
+    assert_eq!(toggle(&mixed, &slashes, 4).concat(), "// // a\n// b\n");
+    assert_eq!(toggle(&lines("  //a\n"), &slashes, 4).concat(), "  a\n");
+
+    let block = Marker { start: "/*", end: Some("*/") };
+    let css = lines("a { color: red; }\n  b {}\n");
+    let commented = toggle(&css, &block, 8);
+    assert_eq!(commented.concat(), "/* a { color: red; } */\n/*   b {} */\n");
+    assert_eq!(toggle(&commented, &block, 8), css);
+}
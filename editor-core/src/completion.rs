@@ -0,0 +1,263 @@
+use crate::file::complete_path;
+use crate::picker::{fuzzy_match, Match};
+use ropey::Rope;
+use std::collections::HashSet;
+use std::path::Path;
+
+/*
+ * insert mode completion
+ *
+ * candidates come from sources, the words in open buffers, file names, snippets
+ * and a language server, and are narrowed with the picker's fuzzy match against
+ * what was typed since the completion started
+ *
+ * the language server answers later than the rest, its items are added to an
+ * open completion when they arrive
+ */
+
+// most distinct words taken from the buffers
+const MAX_WORDS: usize = 5_000;
+
+// rows the popup shows at once
+pub const MAX_ROWS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Words,
+    Paths,
+    Snippets,
+    Lsp,
+}
+
+impl Source {
+    pub fn name(&self) -> &str {
+        match self {
+            Source::Words => "word",
+            Source::Paths => "path",
+            Source::Snippets => "snippet",
+            Source::Lsp => "lsp",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub label: String, // what gets matched and shown
+    pub insert: String, // what replaces the typed text
+    pub source: Source,
+    pub detail: Option<String>, // shown next to the label, like a type
+    pub documentation: Option<String>,
+    pub snippet: bool, // insert is a snippet body
+    pub replace: Option<(usize, usize)>, // a server's own edit, where it starts and how many chars after the cursor it takes
+}
+
+impl Item {
+    pub fn new(label: &str, source: Source) -> Self {
+        Item {
+            label: label.to_string(),
+            insert: label.to_string(),
+            source,
+            detail: None,
+            documentation: None,
+            snippet: false,
+            replace: None,
+        }
+    }
+}
+
+pub struct Completion {
+    pub start: usize, // char index in the buffer where the replaced text starts
+    pub items: Vec<Item>,
+    pub query: String, // the text between start and the cursor
+    pub matches: Vec<Match>,
+    pub selected: usize,
+    pub offset: usize,
+}
+
+impl Completion {
+    pub fn new(start: usize, query: &str, items: Vec<Item>) -> Self {
+        let mut completion = Completion {
+            start,
+            items,
+            query: String::new(),
+            matches: vec![],
+            selected: 0,
+            offset: 0,
+        };
+        completion.set_query(query);
+        completion
+    }
+
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_string();
+        self.update();
+        self.select(0);
+    }
+
+    // items that came in late, a word the server also knows is shown as the server's
+    pub fn add(&mut self, items: Vec<Item>) {
+        let selected = self.selected_item().map(|i| i.label.clone());
+
+        self.items.retain(|i| i.source != Source::Words || !items.iter().any(|n| n.label == i.label));
+        self.items.extend(items);
+        self.update();
+
+        let idx = selected.and_then(|label| self.matches.iter().position(|m| self.items[m.item].label == label));
+        self.select(idx.unwrap_or(0));
+    }
+
+    fn update(&mut self) {
+        let mut matches: Vec<Match> = self.items.iter()
+            .enumerate()
+            .filter_map(|(item, i)| {
+                let (score, positions) = fuzzy_match(&self.query, &i.label)?;
+                Some(Match { item, score, positions })
+            })
+            .collect();
+
+        // best first, ties keep the order the sources gave
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.item.cmp(&b.item)));
+        self.matches = matches;
+    }
+
+    pub fn selected_item(&self) -> Option<&Item> {
+        let m = self.matches.get(self.selected)?;
+        self.items.get(m.item)
+    }
+
+    pub fn select(&mut self, idx: usize) {
+        self.selected = idx.min(self.matches.len().saturating_sub(1));
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + MAX_ROWS {
+            self.offset = self.selected + 1 - MAX_ROWS;
+        }
+    }
+
+    // both ways wrap around the ends of the list
+    pub fn next(&mut self) {
+        match self.selected + 1 < self.matches.len() {
+            true => self.select(self.selected + 1),
+            false => self.select(0),
+        }
+    }
+
+    pub fn prev(&mut self) {
+        match self.selected {
+            0 => self.select(self.matches.len().saturating_sub(1)),
+            n => self.select(n - 1),
+        }
+    }
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// chars that can be in a path typed into text
+fn is_path_char(c: char) -> bool {
+    is_word_char(c) || matches!(c, '/' | '.' | '-' | '~' | '+' | '@')
+}
+
+// the word the text ends with, as a char index in it
+pub fn word_start(before: &str) -> usize {
+    let chars: Vec<char> = before.chars().collect();
+    chars.iter().rposition(|c| !is_word_char(*c)).map_or(0, |i| i + 1)
+}
+
+// the directory part of a path the text ends with, as the text of it and
+// where the name after it starts, None when the text doesn't end in a path
+pub fn path_before(before: &str) -> Option<(String, usize)> {
+    let chars: Vec<char> = before.chars().collect();
+    let start = chars.iter().rposition(|c| !is_path_char(*c)).map_or(0, |i| i + 1);
+    let token: String = chars[start..].iter().collect();
+
+    let slash = token.rfind('/')?;
+    let dir = &token[..slash + 1];
+    Some((dir.to_string(), start + dir.chars().count()))
+}
+
+// words from the texts in the order they come, without the one being typed
+pub fn buffer_words<'a>(texts: impl Iterator<Item = &'a Rope>, typed: &str) -> Vec<Item> {
+    let mut seen = HashSet::new();
+    let mut items = vec![];
+
+    for rope in texts {
+        for line in rope.lines() {
+            let line = line.to_string();
+            for word in line.split(|c: char| !is_word_char(c)) {
+                if items.len() >= MAX_WORDS {
+                    return items;
+                }
+                // numbers and single letters aren't worth completing
+                if word.chars().count() < 2 || word.starts_with(|c: char| c.is_numeric()) || word == typed {
+                    continue;
+                }
+                if seen.insert(word.to_string()) {
+                    items.push(Item::new(word, Source::Words));
+                }
+            }
+        }
+    }
+    items
+}
+
+// entries of the directory a typed path points into, relative to base
+pub fn path_items(base: &Path, dir: &str) -> Vec<Item> {
+    let dir = match dir.strip_prefix("~/") {
+        Some(rest) => std::env::var("HOME").map_or(dir.to_string(), |home| format!("{}/{}", home, rest)),
+        None => dir.to_string(),
+    };
+
+    complete_path(base, &dir)
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.strip_prefix(&dir)?;
+            let mut item = Item::new(name, Source::Paths);
+            item.detail = Some(String::from(if name.ends_with('/') { "dir" } else { "file" }));
+            Some(item)
+        })
+        .collect()
+}
+
+
+#[test]
+fn test_completion() {
+    let rope = Rope::from("let counter = 1;\nlet count = counter + 2;\n");
+    let other = Rope::from("fn compute() {}\n");
+    let words = buffer_words([&rope, &other].into_iter(), "co");
+    let labels: Vec<&str> = words.iter().map(|i| i.label.as_str()).collect();
+    assert_eq!(labels, vec!["let", "counter", "count", "fn", "compute"]);
+
+    let mut completion = Completion::new(20, "co", words);
+    let labels: Vec<&str> = completion.matches.iter().map(|m| completion.items[m.item].label.as_str()).collect();
+    assert_eq!(labels, vec!["counter", "count", "compute"]);
+
+    completion.prev();
+    assert_eq!(completion.selected_item().unwrap().label, "compute");
+    completion.next();
+    assert_eq!(completion.selected_item().unwrap().label, "counter");
+
+    // the server's items take the place of the same words and keep the selection
+    let mut counter = Item::new("counter", Source::Lsp);
+    counter.documentation = Some(String::from("a number"));
+    completion.add(vec![counter, Item::new("cold", Source::Lsp)]);
+    let selected = completion.selected_item().unwrap();
+    assert_eq!((selected.label.as_str(), selected.source), ("counter", Source::Lsp));
+    assert_eq!(completion.items.len(), 6);
+
+    completion.set_query("cnt");
+    assert_eq!(completion.matches.len(), 2);
+
+    assert_eq!(word_start("let x = coun"), 8);
+    assert_eq!(path_before("open(\"src/fi"), Some((String::from("src/"), 10)));
+    assert_eq!(path_before("open(name"), None);
+}
+
+#[test]
+fn test_path_items() {
+    let base = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let items = path_items(base, "src/");
+    assert!(items.iter().any(|i| i.label == "completion.rs" && i.detail.as_deref() == Some("file")));
+    assert!(items.iter().all(|i| !i.label.contains('/')));
+}
@@ -0,0 +1,217 @@
+use crate::quickfix::Severity;
+use ropey::Rope;
+use std::collections::{BTreeMap, HashMap};
+
+/*
+ * problems found in a buffer's text by whatever looked at it, a language server,
+ * :make or a linter, each one covers a range of chars that moves along as the
+ * text around it is edited
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub start: usize, // in chars
+    pub end: usize,
+    pub severity: Severity,
+    pub message: String,
+    pub source: Option<String>, // the tool that reported it, like rustc or clippy
+}
+
+impl Diagnostic {
+    // the full message, as shown in the float
+    pub fn display(&self) -> String {
+        match &self.source {
+            Some(source) => format!("{} {}: {}", self.severity.marker(), source, self.message),
+            None => format!("{} {}", self.severity.marker(), self.message),
+        }
+    }
+
+    // zero width ranges still mark the char they start at
+    pub fn covers(&self, idx: usize) -> bool {
+        (self.start..self.end.max(self.start + 1)).contains(&idx)
+    }
+}
+
+#[derive(Default)]
+pub struct Diagnostics {
+    providers: HashMap<String, Vec<Diagnostic>>, // by what sent them, so each can replace its own
+    rope: Rope, // the text the ranges are for
+    revision: usize, // the buffer revision that text is
+}
+
+impl Diagnostics {
+    // replaces everything the provider reported before, the ranges are for rope
+    pub fn set(&mut self, provider: &str, rope: &Rope, revision: usize, mut diagnostics: Vec<Diagnostic>) {
+        self.update(rope, revision);
+
+        let len = rope.len_chars();
+        for diagnostic in diagnostics.iter_mut() {
+            diagnostic.end = diagnostic.end.min(len);
+            diagnostic.start = diagnostic.start.min(diagnostic.end);
+        }
+
+        if diagnostics.is_empty() {
+            self.providers.remove(provider);
+        } else {
+            self.providers.insert(provider.to_string(), diagnostics);
+        }
+    }
+
+    pub fn clear(&mut self, provider: &str) {
+        self.providers.remove(provider);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.providers.values().map(|d| d.len()).sum()
+    }
+
+    // every diagnostic in order of where it starts
+    pub fn all(&self) -> Vec<&Diagnostic> {
+        let mut all: Vec<&Diagnostic> = self.providers.values().flatten().collect();
+        all.sort_by_key(|d| (d.start, std::cmp::Reverse(d.severity)));
+        all
+    }
+
+    // moves the ranges over whatever changed between the last text and rope,
+    // the change is the part between the common start and end of both. the text
+    // is only looked at when the revision is a new one
+    pub fn update(&mut self, rope: &Rope, revision: usize) {
+        if revision == self.revision {
+            return;
+        }
+        self.revision = revision;
+        if self.providers.is_empty() {
+            self.rope = rope.clone();
+            return;
+        }
+
+        let (old_len, new_len) = (self.rope.len_chars(), rope.len_chars());
+        let prefix = self.rope.chars().zip(rope.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = self.rope.chars_at(old_len).reversed().zip(rope.chars_at(new_len).reversed())
+            .take(old_len.min(new_len) - prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let removed_end = old_len - suffix;
+        let inserted_end = new_len - suffix;
+        let moved = |idx: usize| idx - removed_end + inserted_end;
+
+        // text typed right before a range pushes it along, text typed right after it
+        // or over part of it becomes part of it
+        for diagnostic in self.providers.values_mut().flatten() {
+            let start = match diagnostic.start {
+                start if start < prefix => start,
+                start if start >= removed_end => moved(start),
+                _ => prefix,
+            };
+            let end = match diagnostic.end {
+                end if end < prefix => end,
+                end if end > removed_end => moved(end),
+                _ => inserted_end,
+            };
+            diagnostic.start = start;
+            diagnostic.end = end.max(start);
+        }
+
+        self.rope = rope.clone();
+    }
+
+    // the worst severity on each line that has any
+    pub fn signs(&self) -> BTreeMap<usize, Severity> {
+        let mut signs = BTreeMap::new();
+
+        for diagnostic in self.providers.values().flatten() {
+            let line = self.rope.char_to_line(diagnostic.start);
+            let sign = signs.entry(line).or_insert(diagnostic.severity);
+            *sign = (*sign).max(diagnostic.severity);
+        }
+        signs
+    }
+
+    // the ones touching a line, worst first
+    pub fn on_line(&self, line: usize) -> Vec<&Diagnostic> {
+        if line >= self.rope.len_lines() {
+            return vec![];
+        }
+        let start = self.rope.line_to_char(line);
+        let end = start + self.rope.line(line).len_chars();
+
+        let mut found: Vec<&Diagnostic> = self.providers.values()
+            .flatten()
+            .filter(|d| d.start < end && d.end.max(d.start + 1) > start)
+            .collect();
+        found.sort_by_key(|d| (std::cmp::Reverse(d.severity), d.start));
+        found
+    }
+
+    pub fn at(&self, idx: usize) -> Vec<&Diagnostic> {
+        self.all().into_iter().filter(|d| d.covers(idx)).collect()
+    }
+
+    // where the next one after idx starts, wrapping around the end
+    pub fn next(&self, idx: usize) -> Option<usize> {
+        let starts: Vec<usize> = self.all().iter().map(|d| d.start).collect();
+        starts.iter().find(|start| **start > idx).or(starts.first()).copied()
+    }
+
+    pub fn prev(&self, idx: usize) -> Option<usize> {
+        let starts: Vec<usize> = self.all().iter().map(|d| d.start).collect();
+        starts.iter().rev().find(|start| **start < idx).or(starts.last()).copied()
+    }
+}
+
+
+#[test]
+fn test_update() {
+    let rope = Rope::from("fn main() {\n    let x = y;\n}\n");
+    let mut diagnostics = Diagnostics::default();
+    let error = Diagnostic {
+        start: 24,
+        end: 25,
+        severity: Severity::Error,
+        message: String::from("cannot find value `y`"),
+        source: Some(String::from("rustc")),
+    };
+    let warning = Diagnostic { start: 20, end: 21, severity: Severity::Warning, message: String::from("unused"), source: None };
+    diagnostics.set("make", &rope, 1, vec![error, warning]);
+    assert_eq!(diagnostics.signs().into_iter().collect::<Vec<_>>(), vec![(1, Severity::Error)]);
+    assert_eq!(diagnostics.on_line(1)[0].message, "cannot find value `y`");
+
+    // a line added above moves both down
+    let mut edited = rope.clone();
+    edited.insert(0, "// top\n");
+    diagnostics.update(&edited, 2);
+    assert_eq!(diagnostics.signs().into_iter().collect::<Vec<_>>(), vec![(2, Severity::Error)]);
+    assert_eq!(edited.slice(diagnostics.all()[1].start..diagnostics.all()[1].end), "y");
+
+    // renaming x grows the warning over the new name
+    let renamed = Rope::from("// top\nfn main() {\n    let xs = y;\n}\n");
+    diagnostics.update(&renamed, 3);
+    let all = diagnostics.all();
+    assert_eq!((renamed.slice(all[0].start..all[0].end).to_string(), all[0].severity), (String::from("xs"), Severity::Warning));
+    assert_eq!(renamed.slice(all[1].start..all[1].end), "y");
+    let (warning, error) = (all[0].start, all[1].start);
+
+    // deleting the text under one leaves it empty where the text was
+    let deleted = Rope::from("// top\nfn main() {\n    let xs = ;\n}\n");
+    diagnostics.update(&deleted, 4);
+    assert_eq!((diagnostics.all()[1].start, diagnostics.all()[1].end), (error, error));
+    assert_eq!(diagnostics.at(error).len(), 1);
+
+    assert_eq!(diagnostics.next(0), Some(warning));
+    assert_eq!(diagnostics.next(error), Some(warning));
+    assert_eq!(diagnostics.prev(warning), Some(error));
+
+    // the same revision again isn't looked at
+    diagnostics.update(&rope, 4);
+    assert_eq!(diagnostics.all()[1].start, error);
+
+    diagnostics.set("make", &deleted, 4, vec![]);
+    assert!(diagnostics.is_empty());
+}
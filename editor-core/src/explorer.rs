@@ -0,0 +1,483 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/*
+ * filesystem side of directory buffers
+ *
+ * listings put directories first with a trailing /, everything else is
+ * sorted by name after them
+ *
+ * an edited listing is compared against the entries it was made from, lines
+ * that were replaced in place become renames and the rest become creates and
+ * deletes
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntryKind {
+    Directory,
+    File,
+    Executable,
+    Symlink,
+    Other,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub is_dir: bool, // also true for links to directories
+}
+
+impl Entry {
+    fn new(path: &Path, name: String) -> Self {
+        let is_dir = path.is_dir();
+        let kind = match fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_symlink() => EntryKind::Symlink,
+            Ok(meta) if meta.is_dir() => EntryKind::Directory,
+            Ok(meta) if meta.is_file() && is_executable(&meta) => EntryKind::Executable,
+            Ok(meta) if meta.is_file() => EntryKind::File,
+            _ => EntryKind::Other,
+        };
+
+        Entry { name, kind, is_dir }
+    }
+
+    // name as it appears in the listing
+    pub fn display(&self) -> String {
+        if self.is_dir {
+            format!("{}/", self.name)
+        } else {
+            self.name.clone()
+        }
+    }
+
+    pub fn icon(&self) -> char {
+        match self.kind {
+            EntryKind::Directory => '▸',
+            EntryKind::File => '·',
+            EntryKind::Executable => '*',
+            EntryKind::Symlink => '↪',
+            EntryKind::Other => '?',
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &fs::Metadata) -> bool {
+    false
+}
+
+pub fn list_dir(path: &Path, show_hidden: bool) -> io::Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = fs::read_dir(path)?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !show_hidden && name.starts_with('.') {
+                return None;
+            }
+            Some(Entry::new(&entry.path(), name))
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+
+    Ok(entries)
+}
+
+// makes a new file in dir, a trailing / makes a directory instead,
+// missing directories along the way are created
+pub fn create(dir: &Path, name: &str) -> io::Result<PathBuf> {
+    let path = dir.join(name);
+
+    if name.ends_with('/') {
+        if path.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "already exists"));
+        }
+        fs::create_dir_all(&path)?;
+    } else {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new().write(true).create_new(true).open(&path)?;
+    }
+
+    Ok(path)
+}
+
+pub fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "already exists"));
+    }
+
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)
+}
+
+// copies files and whole directories, links are copied as links
+pub fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "already exists"));
+    }
+
+    copy_recursive(from, to)
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(from)?;
+
+    if meta.file_type().is_symlink() {
+        copy_link(from, to)
+    } else if meta.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, meta.permissions())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+#[cfg(unix)]
+pub fn chmod(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+pub fn chmod(_path: &Path, _mode: u32) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "chmod is only supported on unix"))
+}
+
+// permission bits of path, like 644
+#[cfg(unix)]
+pub fn mode(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::symlink_metadata(path).ok().map(|m| m.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+pub fn mode(_path: &Path) -> Option<u32> {
+    None
+}
+
+// freedesktop trash of the user, $XDG_DATA_HOME/Trash
+pub fn trash_dir() -> Option<PathBuf> {
+    if let Some(data) = std::env::var_os("XDG_DATA_HOME") {
+        return Some(PathBuf::from(data).join("Trash"));
+    }
+
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/Trash"))
+}
+
+// moves path into the trash along with the info needed to restore it,
+// returns where it ended up
+pub fn trash_into(path: &Path, trash: &Path) -> io::Result<PathBuf> {
+    let files = trash.join("files");
+    let info = trash.join("info");
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    let path = std::path::absolute(path)?;
+    let name = path
+        .file_name()
+        .ok_or(io::Error::new(io::ErrorKind::InvalidInput, "nothing to trash"))?
+        .to_string_lossy()
+        .to_string();
+
+    // the info file is created first to claim the name
+    let mut count = 1;
+    let (trash_name, mut info_file) = loop {
+        let trash_name = if count == 1 { name.clone() } else { format!("{}.{}", name, count) };
+        let res = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(info.join(format!("{}.trashinfo", trash_name)));
+
+        match res {
+            Ok(file) if !files.join(&trash_name).exists() => break (trash_name, file),
+            Ok(_) => {
+                let _ = fs::remove_file(info.join(format!("{}.trashinfo", trash_name)));
+            },
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {},
+            Err(err) => return Err(err),
+        }
+        count += 1;
+    };
+
+    write!(
+        info_file,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        path.display(),
+        deletion_date(SystemTime::now())
+    )?;
+
+    let target = files.join(&trash_name);
+    let res = fs::rename(&path, &target).or_else(|err| {
+        // the trash can be on another filesystem
+        copy_recursive(&path, &target)?;
+        if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        }
+        .map_err(|_| err)
+    });
+
+    if let Err(err) = res {
+        let _ = fs::remove_file(info.join(format!("{}.trashinfo", trash_name)));
+        return Err(err);
+    }
+
+    Ok(target)
+}
+
+// YYYY-MM-DDThh:mm:ss in UTC
+fn deletion_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // days to civil date, from Howard Hinnant's algorithm
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, rem / 3600, rem % 3600 / 60, rem % 60
+    )
+}
+
+// filesystem operation that brings a directory in line with its edited listing
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Create(String),
+    Rename { from: String, to: String },
+    Delete(String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Create(name) => write!(f, "create {}", name),
+            Change::Rename { from, to } => write!(f, "rename {} -> {}", from, to),
+            Change::Delete(name) => write!(f, "delete {}", name),
+        }
+    }
+}
+
+// entries are the same whether or not they keep their trailing /
+fn key(name: &str) -> &str {
+    name.trim_end_matches('/')
+}
+
+fn listed(line: &str) -> bool {
+    !matches!(line.trim(), "" | "." | "./" | ".." | "../")
+}
+
+// changes that turn the original listing into the edited one, deletes come
+// first so their names can be reused, then renames and then creates
+pub fn diff_listing(original: &[String], edited: &[String]) -> Vec<Change> {
+    let original: Vec<&str> = original.iter().map(|l| l.as_str()).filter(|l| listed(l)).collect();
+    let mut edited: Vec<&str> = edited.iter().map(|l| l.as_str()).filter(|l| listed(l)).collect();
+
+    // a name typed twice is still one entry
+    let mut seen = vec![];
+    edited.retain(|l| {
+        let new = !seen.contains(&key(l));
+        seen.push(key(l));
+        new
+    });
+
+    let removed = |name: &str| !edited.iter().any(|l| key(l) == key(name));
+    let added = |name: &str| !original.iter().any(|l| key(l) == key(name));
+
+    // longest common subsequence, lines outside of it are what was edited
+    let (n, m) = (original.len(), edited.len());
+    let mut table = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = if key(original[i]) == key(edited[j]) {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut deletes = vec![];
+    let mut renames = vec![];
+    let mut creates = vec![];
+
+    // lines that were taken out and put in at the same spot are renames, paired
+    // in order, the ones left over on either side are deletes or creates
+    let mut hunk = |gone: &mut Vec<&str>, new: &mut Vec<&str>| {
+        let gone: Vec<&str> = gone.drain(..).filter(|l| removed(l)).collect();
+        let new: Vec<&str> = new.drain(..).filter(|l| added(l)).collect();
+        let paired = gone.len().min(new.len());
+
+        renames.extend(gone.iter().zip(&new).map(|(from, to)| Change::Rename {
+            from: key(from).to_string(),
+            to: key(to).to_string(),
+        }));
+        deletes.extend(gone[paired..].iter().map(|from| Change::Delete(key(from).to_string())));
+        creates.extend(new[paired..].iter().map(|to| Change::Create(to.to_string())));
+    };
+
+    let (mut i, mut j) = (0, 0);
+    let (mut gone, mut new) = (vec![], vec![]);
+    while i < n || j < m {
+        if i < n && j < m && key(original[i]) == key(edited[j]) {
+            hunk(&mut gone, &mut new);
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && table[i + 1][j] >= table[i][j + 1]) {
+            gone.push(original[i]);
+            i += 1;
+        } else {
+            new.push(edited[j]);
+            j += 1;
+        }
+    }
+    hunk(&mut gone, &mut new);
+
+    deletes.into_iter().chain(renames).chain(creates).collect()
+}
+
+// carries out a change inside of dir, deleted entries go to the trash
+pub fn apply(dir: &Path, change: &Change, trash: &Path) -> io::Result<()> {
+    match change {
+        Change::Create(name) => create(dir, name).map(|_| ()),
+        Change::Rename { from, to } => rename(&dir.join(from), &dir.join(to)),
+        Change::Delete(name) => trash_into(&dir.join(name), trash).map(|_| ()),
+    }
+}
+
+
+#[test]
+fn test_diff_listing() {
+    let lines = |text: &str| -> Vec<String> { text.lines().map(String::from).collect() };
+    let original = lines("./\n../\nsrc/\na.rs\nb.rs\nc.rs");
+
+    assert!(diff_listing(&original, &original).is_empty());
+
+    // moving lines around or dropping the slash changes nothing
+    assert!(diff_listing(&original, &lines("c.rs\nsrc\nb.rs\na.rs")).is_empty());
+
+    assert_eq!(
+        diff_listing(&original, &lines("./\n../\nsrc/\nmain.rs\nc.rs\nnew/\nnew/")),
+        vec![
+            Change::Delete(String::from("b.rs")),
+            Change::Rename { from: String::from("a.rs"), to: String::from("main.rs") },
+            Change::Create(String::from("new/")),
+        ]
+    );
+
+    // with a line gone the names are paired in order and the last one is deleted
+    assert_eq!(
+        diff_listing(&original, &lines("src/\na.rs\nmain_b.rs")),
+        vec![
+            Change::Delete(String::from("c.rs")),
+            Change::Rename { from: String::from("b.rs"), to: String::from("main_b.rs") },
+        ]
+    );
+    assert_eq!(
+        diff_listing(&original, &lines("src/\na.rs\nc_new.rs")),
+        vec![
+            Change::Delete(String::from("c.rs")),
+            Change::Rename { from: String::from("b.rs"), to: String::from("c_new.rs") },
+        ]
+    );
+
+    assert_eq!(
+        diff_listing(&original, &lines("lib/\na.rs\nb.rs\nc.rs")),
+        vec![Change::Rename { from: String::from("src"), to: String::from("lib") }]
+    );
+}
+
+#[test]
+fn test_deletion_date() {
+    use std::time::Duration;
+
+    assert_eq!(deletion_date(UNIX_EPOCH), "1970-01-01T00:00:00");
+    assert_eq!(deletion_date(UNIX_EPOCH + Duration::from_secs(951782400 + 3723)), "2000-02-29T01:02:03");
+}
+
+#[test]
+fn test_explorer_operations() {
+    let dir = std::env::temp_dir().join(format!("editor-explorer-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    create(&dir, "b.rs").unwrap();
+    create(&dir, "A.rs").unwrap();
+    create(&dir, "zdir/").unwrap();
+    create(&dir, ".hidden").unwrap();
+    assert!(create(&dir, "b.rs").is_err());
+
+    let names = |show_hidden| -> Vec<String> {
+        list_dir(&dir, show_hidden).unwrap().iter().map(|e| e.display()).collect()
+    };
+    assert_eq!(names(false), vec!["zdir/", "A.rs", "b.rs"]);
+    assert_eq!(names(true), vec!["zdir/", ".hidden", "A.rs", "b.rs"]);
+
+    rename(&dir.join("b.rs"), &dir.join("zdir/c.rs")).unwrap();
+    assert!(dir.join("zdir/c.rs").exists());
+    assert!(rename(&dir.join("A.rs"), &dir.join("zdir/c.rs")).is_err());
+
+    copy(&dir.join("zdir"), &dir.join("ydir")).unwrap();
+    assert!(dir.join("ydir/c.rs").exists());
+
+    let trash = dir.join("trash");
+    let trashed = trash_into(&dir.join("ydir"), &trash).unwrap();
+    assert!(!dir.join("ydir").exists());
+    assert!(trashed.join("c.rs").exists());
+    let info = fs::read_to_string(trash.join("info/ydir.trashinfo")).unwrap();
+    assert!(info.contains(&format!("Path={}", dir.join("ydir").display())));
+
+    // names already in the trash get a number
+    create(&dir, "ydir/").unwrap();
+    assert_eq!(trash_into(&dir.join("ydir"), &trash).unwrap(), trash.join("files/ydir.2"));
+
+    #[cfg(unix)]
+    {
+        chmod(&dir.join("A.rs"), 0o755).unwrap();
+        assert_eq!(mode(&dir.join("A.rs")), Some(0o755));
+        let entries = list_dir(&dir, false).unwrap();
+        assert_eq!(entries.iter().find(|e| e.name == "A.rs").unwrap().kind, EntryKind::Executable);
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
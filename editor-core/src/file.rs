@@ -0,0 +1,226 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+/*
+ * helpers for getting buffers onto disk
+ *
+ * writes go to a temp file next to the original which is synced and then
+ * renamed over it, so a failed write never leaves a half written file behind
+ */
+
+// max number of links followed before giving up, same as linux
+const MAX_LINKS: usize = 40;
+
+// follows a chain of symlinks to the file that actually holds the contents,
+// the target doesn't need to exist yet
+pub fn resolve_link(path: &Path) -> io::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+
+    for _ in 0..MAX_LINKS {
+        if !path.is_symlink() {
+            return Ok(path);
+        }
+
+        let target = fs::read_link(&path)?;
+        path = match path.parent() {
+            Some(parent) if target.is_relative() => parent.join(target),
+            _ => target,
+        };
+    }
+
+    Err(io::Error::other("too many levels of symbolic links"))
+}
+
+// removes . and .. without touching the filesystem, so links stay as they are
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                if !normal.pop() {
+                    normal.push(component);
+                }
+            },
+            _ => normal.push(component),
+        }
+    }
+
+    normal
+}
+
+// path relative to the working directory when it is inside of it
+pub fn display_name(path: &Path) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let relative = path.strip_prefix(&cwd).unwrap_or(path);
+
+    if relative.as_os_str().is_empty() {
+        String::from(".")
+    } else {
+        relative.display().to_string()
+    }
+}
+
+// entries of the directory part of partial that start with its last component,
+// directories end in a / so completion can keep going into them
+pub fn complete_path(base: &Path, partial: &str) -> Vec<String> {
+    let (dir, name) = match partial.rfind('/') {
+        Some(idx) => partial.split_at(idx + 1),
+        None => ("", partial),
+    };
+
+    let entries = match fs::read_dir(base.join(dir)) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+
+            // hidden files only when asked for
+            if !file_name.starts_with(name) || (file_name.starts_with('.') && !name.starts_with('.')) {
+                return None;
+            }
+
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, file_name, slash))
+        })
+        .collect();
+
+    candidates.sort();
+    candidates
+}
+
+pub fn is_read_only(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|m| m.permissions().readonly())
+        .unwrap_or(false)
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+// writes contents to path through a temp file in the same directory, keeping
+// the mode bits of the file being replaced and writing through symlinks
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let path = resolve_link(path)?;
+    let permissions = fs::metadata(&path).ok().map(|m| m.permissions());
+    let temp = temp_path(&path);
+
+    let res = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        if let Some(permissions) = permissions {
+            fs::set_permissions(&temp, permissions)?;
+        }
+
+        fs::rename(&temp, &path)
+    })();
+
+    if res.is_err() {
+        let _ = fs::remove_file(&temp);
+        return res;
+    }
+
+    // make the rename itself durable, not every platform can open a directory
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+
+#[test]
+fn test_write_atomic() {
+    let dir = std::env::temp_dir().join(format!("editor-file-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("file.txt");
+    write_atomic(&path, b"first").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+
+    // mode bits survive the rename
+    let mut permissions = fs::metadata(&path).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&path, permissions).unwrap();
+    assert!(is_read_only(&path));
+
+    write_atomic(&path, b"second").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+    assert!(is_read_only(&path));
+
+    // no temp files are left behind
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    let mut permissions = fs::metadata(&path).unwrap().permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(&path, permissions).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_normalize() {
+    assert_eq!(normalize(Path::new("/a/b/../c/./d/")), PathBuf::from("/a/c/d"));
+    assert_eq!(normalize(Path::new("/a/..")), PathBuf::from("/"));
+    assert_eq!(normalize(Path::new("../a")), PathBuf::from("../a"));
+}
+
+#[test]
+fn test_complete_path() {
+    let base = std::env::temp_dir().join(format!("editor-complete-path-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&base);
+    fs::create_dir_all(base.join("src")).unwrap();
+    for name in ["src/file.rs", "src/filetype.rs", "src/main.rs", "notes.txt"] {
+        fs::write(base.join(name), "").unwrap();
+    }
+
+    assert_eq!(complete_path(&base, "sr"), vec!["src/".to_string()]);
+    assert_eq!(complete_path(&base, "src/").len(), 3);
+    assert_eq!(complete_path(&base, "src/fi"), vec!["src/file.rs".to_string(), "src/filetype.rs".to_string()]);
+    assert!(complete_path(&base, "nothing/").is_empty());
+
+    fs::remove_dir_all(&base).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_write_through_symlink() {
+    let dir = std::env::temp_dir().join(format!("editor-link-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let target = dir.join("target.txt");
+    let link = dir.join("link.txt");
+    fs::write(&target, "old").unwrap();
+    std::os::unix::fs::symlink("target.txt", &link).unwrap();
+
+    assert_eq!(resolve_link(&link).unwrap(), target);
+
+    write_atomic(&link, b"new").unwrap();
+    assert!(link.is_symlink());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
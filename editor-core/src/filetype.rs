@@ -0,0 +1,572 @@
+use crate::shell;
+use crate::snippet::{self, Snippet};
+use std::path::Path;
+
+/*
+ * what kind of file a buffer holds and the options that come with it
+ *
+ * a file type is picked from, in order:
+ *   a modeline like `vim: set ft=rust:` in the first or last lines
+ *   the whole file name, for files like Makefile and Cargo.lock
+ *   the extension
+ *   the interpreter named on a #! line
+ *
+ * modelines can also set sw/ts/sts/shiftwidth/tabstop/softtabstop and
+ * et/noet/expandtab, and :set changes them for the buffer afterwards. what
+ * :set and :formatprg change stays when the buffer's file type changes
+ *
+ * brackets and quotes typed in insert mode get their closer added, each type
+ * says which pairs it wants, rust leaves ' out for its lifetimes
+ *
+ * a language server is started for each file type and project root, the root
+ * being the nearest directory up with one of the type's markers or .git
+ */
+
+// lines at each end of a file looked at for a modeline
+const MODELINE_LINES: usize = 5;
+
+// what is closed as it is typed when a type doesn't say otherwise
+pub const PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\''), ('`', '`')];
+
+#[derive(Debug, PartialEq)]
+pub struct FileType {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub filenames: &'static [&'static str],
+    pub interpreters: &'static [&'static str],
+    pub indent_width: usize,
+    pub tab_width: usize,
+    pub expand_tab: bool,
+    pub comment: Option<&'static str>, // starts a line comment
+    pub block_comment: Option<(&'static str, &'static str)>, // for types without line comments
+    pub indent_after: &'static [&'static str], // line endings that start a block, besides open brackets
+    pub pairs: &'static [(char, char)],
+    pub formatter: Option<&'static str>,
+    pub grammar: Option<&'static str>, // tree sitter language
+    pub language_server: Option<&'static str>,
+    pub root_markers: &'static [&'static str], // files at the top of a project, nearest first
+    pub snippets: &'static [Snippet],
+}
+
+const fn file_type(name: &'static str) -> FileType {
+    FileType {
+        name,
+        extensions: &[],
+        filenames: &[],
+        interpreters: &[],
+        indent_width: 4,
+        tab_width: 8,
+        expand_tab: true,
+        comment: None,
+        block_comment: None,
+        indent_after: &[],
+        pairs: PAIRS,
+        formatter: None,
+        grammar: None,
+        language_server: None,
+        root_markers: &[],
+        snippets: &[],
+    }
+}
+
+pub static FILE_TYPES: &[FileType] = &[
+    FileType {
+        extensions: &["rs"],
+        comment: Some("//"),
+        pairs: &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('`', '`')],
+        formatter: Some("rustfmt --edition 2021 --emit stdout"),
+        grammar: Some("rust"),
+        language_server: Some("rust-analyzer"),
+        root_markers: &["Cargo.lock", "Cargo.toml"],
+        snippets: snippet::RUST,
+        ..file_type("rust")
+    },
+    FileType {
+        extensions: &["toml"],
+        filenames: &["Cargo.lock"],
+        comment: Some("#"),
+        grammar: Some("toml"),
+        ..file_type("toml")
+    },
+    FileType {
+        extensions: &["mk"],
+        filenames: &["Makefile", "makefile", "GNUmakefile"],
+        indent_width: 8,
+        expand_tab: false,
+        comment: Some("#"),
+        indent_after: &[":"],
+        ..file_type("make")
+    },
+    FileType {
+        extensions: &["py", "pyi"],
+        interpreters: &["python"],
+        comment: Some("#"),
+        formatter: Some("black --quiet -"),
+        grammar: Some("python"),
+        language_server: Some("pylsp"),
+        root_markers: &["pyproject.toml", "setup.py", "setup.cfg"],
+        snippets: snippet::PYTHON,
+        indent_after: &[":"],
+        ..file_type("python")
+    },
+    FileType {
+        extensions: &["go"],
+        indent_width: 8,
+        expand_tab: false,
+        comment: Some("//"),
+        formatter: Some("gofmt"),
+        grammar: Some("go"),
+        language_server: Some("gopls"),
+        root_markers: &["go.work", "go.mod"],
+        snippets: snippet::GO,
+        ..file_type("go")
+    },
+    FileType {
+        extensions: &["c", "h"],
+        comment: Some("//"),
+        formatter: Some("clang-format --assume-filename %"),
+        grammar: Some("c"),
+        language_server: Some("clangd"),
+        root_markers: &["compile_commands.json", "compile_flags.txt"],
+        snippets: snippet::C,
+        ..file_type("c")
+    },
+    FileType {
+        extensions: &["cc", "cpp", "cxx", "hpp", "hh"],
+        comment: Some("//"),
+        formatter: Some("clang-format --assume-filename %"),
+        grammar: Some("cpp"),
+        language_server: Some("clangd"),
+        root_markers: &["compile_commands.json", "compile_flags.txt"],
+        snippets: snippet::C,
+        ..file_type("cpp")
+    },
+    FileType {
+        extensions: &["js", "jsx", "mjs", "cjs"],
+        interpreters: &["node"],
+        indent_width: 2,
+        comment: Some("//"),
+        formatter: Some("prettier --stdin-filepath %"),
+        grammar: Some("javascript"),
+        language_server: Some("typescript-language-server --stdio"),
+        root_markers: &["package.json", "jsconfig.json"],
+        snippets: snippet::JAVASCRIPT,
+        ..file_type("javascript")
+    },
+    FileType {
+        extensions: &["ts", "tsx"],
+        interpreters: &["deno"],
+        indent_width: 2,
+        comment: Some("//"),
+        formatter: Some("prettier --stdin-filepath %"),
+        grammar: Some("typescript"),
+        language_server: Some("typescript-language-server --stdio"),
+        root_markers: &["package.json", "tsconfig.json"],
+        snippets: snippet::JAVASCRIPT,
+        ..file_type("typescript")
+    },
+    FileType {
+        extensions: &["json"],
+        indent_width: 2,
+        formatter: Some("prettier --stdin-filepath %"),
+        grammar: Some("json"),
+        ..file_type("json")
+    },
+    FileType {
+        extensions: &["css", "scss"],
+        indent_width: 2,
+        formatter: Some("prettier --stdin-filepath %"),
+        block_comment: Some(("/*", "*/")),
+        grammar: Some("css"),
+        ..file_type("css")
+    },
+    FileType {
+        extensions: &["html", "htm"],
+        indent_width: 2,
+        formatter: Some("prettier --stdin-filepath %"),
+        block_comment: Some(("<!--", "-->")),
+        grammar: Some("html"),
+        ..file_type("html")
+    },
+    FileType {
+        extensions: &["md", "markdown"],
+        indent_width: 2,
+        formatter: Some("prettier --stdin-filepath %"),
+        block_comment: Some(("<!--", "-->")),
+        grammar: Some("markdown"),
+        ..file_type("markdown")
+    },
+    FileType {
+        extensions: &["yaml", "yml"],
+        indent_width: 2,
+        comment: Some("#"),
+        formatter: Some("prettier --stdin-filepath %"),
+        grammar: Some("yaml"),
+        indent_after: &[":"],
+        ..file_type("yaml")
+    },
+    FileType {
+        extensions: &["sh", "bash", "zsh"],
+        filenames: &[".bashrc", ".bash_profile", ".profile", ".zshrc"],
+        interpreters: &["sh", "bash", "zsh", "dash"],
+        indent_width: 2,
+        comment: Some("#"),
+        grammar: Some("bash"),
+        language_server: Some("bash-language-server start"),
+        snippets: snippet::SH,
+        indent_after: &["then", "do", "else"],
+        ..file_type("sh")
+    },
+    FileType {
+        extensions: &["lua"],
+        interpreters: &["lua"],
+        indent_width: 2,
+        comment: Some("--"),
+        grammar: Some("lua"),
+        language_server: Some("lua-language-server"),
+        root_markers: &[".luarc.json"],
+        indent_after: &["then", "do", "else", "function()"],
+        ..file_type("lua")
+    },
+];
+
+pub fn by_name(name: &str) -> Option<&'static FileType> {
+    FILE_TYPES.iter().find(|ft| ft.name == name)
+}
+
+// options a buffer edits with, they start out as its file type's
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub indent_width: usize, // shiftwidth, what >> and an indent level add
+    pub tab_width: usize, // tabstop, the columns a tab char takes
+    pub soft_tab: usize, // softtabstop, what Tab and Backspace move by, 0 for a tab char
+    pub expand_tab: bool,
+    pub auto_pairs: bool,
+    pub pairs: Vec<(char, char)>,
+    pub comment: Option<String>,
+    pub block_comment: Option<(String, String)>,
+    pub formatter: Option<String>, // only defaulted to when it is installed
+    pub explicit: Vec<&'static str>, // options set by hand, by their full names
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            indent_width: 4,
+            tab_width: 8,
+            soft_tab: 4,
+            expand_tab: true,
+            auto_pairs: true,
+            pairs: PAIRS.to_vec(),
+            comment: None,
+            block_comment: None,
+            formatter: None,
+            explicit: vec![],
+        }
+    }
+}
+
+impl Options {
+    pub fn for_type(file_type: Option<&FileType>) -> Self {
+        let file_type = match file_type {
+            Some(file_type) => file_type,
+            None => return Options::default(),
+        };
+
+        Options {
+            indent_width: file_type.indent_width,
+            tab_width: file_type.tab_width,
+            // spaces are taken out an indent at a time like a tab would be
+            soft_tab: if file_type.expand_tab { file_type.indent_width } else { 0 },
+            expand_tab: file_type.expand_tab,
+            auto_pairs: true,
+            pairs: file_type.pairs.to_vec(),
+            comment: file_type.comment.map(String::from),
+            block_comment: file_type.block_comment.map(|(start, end)| (String::from(start), String::from(end))),
+            formatter: file_type.formatter.filter(|cmd| shell::installed(cmd)).map(String::from),
+            explicit: vec![],
+        }
+    }
+
+    // the options of another file type, with the ones set by hand kept
+    pub fn for_new_type(&self, file_type: Option<&FileType>) -> Self {
+        let mut options = Options::for_type(file_type);
+        for name in &self.explicit {
+            match *name {
+                "tabstop" => options.tab_width = self.tab_width,
+                "shiftwidth" => options.indent_width = self.indent_width,
+                "softtabstop" => options.soft_tab = self.soft_tab,
+                "expandtab" => options.expand_tab = self.expand_tab,
+                "autopairs" => options.auto_pairs = self.auto_pairs,
+                "formatprg" => options.formatter = self.formatter.clone(),
+                _ => {},
+            }
+        }
+        options.explicit = self.explicit.clone();
+        options
+    }
+
+    // :formatprg, None turns formatting off
+    pub fn set_formatter(&mut self, cmd: Option<String>) {
+        self.formatter = cmd;
+        self.mark("formatprg");
+    }
+
+    fn mark(&mut self, name: &'static str) {
+        if !self.explicit.contains(&name) {
+            self.explicit.push(name);
+        }
+    }
+
+    // what the options are set to, as :set shows them
+    pub fn display(&self) -> String {
+        format!(
+            "tabstop={} shiftwidth={} softtabstop={} {} {}",
+            self.tab_width,
+            self.indent_width,
+            self.soft_tab,
+            if self.expand_tab { "expandtab" } else { "noexpandtab" },
+            if self.auto_pairs { "autopairs" } else { "noautopairs" },
+        )
+    }
+
+    // the arguments to :set, like `ts=4 sw=4 et`, a name with ? after it gives its value
+    pub fn set(&mut self, args: &str) -> Result<Option<String>, String> {
+        let mut shown = vec![];
+
+        for arg in args.split_whitespace() {
+            let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+            let name = name.trim_end_matches('?');
+
+            // on and off options, set with their name and unset with no in front of it
+            let flag = match name.strip_prefix("no").unwrap_or(name) {
+                "et" | "expandtab" => Some((&mut self.expand_tab, "expandtab")),
+                "ap" | "autopairs" => Some((&mut self.auto_pairs, "autopairs")),
+                _ => None,
+            };
+            if let Some((flag, full)) = flag {
+                match (arg.ends_with('?'), value.is_empty()) {
+                    (true, _) => shown.push(format!("{}{}", if *flag { "" } else { "no" }, full)),
+                    (false, true) => {
+                        *flag = !name.starts_with("no");
+                        self.mark(full);
+                    },
+                    (false, false) => return Err(format!("Invalid argument: {}", arg)),
+                }
+                continue;
+            }
+
+            let (width, full) = match name {
+                "ts" | "tabstop" => (&mut self.tab_width, "tabstop"),
+                "sw" | "shiftwidth" => (&mut self.indent_width, "shiftwidth"),
+                "sts" | "softtabstop" => (&mut self.soft_tab, "softtabstop"),
+                _ => return Err(format!("Unknown option: {}", name)),
+            };
+
+            if value.is_empty() {
+                shown.push(format!("{}={}", name, width));
+                continue;
+            }
+            match value.parse::<usize>() {
+                // a tab has to take up some room, the others can be 0
+                Ok(0) if matches!(name, "ts" | "tabstop" | "sw" | "shiftwidth") => return Err(format!("Invalid argument: {}", arg)),
+                Ok(value) => {
+                    *width = value;
+                    self.mark(full);
+                },
+                Err(_) => return Err(format!("Invalid argument: {}", arg)),
+            }
+        }
+
+        match shown.is_empty() {
+            true if args.trim().is_empty() => Ok(Some(self.display())),
+            true => Ok(None),
+            false => Ok(Some(shown.join(" "))),
+        }
+    }
+}
+
+// what a modeline asked for
+#[derive(Debug, Default, PartialEq)]
+pub struct Modeline {
+    pub file_type: Option<String>,
+    pub indent_width: Option<usize>,
+    pub tab_width: Option<usize>,
+    pub soft_tab: Option<usize>,
+    pub expand_tab: Option<bool>,
+}
+
+impl Modeline {
+    pub fn apply(&self, options: &mut Options) {
+        if let Some(width) = self.indent_width {
+            options.indent_width = width;
+        }
+        if let Some(width) = self.tab_width {
+            options.tab_width = width;
+        }
+        if let Some(width) = self.soft_tab {
+            options.soft_tab = width;
+        }
+        if let Some(expand) = self.expand_tab {
+            options.expand_tab = expand;
+        }
+    }
+}
+
+// the first modeline in the first or last few lines of text
+pub fn modeline(text: &str) -> Option<Modeline> {
+    let lines: Vec<&str> = text.lines().collect();
+    let tail = lines.len().saturating_sub(MODELINE_LINES).max(MODELINE_LINES);
+
+    lines.iter()
+        .take(MODELINE_LINES)
+        .chain(lines.iter().skip(tail))
+        .find_map(|line| parse_modeline(line))
+}
+
+// `vim: ft=rust sw=2` or `vim: set ft=rust sw=2:`, vi: and ex: work too
+fn parse_modeline(line: &str) -> Option<Modeline> {
+    let start = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        let i = line.find(marker)?;
+        let before = line[..i].chars().last();
+        // a word like "navi:" isn't a modeline
+        if before.is_none_or(char::is_whitespace) {
+            Some(i + marker.len())
+        } else {
+            None
+        }
+    })?;
+
+    let rest = line[start..].trim_start();
+    let options = match rest.strip_prefix("set ").or(rest.strip_prefix("se ")) {
+        // ends at the next colon, anything after it is the end of a comment
+        Some(rest) => rest.split(':').next().unwrap_or(""),
+        None => rest,
+    };
+
+    let mut modeline = Modeline::default();
+    for option in options.split([' ', ':']).filter(|o| !o.is_empty()) {
+        let (name, value) = option.split_once('=').unwrap_or((option, ""));
+        match name {
+            "ft" | "filetype" if !value.is_empty() => modeline.file_type = Some(value.to_string()),
+            "sw" | "shiftwidth" | "ts" | "tabstop" => {
+                if let Ok(width) = value.parse::<usize>() {
+                    // shiftwidth wins over tabstop when both are set
+                    if width > 0 && (modeline.indent_width.is_none() || name.starts_with('s')) {
+                        modeline.indent_width = Some(width);
+                    }
+                    if width > 0 && name.starts_with('t') {
+                        modeline.tab_width = Some(width);
+                    }
+                }
+            },
+            "sts" | "softtabstop" => modeline.soft_tab = value.parse().ok(),
+            "et" | "expandtab" => modeline.expand_tab = Some(true),
+            "noet" | "noexpandtab" => modeline.expand_tab = Some(false),
+            _ => {},
+        }
+    }
+
+    if modeline == Modeline::default() {
+        None
+    } else {
+        Some(modeline)
+    }
+}
+
+// the program a #! line runs, through env or not, without its version
+fn interpreter(first_line: &str) -> Option<&str> {
+    let command = first_line.strip_prefix("#!")?.trim();
+    let mut words = command.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-'))?;
+    }
+
+    Some(program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.'))
+}
+
+// the file type for a file with the given contents, path is None for unnamed buffers
+pub fn detect(path: Option<&Path>, text: &str) -> Option<&'static FileType> {
+    if let Some(name) = modeline(text).and_then(|m| m.file_type) {
+        if let Some(file_type) = by_name(&name) {
+            return Some(file_type);
+        }
+    }
+
+    if let Some(path) = path {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if let Some(file_type) = FILE_TYPES.iter().find(|ft| ft.filenames.contains(&name)) {
+            return Some(file_type);
+        }
+
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            if let Some(file_type) = FILE_TYPES.iter().find(|ft| ft.extensions.contains(&ext)) {
+                return Some(file_type);
+            }
+        }
+    }
+
+    let program = interpreter(text.lines().next()?)?;
+    FILE_TYPES.iter().find(|ft| ft.interpreters.contains(&program))
+}
+
+
+#[test]
+fn test_detect() {
+    let name = |path: &str, text: &str| detect(Some(Path::new(path)), text).map(|ft| ft.name);
+
+    assert_eq!(name("src/main.rs", ""), Some("rust"));
+    assert_eq!(name("Cargo.lock", ""), Some("toml"));
+    assert_eq!(name("sub/Makefile", "all:\n"), Some("make"));
+    assert_eq!(name("notes.txt", ""), None);
+
+    // scripts without an extension
+    assert_eq!(name("bin/run", "#!/usr/bin/env python3\nprint(1)\n"), Some("python"));
+    assert_eq!(name("bin/build", "#!/bin/bash -e\n"), Some("sh"));
+    assert_eq!(name("bin/other", "#!/usr/bin/perl\n"), None);
+
+    // a modeline beats everything else
+    assert_eq!(name("build.txt", "# vim: set ft=python:\n"), Some("python"));
+    assert_eq!(name("main.rs", "// vim: ft=unknown\n"), Some("rust"));
+    assert_eq!(detect(None, "-- vi: ft=lua\n").map(|ft| ft.name), Some("lua"));
+}
+
+#[test]
+fn test_modeline() {
+    let text = "fn main() {}\n\n\n\n\n\n\n// vim: set sw=2 ts=8 noet: trailing\n";
+    let modeline = modeline(text).unwrap();
+    assert_eq!(modeline, Modeline { file_type: None, indent_width: Some(2), tab_width: Some(8), soft_tab: None, expand_tab: Some(false) });
+
+    let mut options = Options::for_type(by_name("make"));
+    assert_eq!((options.indent_width, options.expand_tab), (8, false));
+    Modeline { indent_width: Some(3), expand_tab: Some(true), ..Modeline::default() }.apply(&mut options);
+    assert_eq!((options.indent_width, options.expand_tab), (3, true));
+
+    assert!(parse_modeline("the navi: ft=rust").is_none());
+    assert!(parse_modeline("vim: nothing useful").is_none());
+    assert_eq!(parse_modeline("# vim:ts=4:et").unwrap().indent_width, Some(4));
+    assert_eq!(parse_modeline("# vim: sts=2").unwrap().soft_tab, Some(2));
+}
+
+#[test]
+fn test_set() {
+    let mut options = Options::for_type(by_name("go"));
+    assert_eq!(options.display(), "tabstop=8 shiftwidth=8 softtabstop=0 noexpandtab autopairs");
+
+    assert_eq!(options.set("ts=4 sw=4 sts=4 et"), Ok(None));
+    assert_eq!((options.tab_width, options.indent_width, options.soft_tab, options.expand_tab), (4, 4, 4, true));
+    assert_eq!(options.set("sw? noet et?"), Ok(Some(String::from("sw=4 noexpandtab"))));
+    assert_eq!(options.set("ts"), Ok(Some(String::from("ts=4"))));
+    assert!(options.set("ts=0").is_err());
+    assert!(options.set("wrap").is_err());
+    assert_eq!(options.set("noap ap?"), Ok(Some(String::from("noautopairs"))));
+    assert!(options.set("ap=1").is_err());
+
+    // another file type keeps what was set by hand
+    options.set_formatter(None);
+    let python = options.for_new_type(by_name("python"));
+    assert_eq!((python.tab_width, python.indent_width, python.expand_tab, python.auto_pairs), (4, 4, false, false));
+    assert_eq!((python.comment.as_deref(), python.formatter.as_deref()), (Some("#"), None));
+}
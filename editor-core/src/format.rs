@@ -0,0 +1,77 @@
+use crate::shell;
+use std::path::Path;
+
+/*
+ * formatting a buffer through an external command before it is written
+ *
+ * the formatter reads the text on stdin and prints the formatted text, a %
+ * argument in the command is replaced by the file's path for tools that pick
+ * their rules from it. a % inside of another argument, like printf's %s, is
+ * left alone
+ *
+ * the file type defaults are only used when their program is installed, so a
+ * missing formatter never stops a file from being saved
+ */
+
+// runs text through the formatter, failures come back with what it wrote to stderr
+pub fn format(cmd: &str, text: &str, path: &Path) -> Result<String, String> {
+    let quoted = format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"));
+    let cmd = cmd.split(' ')
+        .map(|arg| if arg == "%" { quoted.as_str() } else { arg })
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let dir = path.parent().filter(|dir| dir.is_dir());
+
+    let output = shell::run(&cmd, Some(text), dir)
+        .map_err(|err| format!("Can't run formatter: {}", err.kind()))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim_end_matches('\n');
+    match output.status.code() {
+        Some(0) => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        Some(code) => Err(format!("{}\nformatter returned {}", stderr, code).trim_start().to_string()),
+        None => Err(format!("{}\nformatter was terminated", stderr).trim_start().to_string()),
+    }
+}
+
+// where a char offset in old ends up in new, formatters mostly move whitespace
+// around so the cursor stays after the same number of other characters
+pub fn map_offset(old: &str, offset: usize, new: &str) -> usize {
+    let before = old.chars().take(offset).filter(|c| !c.is_whitespace()).count();
+
+    let mut seen = 0;
+    for (i, c) in new.chars().enumerate() {
+        if !c.is_whitespace() {
+            if seen == before {
+                return i;
+            }
+            seen += 1;
+        }
+    }
+
+    new.chars().count().saturating_sub(1)
+}
+
+
+#[cfg(unix)]
+#[test]
+fn test_format() {
+    assert_eq!(format("tr a-z A-Z", "fn main() {}\n", Path::new("main.rs")).unwrap(), "FN MAIN() {}\n");
+    assert_eq!(format("echo %", "", Path::new("it's.rs")).unwrap(), "it's.rs\n");
+    assert_eq!(format("printf '%s-%%\\n' %", "", Path::new("a.rs")).unwrap(), "a.rs-%\n");
+
+    let err = format("echo 'expected `;`' >&2; exit 1", "", Path::new("main.rs")).unwrap_err();
+    assert_eq!(err, "expected `;`\nformatter returned 1");
+}
+
+#[test]
+fn test_map_offset() {
+    let old = "fn main(){\n  let x=1;\n}\n";
+    let new = "fn main() {\n    let x = 1;\n}\n";
+
+    // on the 1 of x=1
+    let offset = old.find('1').unwrap();
+    assert_eq!(map_offset(old, offset, new), new.find('1').unwrap());
+    assert_eq!(map_offset(old, 0, new), 0);
+    assert_eq!(map_offset(old, old.len(), "x"), 0);
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/*
+ * just enough git to mark changed files in the file tree
+ *
+ * statuses are ordered by how much attention they need, a directory shows
+ * the highest status of anything inside of it
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GitStatus {
+    Untracked,
+    Renamed,
+    Modified,
+    Added,
+    Deleted,
+    Conflicted,
+}
+
+impl GitStatus {
+    pub fn marker(&self) -> char {
+        match self {
+            GitStatus::Untracked => '?',
+            GitStatus::Renamed => 'R',
+            GitStatus::Modified => 'M',
+            GitStatus::Added => 'A',
+            GitStatus::Deleted => 'D',
+            GitStatus::Conflicted => 'U',
+        }
+    }
+}
+
+// top of the repository path is in
+pub fn repo_root(path: &Path) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let root = String::from_utf8_lossy(&output.stdout).trim_end().to_string();
+    Some(PathBuf::from(root))
+}
+
+// status of every changed path in the repository path is in, keyed by full path,
+// empty when it isn't in one or git isn't around
+pub fn status(path: &Path) -> HashMap<PathBuf, GitStatus> {
+    let root = match repo_root(path) {
+        Some(root) => root,
+        None => return HashMap::new(),
+    };
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(&root)
+        .args(["status", "--porcelain", "-z"])
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_status(&root, &String::from_utf8_lossy(&output.stdout))
+        },
+        _ => HashMap::new(),
+    }
+}
+
+// reads `git status --porcelain -z`, directories get the status of their children
+fn parse_status(root: &Path, text: &str) -> HashMap<PathBuf, GitStatus> {
+    let mut statuses = HashMap::new();
+    let mut records = text.split('\0').filter(|r| r.len() > 3);
+
+    while let Some(record) = records.next() {
+        let (code, path) = record.split_at(3);
+        let mut code = code.chars();
+        let (x, y) = (code.next().unwrap_or(' '), code.next().unwrap_or(' '));
+
+        let status = match (x, y) {
+            ('?', '?') => GitStatus::Untracked,
+            ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => GitStatus::Conflicted,
+            ('D', _) | (_, 'D') => GitStatus::Deleted,
+            ('A', _) => GitStatus::Added,
+            ('R', _) | ('C', _) => GitStatus::Renamed,
+            _ => GitStatus::Modified,
+        };
+
+        // renames are followed by the path they came from
+        if x == 'R' || x == 'C' {
+            records.next();
+        }
+
+        let path = root.join(path.trim_end_matches('/'));
+        for dir in path.ancestors().skip(1).take_while(|p| p.starts_with(root) && *p != root) {
+            let entry = statuses.entry(dir.to_path_buf()).or_insert(status);
+            *entry = (*entry).max(status);
+        }
+        statuses.insert(path, status);
+    }
+
+    statuses
+}
+
+
+#[test]
+fn test_parse_status() {
+    let root = Path::new("/repo");
+    let text = " M src/main.rs\0?? notes/\0R  src/new.rs\0src/old.rs\0UU src/deep/merge.rs\0";
+    let statuses = parse_status(root, text);
+
+    assert_eq!(statuses.get(&root.join("src/main.rs")), Some(&GitStatus::Modified));
+    assert_eq!(statuses.get(&root.join("notes")), Some(&GitStatus::Untracked));
+    assert_eq!(statuses.get(&root.join("src/new.rs")), Some(&GitStatus::Renamed));
+    assert_eq!(statuses.get(&root.join("src/old.rs")), None);
+    assert_eq!(statuses.get(&root.join("src")), Some(&GitStatus::Conflicted));
+    assert_eq!(statuses.get(&root.join("src/deep")), Some(&GitStatus::Conflicted));
+    assert_eq!(statuses.get(root), None);
+}
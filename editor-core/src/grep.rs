@@ -0,0 +1,91 @@
+use crate::quickfix::Entry;
+use ignore::WalkBuilder;
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+/*
+ * searching files for a regex, the same files the picker lists
+ *
+ * files with a nul byte near the start are taken to be binary and skipped
+ */
+
+// stop collecting after this many matches
+const MAX_MATCHES: usize = 10_000;
+
+// bytes looked at to decide if a file is binary
+const BINARY_CHECK: usize = 8 * 1024;
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_CHECK)].contains(&0)
+}
+
+// every match of pattern in the files under path, path can also be a single file,
+// entries are relative to base when they are inside of it
+pub fn grep(pattern: &Regex, path: &Path, base: &Path) -> Vec<Entry> {
+    let mut entries = vec![];
+
+    let walker = WalkBuilder::new(path)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+    for entry in walker.flatten() {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+
+        let bytes = match fs::read(entry.path()) {
+            Ok(bytes) if !is_binary(&bytes) => bytes,
+            _ => continue,
+        };
+        let text = String::from_utf8_lossy(&bytes);
+        let file = entry.path().strip_prefix(base).unwrap_or(entry.path());
+
+        for (line, content) in text.lines().enumerate() {
+            if let Some(m) = pattern.find(content) {
+                entries.push(Entry {
+                    path: file.to_path_buf(),
+                    line,
+                    col: content[..m.start()].chars().count(),
+                    text: content.trim().to_string(),
+                    severity: None,
+                });
+
+                if entries.len() >= MAX_MATCHES {
+                    return entries;
+                }
+            }
+        }
+    }
+
+    entries
+}
+
+
+#[test]
+fn test_grep() {
+    let dir = std::env::temp_dir().join(format!("editor-grep-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join("build")).unwrap();
+    fs::write(dir.join("src/a.rs"), "fn main() {\n    let needle = 1;\n}\n").unwrap();
+    fs::write(dir.join("b.txt"), "no needle here\nneedle\n").unwrap();
+    fs::write(dir.join("build/out.txt"), "needle\n").unwrap();
+    fs::write(dir.join("blob.bin"), b"needle\0\x01").unwrap();
+    fs::write(dir.join(".gitignore"), "build/\n").unwrap();
+
+    let pattern = Regex::new("need+le").unwrap();
+    let entries = grep(&pattern, &dir, &dir);
+
+    let found: Vec<String> = entries.iter().map(|e| e.display()).collect();
+    assert_eq!(found, vec![
+        "b.txt:1:4: no needle here",
+        "b.txt:2:1: needle",
+        "src/a.rs:2:9: let needle = 1;",
+    ]);
+
+    // a single file works too
+    assert_eq!(grep(&pattern, &dir.join("b.txt"), &dir).len(), 2);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
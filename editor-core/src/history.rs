@@ -0,0 +1,104 @@
+use ropey::Rope;
+
+/*
+ * undo history for a buffer
+ *
+ * ropes share their nodes when cloned, so each revision keeps a full snapshot
+ * of the text instead of an inverse edit
+ *
+ * every state of the text gets an id, which is what the buffer compares against
+ * the saved state to know if it was modified
+ */
+
+#[derive(Clone)]
+pub struct Revision {
+    pub id: usize,
+    pub rope: Rope,
+    pub cursor: (usize, usize),
+    pub ptr_y: usize,
+}
+
+pub struct History {
+    undo: Vec<Revision>,
+    redo: Vec<Revision>,
+    next_id: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    pub fn new() -> Self {
+        History { undo: vec![], redo: vec![], next_id: 1 }
+    }
+
+    // id for a state of the text that hasn't been seen before, 0 is the text a buffer starts with
+    pub fn new_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    // records the state from before an edit, a new edit drops anything that was undone
+    pub fn push(&mut self, revision: Revision) {
+        self.undo.push(revision);
+        self.redo.clear();
+    }
+
+    // forgets every revision, ids given out after this are still new ones
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, current: Revision) -> Option<Revision> {
+        let revision = self.undo.pop()?;
+        self.redo.push(current);
+        Some(revision)
+    }
+
+    pub fn redo(&mut self, current: Revision) -> Option<Revision> {
+        let revision = self.redo.pop()?;
+        self.undo.push(current);
+        Some(revision)
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+}
+
+
+#[test]
+fn test_history() {
+    let rev = |text: &str| Revision { id: text.len(), rope: Rope::from_str(text), cursor: (0, 0), ptr_y: 0 };
+
+    let mut history = History::new();
+    assert!(history.undo(rev("a")).is_none());
+
+    history.push(rev("a"));
+    history.push(rev("ab"));
+
+    let undone = history.undo(rev("abc")).unwrap();
+    assert_eq!(undone.rope.to_string(), "ab");
+    assert_eq!(undone.id, 2);
+    assert_eq!(history.redo_len(), 1);
+
+    let redone = history.redo(undone).unwrap();
+    assert_eq!(redone.rope.to_string(), "abc");
+
+    history.undo(redone);
+    history.push(rev("ab"));
+    assert_eq!(history.redo_len(), 0);
+    assert_eq!(history.undo_len(), 2);
+
+    assert_eq!(history.new_id(), 1);
+    assert_eq!(history.new_id(), 2);
+}
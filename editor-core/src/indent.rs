@@ -0,0 +1,238 @@
+use crate::filetype::Options;
+
+/*
+ * columns of text with tabs in it, and the whitespace that makes up an indent
+ *
+ * a tab goes to the next multiple of the tab width, an indent is written with
+ * tabs where they fit unless the buffer expands them to spaces
+ *
+ * the indent a line should have comes from the code line before it: a level
+ * more after a line that leaves a bracket open or ends in one of the file
+ * type's block openers, like : in python, a level less after one that closes
+ * more than it opens and for a line starting with a closing bracket
+ */
+
+const OPENERS: [char; 3] = ['(', '[', '{'];
+const CLOSERS: [char; 3] = [')', ']', '}'];
+
+// the column a tab at col ends at
+pub fn next_tab_stop(col: usize, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    (col / tab_width + 1) * tab_width
+}
+
+// the screen column a char of the line starts at
+pub fn display_col(line: &str, char_col: usize, tab_width: usize) -> usize {
+    line.chars()
+        .take(char_col)
+        .fold(0, |col, c| match c {
+            '\t' => next_tab_stop(col, tab_width),
+            _ => col + 1,
+        })
+}
+
+// the char of the line shown at a screen column
+pub fn char_col(line: &str, display: usize, tab_width: usize) -> usize {
+    let mut col = 0;
+    for (i, c) in line.chars().enumerate() {
+        col = match c {
+            '\t' => next_tab_stop(col, tab_width),
+            '\n' => return i,
+            _ => col + 1,
+        };
+        if col > display {
+            return i;
+        }
+    }
+    line.chars().count()
+}
+
+// the line as it is shown, tabs turned into spaces
+pub fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut text = String::new();
+    let mut col = 0;
+
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let next = next_tab_stop(col, tab_width);
+                text.push_str(&" ".repeat(next - col));
+                col = next;
+            },
+            c => {
+                text.push(c);
+                col += 1;
+            },
+        }
+    }
+    text
+}
+
+// the spaces and tabs the line starts with
+pub fn leading(line: &str) -> &str {
+    let end = line.len() - line.trim_start_matches([' ', '\t']).len();
+    &line[..end]
+}
+
+// how wide the line's indent is on screen
+pub fn indent_of(line: &str, tab_width: usize) -> usize {
+    let indent = leading(line);
+    display_col(indent, indent.chars().count(), tab_width)
+}
+
+// whitespace that goes from one screen column to another, with tabs unless they are expanded
+pub fn fill(from: usize, to: usize, options: &Options) -> String {
+    if options.expand_tab {
+        return " ".repeat(to.saturating_sub(from));
+    }
+
+    let mut text = String::new();
+    let mut col = from;
+    while next_tab_stop(col, options.tab_width) <= to {
+        text.push('\t');
+        col = next_tab_stop(col, options.tab_width);
+    }
+    text.push_str(&" ".repeat(to.saturating_sub(col)));
+    text
+}
+
+// the line with its indent made a given width
+pub fn with_indent(line: &str, width: usize, options: &Options) -> String {
+    let rest = &line[leading(line).len()..];
+    // blank lines don't keep whitespace
+    if rest.trim_end_matches('\n').is_empty() {
+        return rest.to_string();
+    }
+    format!("{}{}", fill(0, width, options), rest)
+}
+
+// the line moved a number of shift widths to the right, or the left when negative
+pub fn shift(line: &str, by: isize, options: &Options) -> String {
+    let width = indent_of(line, options.tab_width);
+    let change = by.unsigned_abs() * options.indent_width;
+    let width = match by < 0 {
+        true => width.saturating_sub(change),
+        false => width + change,
+    };
+    with_indent(line, width, options)
+}
+
+// the line without what is in strings or after a line comment
+fn code(line: &str, comment: Option<&str>) -> String {
+    let mut code = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if !escaped && c == q {
+                    quote = None;
+                    code.push(c);
+                }
+                escaped = !escaped && c == '\\';
+            },
+            None => {
+                if comment.is_some_and(|m| line[i..].starts_with(m)) {
+                    break;
+                }
+                if c == '"' || c == '`' {
+                    quote = Some(c);
+                }
+                code.push(c);
+            },
+        }
+    }
+    code
+}
+
+// closing brackets the line starts with
+pub fn leading_closers(line: &str) -> usize {
+    line.trim_start().chars().take_while(|c| CLOSERS.contains(c)).count()
+}
+
+// brackets left open less the ones closed, not counting the closers it starts with
+fn balance(code: &str) -> isize {
+    let rest = code.trim_start().trim_start_matches(CLOSERS);
+    rest.chars().fold(0, |depth, c| match c {
+        c if OPENERS.contains(&c) => depth + 1,
+        c if CLOSERS.contains(&c) => depth - 1,
+        _ => depth,
+    })
+}
+
+// whether a level starts after the line, a bracket left open or a block opener at its end
+pub fn opens_block(line: &str, options: &Options, openers: &[&str]) -> bool {
+    let code = code(line.trim_end(), options.comment.as_deref());
+    let code = code.trim_end();
+    balance(code) > 0 || openers.iter().any(|o| code.ends_with(o))
+}
+
+// the indent for a line following the code line prev, from prev's own indent
+pub fn indent_for(prev: Option<&str>, line: &str, options: &Options, openers: &[&str]) -> usize {
+    let prev = match prev {
+        Some(prev) => prev,
+        None => return 0,
+    };
+    let mut width = indent_of(prev, options.tab_width);
+
+    let code = code(prev.trim_end(), options.comment.as_deref());
+    if opens_block(prev, options, openers) {
+        width += options.indent_width;
+    } else if balance(&code) < 0 {
+        width = width.saturating_sub(options.indent_width);
+    }
+
+    if leading_closers(line) > 0 {
+        width = width.saturating_sub(options.indent_width);
+    }
+    width
+}
+
+// the closer that goes with an opener
+pub fn closer_for(opener: char) -> Option<char> {
+    OPENERS.iter().position(|o| *o == opener).map(|i| CLOSERS[i])
+}
+
+pub fn is_closer(c: char) -> bool {
+    CLOSERS.contains(&c)
+}
+
+
+#[test]
+fn test_columns() {
+    let line = "\tif x {\t// y\n";
+    assert_eq!(display_col(line, 1, 4), 4);
+    assert_eq!(display_col(line, 7, 4), 10);
+    assert_eq!(display_col(line, 8, 4), 12);
+    assert_eq!((char_col(line, 2, 4), char_col(line, 11, 4), char_col(line, 40, 4)), (0, 7, 12));
+    assert_eq!(expand_tabs(line, 4), "    if x {  // y\n");
+    assert_eq!(indent_of("  \t x", 8), 9);
+}
+
+#[test]
+fn test_shift() {
+    let mut options = Options { indent_width: 4, tab_width: 8, expand_tab: false, ..Options::default() };
+    assert_eq!(shift("    x\n", 1, &options), "\tx\n");
+    assert_eq!(shift("\tx\n", 1, &options), "\t    x\n");
+    assert_eq!(shift("  x\n", -1, &options), "x\n");
+    assert_eq!(shift("\n", 1, &options), "\n");
+
+    options.expand_tab = true;
+    assert_eq!(shift("\tx\n", -1, &options), "    x\n");
+    assert_eq!(fill(2, 8, &options), "      ");
+}
+
+#[test]
+fn test_indent_for() {
+    let options = Options { comment: Some(String::from("//")), ..Options::default() };
+    let indent = |prev: &str, line: &str| indent_for(Some(prev), line, &options, &[]);
+
+    assert_eq!(indent("fn main() {", "let x = 1;"), 4);
+    assert_eq!(indent("    let x = 1;", "}"), 0);
+    assert_eq!(indent("    } else {", "x"), 8);
+    assert_eq!(indent("    let x = f(", "a,"), 8);
+    assert_eq!(indent("        b);", "next"), 4);
+    assert_eq!(indent("    let s = \"{\"; // {", "next"), 4);
+    assert_eq!(indent_for(Some("if x:  # why"), "y", &Options { comment: Some(String::from("#")), ..Options::default() }, &[":"]), 4);
+}
@@ -1,5 +1,25 @@
 pub mod buffer;
 pub mod command;
+pub mod comment;
+pub mod completion;
+pub mod diagnostic;
+pub mod explorer;
+pub mod file;
+pub mod filetype;
+pub mod format;
+pub mod git;
+pub mod grep;
+pub mod history;
+pub mod indent;
+pub mod make;
+pub mod pair;
+pub mod picker;
+pub mod quickfix;
+pub mod shell;
+pub mod snippet;
+pub mod terminal;
+pub mod text;
+pub mod tree;
 pub mod word;
 
 pub static X_OFFSET: usize = 5;
@@ -0,0 +1,222 @@
+use crate::quickfix::{Entry, Severity};
+use regex::Regex;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/*
+ * reading compiler output into quickfix entries
+ *
+ * an errorformat is a list of line patterns split by commas, like vim's:
+ *   %f  file     %l  line     %c  column
+ *   %m  message  %t  type, e for errors and w for warnings
+ *   %%  a literal %, \, a literal comma
+ *
+ * cargo's json messages are read natively when the command asks for them
+ */
+
+pub const DEFAULT_MAKE: &str = "cargo build --message-format=short";
+pub const DEFAULT_ERROR_FORMAT: &str = "%f:%l:%c: %m";
+
+pub struct ErrorFormat {
+    patterns: Vec<Regex>,
+}
+
+impl ErrorFormat {
+    pub fn new(format: &str) -> Result<Self, String> {
+        let mut patterns = vec![];
+
+        for part in split_formats(format) {
+            let mut regex = String::from("^");
+            let mut chars = part.chars();
+
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    regex.push_str(&regex::escape(&c.to_string()));
+                    continue;
+                }
+
+                match chars.next() {
+                    Some('f') => regex.push_str(r"(?P<f>.+?)"),
+                    Some('l') => regex.push_str(r"(?P<l>\d+)"),
+                    Some('c') => regex.push_str(r"(?P<c>\d+)"),
+                    Some('m') => regex.push_str(r"(?P<m>.*)"),
+                    Some('t') => regex.push_str(r"(?P<t>\w)"),
+                    Some('%') => regex.push('%'),
+                    Some(other) => return Err(format!("Unknown errorformat item: %{}", other)),
+                    None => return Err(String::from("Errorformat ends with %")),
+                }
+            }
+            regex.push('$');
+
+            if !regex.contains("(?P<f>") || !regex.contains("(?P<l>") {
+                return Err(format!("Errorformat needs %f and %l: {}", part));
+            }
+
+            let regex = Regex::new(&regex).map_err(|err| format!("Invalid errorformat: {}", err))?;
+            patterns.push(regex);
+        }
+
+        if patterns.is_empty() {
+            return Err(String::from("Empty errorformat"));
+        }
+
+        Ok(ErrorFormat { patterns })
+    }
+
+    // an entry for every line that one of the patterns matches
+    pub fn parse(&self, text: &str) -> Vec<Entry> {
+        text.lines()
+            .filter_map(|line| {
+                let caps = self.patterns.iter().find_map(|p| p.captures(line))?;
+
+                let number = |name| caps.name(name).and_then(|m| m.as_str().parse::<usize>().ok());
+                let message = caps.name("m").map_or("", |m| m.as_str()).trim().to_string();
+                let severity = match caps.name("t").map(|m| m.as_str()) {
+                    Some("e" | "E") => Severity::Error,
+                    Some("w" | "W") => Severity::Warning,
+                    Some(_) => Severity::Info,
+                    None => severity_of(&message).unwrap_or(Severity::Error),
+                };
+
+                Some(Entry {
+                    path: PathBuf::from(caps.name("f")?.as_str()),
+                    line: number("l")?.saturating_sub(1),
+                    col: number("c").unwrap_or(1).saturating_sub(1),
+                    text: message,
+                    severity: Some(severity),
+                })
+            })
+            .collect()
+    }
+}
+
+// commas split formats unless they are escaped
+fn split_formats(format: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(',') => parts.last_mut().unwrap().push(','),
+                Some(c) => {
+                    parts.last_mut().unwrap().push('\\');
+                    parts.last_mut().unwrap().push(c);
+                },
+                None => parts.last_mut().unwrap().push('\\'),
+            },
+            ',' => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+
+    parts.into_iter().filter(|p| !p.is_empty()).collect()
+}
+
+// rustc and most compilers start their messages with the level
+fn severity_of(message: &str) -> Option<Severity> {
+    let level = message.split([':', '[']).next()?.trim();
+
+    match level {
+        "error" | "fatal error" => Some(Severity::Error),
+        "warning" => Some(Severity::Warning),
+        "note" | "help" | "info" => Some(Severity::Info),
+        _ => None,
+    }
+}
+
+pub fn is_json(cmd: &str) -> bool {
+    cmd.contains("--message-format=json") || cmd.contains("--message-format json")
+}
+
+// entries from `cargo build --message-format=json`, one json object per line,
+// each message goes to its primary span
+pub fn parse_cargo_json(text: &str) -> Vec<Entry> {
+    let mut entries = vec![];
+
+    for line in text.lines() {
+        let value: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+
+        if value["reason"] != "compiler-message" {
+            continue;
+        }
+
+        let message = &value["message"];
+        let level = message["level"].as_str().unwrap_or("error");
+        let spans = message["spans"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+        let span = match spans.iter().find(|s| s["is_primary"] == true).or(spans.first()) {
+            Some(span) => span,
+            None => continue,
+        };
+
+        let number = |name: &str| span[name].as_u64().unwrap_or(1) as usize;
+        let path = match span["file_name"].as_str() {
+            Some(path) => PathBuf::from(path),
+            None => continue,
+        };
+
+        entries.push(Entry {
+            path,
+            line: number("line_start").saturating_sub(1),
+            col: number("column_start").saturating_sub(1),
+            text: format!("{}: {}", level, message["message"].as_str().unwrap_or("")),
+            severity: Some(severity_of(level).unwrap_or(Severity::Error)),
+        });
+    }
+
+    entries
+}
+
+// reads the output of cmd the way it was asked to be printed
+pub fn parse_output(cmd: &str, format: &ErrorFormat, text: &str) -> Vec<Entry> {
+    if is_json(cmd) {
+        parse_cargo_json(text)
+    } else {
+        format.parse(text)
+    }
+}
+
+
+#[test]
+fn test_error_format() {
+    let format = ErrorFormat::new(DEFAULT_ERROR_FORMAT).unwrap();
+    let output = "   Compiling demo v0.1.0\n\
+        src/main.rs:2:9: warning: unused variable: `x`\n\
+        src/lib.rs:10:5: error[E0425]: cannot find value `y` in this scope\n\
+        error: could not compile `demo`\n";
+
+    let entries = format.parse(output);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].display(), "src/main.rs:2:9: warning: unused variable: `x`");
+    assert_eq!(entries[0].severity, Some(Severity::Warning));
+    assert_eq!((entries[1].line, entries[1].col), (9, 4));
+    assert_eq!(entries[1].severity, Some(Severity::Error));
+
+    // several patterns, with a type and no column
+    let err = ErrorFormat::new(r"%f(%l): %t%*: %m").err().unwrap();
+    assert_eq!(err, "Unknown errorformat item: %*");
+    let format = ErrorFormat::new(r"%f(%l): %t: %m,%f line %l\, %m").unwrap();
+    let entries = format.parse("a.c(3): w: careful\nb.c line 7, broken\n");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].severity, Some(Severity::Warning));
+    assert_eq!((entries[1].path.to_str().unwrap(), entries[1].line, entries[1].col), ("b.c", 6, 0));
+
+    assert!(ErrorFormat::new("%m").is_err());
+}
+
+#[test]
+fn test_parse_cargo_json() {
+    let output = r#"{"reason":"compiler-artifact","package_id":"demo"}
+{"reason":"compiler-message","message":{"level":"error","message":"mismatched types","spans":[{"file_name":"src/a.rs","line_start":4,"column_start":2,"is_primary":false},{"file_name":"src/main.rs","line_start":12,"column_start":18,"is_primary":true}]}}
+{"reason":"compiler-message","message":{"level":"warning","message":"2 warnings emitted","spans":[]}}
+not json
+"#;
+
+    let entries = parse_output("cargo build --message-format=json", &ErrorFormat::new(DEFAULT_ERROR_FORMAT).unwrap(), output);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].display(), "src/main.rs:12:18: error: mismatched types");
+    assert_eq!(entries[0].severity, Some(Severity::Error));
+}
@@ -0,0 +1,210 @@
+use std::ops::Range;
+use ropey::Rope;
+
+/*
+ * the other half of a pair in the rope, for % and the highlight by the cursor
+ *
+ * brackets are matched by counting the same kind in between, block comments by
+ * the nearest other end and #if, #else and #endif lines by the ones at the same
+ * depth, going from #endif back up to its #if
+ *
+ * it is all plain text, so a bracket in a string or a comment still counts
+ */
+
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+pub fn is_bracket(c: char) -> bool {
+    BRACKETS.iter().any(|(open, close)| c == *open || c == *close)
+}
+
+// the other bracket of the one at idx, only looking at chars in within
+pub fn bracket_match(rope: &Rope, idx: usize, within: Range<usize>) -> Option<usize> {
+    let c = rope.get_char(idx)?;
+    let &(open, close) = BRACKETS.iter().find(|(open, close)| c == *open || c == *close)?;
+
+    let end = within.end.min(rope.len_chars());
+    let mut depth = 0;
+    if c == open {
+        for (i, c) in rope.chars_at(idx + 1).enumerate().take(end.saturating_sub(idx + 1)) {
+            match c {
+                c if c == open => depth += 1,
+                c if c == close && depth == 0 => return Some(idx + 1 + i),
+                c if c == close => depth -= 1,
+                _ => {},
+            }
+        }
+    } else {
+        let mut chars = rope.chars_at(idx);
+        let mut i = idx;
+        while i > within.start {
+            i -= 1;
+            match chars.prev()? {
+                c if c == close => depth += 1,
+                c if c == open && depth == 0 => return Some(i),
+                c if c == open => depth -= 1,
+                _ => {},
+            }
+        }
+    }
+    None
+}
+
+// the opener of the kind of bracket left open before idx, or the closer that closes one after it
+pub fn enclosing(rope: &Rope, idx: usize, bracket: char) -> Option<usize> {
+    let &(open, close) = BRACKETS.iter().find(|(open, close)| bracket == *open || bracket == *close)?;
+    let mut depth = 0;
+
+    if bracket == open {
+        let mut chars = rope.chars_at(idx.min(rope.len_chars()));
+        let mut i = idx;
+        while let Some(c) = chars.prev() {
+            i -= 1;
+            match c {
+                c if c == close => depth += 1,
+                c if c == open && depth == 0 => return Some(i),
+                c if c == open => depth -= 1,
+                _ => {},
+            }
+        }
+    } else {
+        for (i, c) in rope.chars_at((idx + 1).min(rope.len_chars())).enumerate() {
+            match c {
+                c if c == open => depth += 1,
+                c if c == close && depth == 0 => return Some(idx + 1 + i),
+                c if c == close => depth -= 1,
+                _ => {},
+            }
+        }
+    }
+    None
+}
+
+// the other end of a /* */ comment when idx is on one of its two chars
+fn comment_match(rope: &Rope, idx: usize) -> Option<usize> {
+    let text = |from: usize| -> String { rope.chars_at(from).take(2).collect() };
+
+    let on = [Some(idx), idx.checked_sub(1)].into_iter()
+        .flatten()
+        .find(|i| matches!(text(*i).as_str(), "/*" | "*/"))?;
+    // the scan stops at the nearest other end, without copying the text around it
+    let mut last = None;
+    if text(on) == "/*" {
+        for (i, c) in rope.chars_at(on + 2).enumerate() {
+            if last == Some('*') && c == '/' {
+                return Some(on + 2 + i);
+            }
+            last = Some(c);
+        }
+    } else {
+        let mut chars = rope.chars_at(on);
+        let mut i = on;
+        while let Some(c) = chars.prev() {
+            i -= 1;
+            if c == '/' && last == Some('*') {
+                return Some(i);
+            }
+            last = Some(c);
+        }
+    }
+    None
+}
+
+// the preprocessor directive a line is, like if for #ifdef
+fn directive(line: &str) -> Option<&'static str> {
+    let word = line.trim_start().strip_prefix('#')?.trim_start();
+    let word = &word[..word.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(word.len())];
+    match word {
+        "if" | "ifdef" | "ifndef" => Some("if"),
+        "elif" | "else" => Some("else"),
+        "endif" => Some("endif"),
+        _ => None,
+    }
+}
+
+// the line of the next #else or #endif for the one on line, #endif goes back to its #if
+fn directive_match(rope: &Rope, line: usize) -> Option<usize> {
+    let kind = directive(&rope.get_line(line)?.to_string())?;
+    let mut depth = 0;
+
+    if kind == "endif" {
+        for i in (0..line).rev() {
+            match directive(&rope.line(i).to_string()) {
+                Some("endif") => depth += 1,
+                Some("if") if depth == 0 => return Some(i),
+                Some("if") => depth -= 1,
+                _ => {},
+            }
+        }
+    } else {
+        for i in line + 1..rope.len_lines() {
+            match directive(&rope.line(i).to_string()) {
+                Some("if") => depth += 1,
+                Some(_) if depth == 0 => return Some(i),
+                Some("endif") => depth -= 1,
+                _ => {},
+            }
+        }
+    }
+    None
+}
+
+// where % goes from idx: the other end of the comment under it, of the first bracket from
+// it to the end of the line or of the directive the line is
+pub fn find_match(rope: &Rope, idx: usize, directives: bool) -> Option<usize> {
+    if let Some(found) = comment_match(rope, idx) {
+        return Some(found);
+    }
+
+    let line = rope.char_to_line(idx);
+    let line_end = rope.line_to_char(line) + rope.line(line).len_chars();
+    let bracket = rope.chars_at(idx)
+        .take(line_end - idx)
+        .position(is_bracket)
+        .map(|i| idx + i);
+    if let Some(found) = bracket.and_then(|b| bracket_match(rope, b, 0..rope.len_chars())) {
+        return Some(found);
+    }
+
+    match directives {
+        true => directive_match(rope, line).map(|l| rope.line_to_char(l) + rope.line(l).chars().take_while(|c| *c != '#').count()),
+        false => None,
+    }
+}
+
+
+#[test]
+fn test_bracket_match() {
+    let rope = Rope::from_str("fn f(a: [u8]) {\n    g(h(), \"(\");\n}\n");
+    assert_eq!(bracket_match(&rope, 4, 0..rope.len_chars()), Some(12));
+    assert_eq!(bracket_match(&rope, 12, 0..rope.len_chars()), Some(4));
+    assert_eq!(bracket_match(&rope, 14, 0..rope.len_chars()), Some(33));
+    assert_eq!(bracket_match(&rope, 33, 0..rope.len_chars()), Some(14));
+    assert_eq!(bracket_match(&rope, 14, 0..20), None);
+    assert_eq!(bracket_match(&rope, 0, 0..rope.len_chars()), None);
+
+    // from the h the enclosing ( is g's, h's own brackets are closed before it
+    assert_eq!(enclosing(&rope, 22, '('), Some(21));
+    assert_eq!(enclosing(&rope, 26, '('), Some(21));
+    assert_eq!(enclosing(&rope, 22, '{'), Some(14));
+    assert_eq!(enclosing(&rope, 22, '}'), Some(33));
+    assert_eq!(enclosing(&rope, 2, '{'), None);
+}
+
+#[test]
+fn test_find_match() {
+    let rope = Rope::from_str("/* a\n * b */\n#ifdef X\nx(1);\n#  else\n#if Y\n#endif\n#endif\n");
+    assert_eq!(find_match(&rope, 0, true), Some(11));
+    assert_eq!(find_match(&rope, 11, true), Some(0));
+    assert_eq!(find_match(&rope, 22, true), Some(25));
+    assert_eq!(find_match(&Rope::from_str("a /*/ b */"), 2, true), Some(9));
+    assert_eq!(find_match(&Rope::from_str("a /* b /*/"), 9, true), Some(2));
+    assert_eq!(find_match(&Rope::from_str("a /* b"), 2, true), None);
+
+    // #ifdef to #else to #endif and back up, skipping the nested #if
+    let start = |line: usize| rope.line_to_char(line);
+    assert_eq!(find_match(&rope, start(2), true), Some(start(4)));
+    assert_eq!(find_match(&rope, start(4), true), Some(start(7)));
+    assert_eq!(find_match(&rope, start(7), true), Some(start(2)));
+    assert_eq!(find_match(&rope, start(5), true), Some(start(6)));
+    assert_eq!(find_match(&rope, start(2), false), None);
+}
@@ -0,0 +1,304 @@
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+/*
+ * fuzzy picker for files, buffers, command history and marks
+ *
+ * the query matches when its characters appear in order in an item, matches
+ * score higher when they are next to each other or start a word
+ *
+ * typing more of a query can only narrow the matches, so only the items that
+ * matched before are scored again
+ */
+
+// max number of files listed from a project
+const MAX_FILES: usize = 50_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PickerKind {
+    Files { root: PathBuf },
+    Buffers,
+    History,
+    Marks,
+}
+
+impl PickerKind {
+    pub fn title(&self) -> &str {
+        match self {
+            PickerKind::Files { .. } => "Files",
+            PickerKind::Buffers => "Buffers",
+            PickerKind::History => "Command History",
+            PickerKind::Marks => "Marks",
+        }
+    }
+}
+
+// label is what gets matched and shown, id points back at what it stands for
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub label: String,
+    pub id: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub item: usize,
+    pub score: i64,
+    pub positions: Vec<usize>, // char indexes of the label that matched
+}
+
+pub struct Picker {
+    pub kind: PickerKind,
+    pub items: Vec<Item>,
+    pub query: String,
+    pub matches: Vec<Match>,
+    pub selected: usize,
+    pub offset: usize,
+    pub rows: usize, // how many matches fit on screen
+    pub preview: Option<(usize, Vec<String>)>, // item the preview lines belong to
+    scored: String, // query the matches were scored for
+}
+
+impl Picker {
+    pub fn new(kind: PickerKind, items: Vec<Item>) -> Self {
+        let matches = (0..items.len())
+            .map(|item| Match { item, score: 0, positions: vec![] })
+            .collect();
+
+        Picker {
+            kind,
+            items,
+            query: String::new(),
+            matches,
+            selected: 0,
+            offset: 0,
+            rows: 10,
+            preview: None,
+            scored: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.update();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.update();
+    }
+
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_string();
+        self.update();
+    }
+
+    fn update(&mut self) {
+        let candidates: Vec<usize> = if !self.scored.is_empty() && self.query.starts_with(&self.scored) {
+            self.matches.iter().map(|m| m.item).collect()
+        } else {
+            (0..self.items.len()).collect()
+        };
+
+        let mut matches: Vec<Match> = candidates
+            .into_iter()
+            .filter_map(|item| {
+                let (score, positions) = fuzzy_match(&self.query, &self.items[item].label)?;
+                Some(Match { item, score, positions })
+            })
+            .collect();
+
+        // best first, ties keep the order the items came in
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.item.cmp(&b.item)));
+
+        self.matches = matches;
+        self.scored = self.query.clone();
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    pub fn selected_item(&self) -> Option<&Item> {
+        let m = self.matches.get(self.selected)?;
+        self.items.get(m.item)
+    }
+
+    pub fn move_down(&mut self, height: usize) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+        if self.selected >= self.offset + height.max(1) {
+            self.offset = self.selected + 1 - height.max(1);
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        }
+    }
+}
+
+fn is_boundary(prev: Option<char>, c: char) -> bool {
+    match prev {
+        None => true,
+        Some(prev) => {
+            matches!(prev, '/' | '_' | '-' | '.' | ' ' | ':')
+                || (prev.is_lowercase() && c.is_uppercase())
+        },
+    }
+}
+
+// score of query against text with the positions it matched at, None when it doesn't,
+// an uppercase letter in the query makes it case sensitive
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    if query.is_empty() {
+        return Some((0, vec![]));
+    }
+
+    let sensitive = query.chars().any(|c| c.is_uppercase());
+    let fold = |c: char| if sensitive { c } else { c.to_ascii_lowercase() };
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).map(fold).collect();
+    let chars: Vec<char> = text.chars().collect();
+    let folded: Vec<char> = chars.iter().map(|c| fold(*c)).collect();
+
+    if query.is_empty() {
+        return Some((0, vec![]));
+    }
+
+    // first place the whole query fits
+    let mut q = 0;
+    let mut end = None;
+    for (i, c) in folded.iter().enumerate() {
+        if *c == query[q] {
+            q += 1;
+            if q == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // walk back from there to find the tightest start
+    let mut q = query.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if folded[i] == query[q - 1] {
+            q -= 1;
+            if q == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    // then forward again so every character takes its first spot in that window
+    let mut positions = vec![];
+    let mut q = 0;
+    for (i, c) in folded.iter().enumerate().take(end + 1).skip(start) {
+        if q < query.len() && *c == query[q] {
+            positions.push(i);
+            q += 1;
+        }
+    }
+
+    let mut score: i64 = 0;
+    for (n, &i) in positions.iter().enumerate() {
+        score += 16;
+
+        if is_boundary(if i == 0 { None } else { Some(chars[i - 1]) }, chars[i]) {
+            score += 10;
+        }
+        if n > 0 && positions[n - 1] + 1 == i {
+            score += 15;
+        } else if n > 0 {
+            score -= (i - positions[n - 1] - 1).min(10) as i64;
+        }
+    }
+
+    // matches in the file name beat matches in the directories above it
+    let name_start = chars.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+    if positions.first().is_some_and(|p| *p >= name_start) {
+        score += 8;
+    }
+
+    // shorter items are closer to what was typed
+    score -= (chars.len() / 8) as i64;
+
+    Some((score, positions))
+}
+
+// files under root relative to it, skipping hidden files and anything .gitignore'd
+pub fn project_files(root: &Path) -> Vec<String> {
+    let mut files: Vec<String> = WalkBuilder::new(root)
+        .require_git(false)
+        .build()
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(root).ok()?;
+            Some(relative.to_string_lossy().to_string())
+        })
+        .take(MAX_FILES)
+        .collect();
+
+    files.sort();
+    files
+}
+
+
+#[test]
+fn test_fuzzy_match() {
+    assert!(fuzzy_match("xyz", "src/main.rs").is_none());
+    assert_eq!(fuzzy_match("mrs", "src/main.rs").unwrap().1, vec![4, 9, 10]);
+
+    // words starts and runs of characters win
+    let score = |q, t| fuzzy_match(q, t).unwrap().0;
+    assert!(score("buf", "src/buffer.rs") > score("buf", "src/bad_uf.rs"));
+    assert!(score("ed", "editor.rs") > score("ed", "src/ceded.rs"));
+
+    // smart case
+    assert!(fuzzy_match("Main", "src/main.rs").is_none());
+    assert!(fuzzy_match("main", "src/Main.rs").is_some());
+}
+
+#[test]
+fn test_picker() {
+    let items = ["src/main.rs", "src/buffer.rs", "README.md", "Cargo.toml"]
+        .iter()
+        .enumerate()
+        .map(|(id, label)| Item { label: label.to_string(), id })
+        .collect();
+    let mut picker = Picker::new(PickerKind::Buffers, items);
+    assert_eq!(picker.matches.len(), 4);
+
+    picker.push('r');
+    picker.push('s');
+    assert_eq!(picker.matches.len(), 2);
+
+    picker.set_query("sbu");
+    assert_eq!(picker.matches.len(), 1);
+    assert_eq!(picker.selected_item().unwrap().label, "src/buffer.rs");
+
+    // deleting goes back to every item
+    picker.set_query("");
+    assert_eq!(picker.matches.len(), 4);
+
+    picker.move_down(2);
+    picker.move_down(2);
+    assert_eq!((picker.selected, picker.offset), (2, 1));
+    picker.move_up();
+    picker.move_up();
+    assert_eq!((picker.selected, picker.offset), (0, 0));
+}
+
+#[test]
+fn test_project_files() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let files = project_files(&root);
+
+    assert!(files.contains(&String::from("editor-core/src/picker.rs")));
+    // target/ is in .gitignore
+    assert!(!files.iter().any(|f| f.starts_with("target/")));
+}
@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+/*
+ * list of places in files, filled by :grep and :make and walked with :cn and :cprev
+ */
+
+// how bad a compiler message is, ordered so the worst on a line wins
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn marker(&self) -> char {
+        match self {
+            Severity::Info => 'I',
+            Severity::Warning => 'W',
+            Severity::Error => 'E',
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub line: usize, // from 0
+    pub col: usize,  // from 0, in chars
+    pub text: String,
+    pub severity: Option<Severity>, // only for compiler messages
+}
+
+impl Entry {
+    // how the entry is listed, with lines and columns from 1
+    pub fn display(&self) -> String {
+        format!("{}:{}:{}: {}", self.path.display(), self.line + 1, self.col + 1, self.text)
+    }
+}
+
+// what filled the list
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuickfixKind {
+    Grep,
+    Make,
+    Diagnostics,
+    Locations, // definitions and references from a language server
+}
+
+pub struct Quickfix {
+    pub kind: QuickfixKind,
+    pub title: String,
+    pub entries: Vec<Entry>,
+    pub current: usize,
+    pub offset: usize, // first entry on screen
+    pub rows: usize,   // how many entries fit on screen
+}
+
+impl Quickfix {
+    pub fn new(kind: QuickfixKind, title: String, entries: Vec<Entry>) -> Self {
+        Quickfix { kind, title, entries, current: 0, offset: 0, rows: 10 }
+    }
+
+    pub fn current_entry(&self) -> Option<&Entry> {
+        self.entries.get(self.current)
+    }
+
+    pub fn next_entry(&mut self) -> Option<&Entry> {
+        if self.current + 1 >= self.entries.len() {
+            return None;
+        }
+        self.select(self.current + 1);
+        self.current_entry()
+    }
+
+    pub fn prev_entry(&mut self) -> Option<&Entry> {
+        if self.current == 0 {
+            return None;
+        }
+        self.select(self.current - 1);
+        self.current_entry()
+    }
+
+    pub fn select(&mut self, idx: usize) {
+        self.current = idx.min(self.entries.len().saturating_sub(1));
+
+        let rows = self.rows.max(1);
+        if self.current < self.offset {
+            self.offset = self.current;
+        } else if self.current >= self.offset + rows {
+            self.offset = self.current + 1 - rows;
+        }
+    }
+}
+
+
+#[test]
+fn test_quickfix() {
+    let entry = |line| Entry { path: PathBuf::from("src/main.rs"), line, col: 0, text: String::new(), severity: None };
+    let mut list = Quickfix::new(QuickfixKind::Grep, String::from("grep"), vec![entry(0), entry(4), entry(9)]);
+    list.rows = 2;
+
+    assert!(list.prev_entry().is_none());
+    assert_eq!(list.next_entry().unwrap().line, 4);
+    assert_eq!(list.next_entry().unwrap().line, 9);
+    assert!(list.next_entry().is_none());
+    assert_eq!(list.offset, 1);
+
+    assert_eq!(entry(4).display(), "src/main.rs:5:1: ");
+}
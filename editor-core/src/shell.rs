@@ -0,0 +1,105 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/*
+ * running external commands through the user's shell
+ */
+
+fn shell() -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    } else {
+        let shell = std::env::var("SHELL").unwrap_or(String::from("sh"));
+        let mut command = Command::new(shell);
+        command.arg("-c");
+        command
+    }
+}
+
+// runs cmd with input on stdin, collecting stdout and stderr
+pub fn run(cmd: &str, input: Option<&str>, dir: Option<&Path>) -> io::Result<Output> {
+    let mut command = shell();
+    command
+        .arg(cmd)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+
+    let mut child = command.spawn()?;
+
+    // write from another thread so a full stdout pipe can't deadlock us
+    let writer = match (child.stdin.take(), input) {
+        (Some(mut stdin), Some(input)) => {
+            let input = input.to_string();
+            Some(std::thread::spawn(move || stdin.write_all(input.as_bytes())))
+        },
+        _ => None,
+    };
+
+    let output = child.wait_with_output()?;
+
+    if let Some(writer) = writer {
+        // the command is allowed to stop reading early
+        match writer.join() {
+            Ok(Err(err)) if err.kind() != io::ErrorKind::BrokenPipe => return Err(err),
+            _ => {}
+        }
+    }
+
+    Ok(output)
+}
+
+// where program is found on the path
+pub fn which(program: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find_map(|file| match file.is_file() {
+            true => Some(file),
+            false if cfg!(windows) => Some(file.with_extension("exe")).filter(|file| file.is_file()),
+            false => None,
+        })
+}
+
+// the program a command starts is on the path
+pub fn installed(cmd: &str) -> bool {
+    cmd.split_whitespace().next().and_then(which).is_some()
+}
+
+// stdout followed by stderr, without the trailing newline
+pub fn output_text(output: &Output) -> String {
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    text.trim_end_matches('\n').to_string()
+}
+
+
+#[cfg(unix)]
+#[test]
+fn test_run() {
+    let output = run("tr a-z A-Z", Some("hello\n"), None).unwrap();
+    assert!(output.status.success());
+    assert_eq!(output_text(&output), "HELLO");
+
+    let output = run("echo oops >&2; exit 3", None, None).unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(output_text(&output), "oops");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_which() {
+    assert!(which("sh").is_some_and(|path| path.ends_with("sh")));
+    assert!(installed("sh -c true"));
+    assert!(!installed("surely-not-a-formatter --stdin"));
+    assert!(!installed(""));
+}
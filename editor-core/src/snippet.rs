@@ -0,0 +1,479 @@
+use ropey::Rope;
+use std::collections::BTreeMap;
+
+/*
+ * snippets in the syntax language servers use
+ *
+ *   $1, ${1}           a tabstop, $0 is where the cursor ends up
+ *   ${1:default}       a placeholder, typing replaces the default
+ *   ${1|one,two|}      a choice of texts
+ *   $1 again           mirrors the first one as it is typed
+ *   $NAME, ${NAME:x}   a variable like TM_FILENAME, or x when it isn't set
+ *
+ * after expanding, a session keeps the ranges of the tabstops in the buffer
+ * and moves them along as the text is edited, the tabstop being typed in
+ * grows and its mirrors are rewritten to match it
+ */
+
+#[derive(Debug, PartialEq)]
+pub struct Snippet {
+    pub prefix: &'static str,
+    pub description: &'static str,
+    pub body: &'static str,
+}
+
+const fn snippet(prefix: &'static str, description: &'static str, body: &'static str) -> Snippet {
+    Snippet { prefix, description, body }
+}
+
+pub static RUST: &[Snippet] = &[
+    snippet("fn", "function", "fn ${1:name}($2)${3: -> ${4:()}} {\n\t$0\n}"),
+    snippet("pfn", "public function", "pub fn ${1:name}($2)${3: -> ${4:()}} {\n\t$0\n}"),
+    snippet("new", "constructor", "pub fn new(${1:value}: ${2:Type}) -> Self {\n\tSelf { $1 }\n}"),
+    snippet("impl", "impl block", "impl ${1:Type} {\n\t$0\n}"),
+    snippet("implt", "trait impl", "impl ${1:Trait} for ${2:Type} {\n\t$0\n}"),
+    snippet("struct", "struct", "${1|pub ,pub(crate) , |}struct ${2:Name} {\n\t$0\n}"),
+    snippet("enum", "enum", "${1|pub ,pub(crate) , |}enum ${2:Name} {\n\t$0\n}"),
+    snippet("derive", "derive attribute", "#[derive(${1|Debug,Clone,Copy,Default,PartialEq|})]"),
+    snippet("match", "match", "match ${1:value} {\n\t${2:pattern} => $0,\n}"),
+    snippet("iflet", "if let", "if let ${1:Some(x)} = ${2:value} {\n\t$0\n}"),
+    snippet("for", "for loop", "for ${1:item} in ${2:items} {\n\t$0\n}"),
+    snippet("test", "test function", "#[test]\nfn ${1:name}() {\n\t$0\n}"),
+    snippet("ttest", "tokio test", "#[tokio::test]\nasync fn ${1:name}() {\n\t$0\n}"),
+    snippet("tests", "test module", "#[cfg(test)]\nmod tests {\n\tuse super::*;\n\n\t#[test]\n\tfn ${1:name}() {\n\t\t$0\n\t}\n}"),
+];
+
+pub static PYTHON: &[Snippet] = &[
+    snippet("def", "function", "def ${1:name}($2):\n\t${0:pass}"),
+    snippet("class", "class", "class ${1:Name}:\n\tdef __init__(self$2):\n\t\t${0:pass}"),
+    snippet("main", "main guard", "if __name__ == \"__main__\":\n\t${0:main()}"),
+    snippet("for", "for loop", "for ${1:item} in ${2:items}:\n\t${0:pass}"),
+];
+
+pub static GO: &[Snippet] = &[
+    snippet("func", "function", "func ${1:name}($2) ${3:error} {\n\t$0\n}"),
+    snippet("iferr", "error check", "if err != nil {\n\treturn ${1:err}\n}"),
+    snippet("test", "test function", "func Test${1:Name}(t *testing.T) {\n\t$0\n}"),
+    snippet("for", "range loop", "for ${1:_}, ${2:v} := range ${3:items} {\n\t$0\n}"),
+];
+
+pub static C: &[Snippet] = &[
+    snippet("main", "main function", "int main(int argc, char *argv[]) {\n\t$0\n\treturn 0;\n}"),
+    snippet("for", "for loop", "for (${1:int} ${2:i} = 0; $2 < ${3:n}; $2++) {\n\t$0\n}"),
+    snippet("inc", "include", "#include ${1|<,\"|}${2:stdio.h}${3|>,\"|}"),
+    snippet("guard", "include guard", "#ifndef ${1:${TM_FILENAME_BASE}_H}\n#define $1\n\n$0\n\n#endif"),
+];
+
+pub static JAVASCRIPT: &[Snippet] = &[
+    snippet("fn", "function", "function ${1:name}($2) {\n\t$0\n}"),
+    snippet("af", "arrow function", "(${1:args}) => {\n\t$0\n}"),
+    snippet("log", "console.log", "console.log($0);"),
+    snippet("test", "test case", "test(\"${1:does something}\", () => {\n\t$0\n});"),
+];
+
+pub static SH: &[Snippet] = &[
+    snippet("if", "if", "if [ ${1:condition} ]; then\n\t$0\nfi"),
+    snippet("for", "for loop", "for ${1:item} in ${2:items}; do\n\t$0\ndone"),
+    snippet("fn", "function", "${1:name}() {\n\t$0\n}"),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stop {
+    pub number: usize,
+    pub ranges: Vec<(usize, usize)>, // in chars, the first is typed in and the rest mirror it
+    pub choices: Vec<String>,
+}
+
+// the text a snippet expands to, with its tabstops in the order they are visited
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expansion {
+    pub text: String,
+    pub stops: Vec<Stop>,
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    text: String,
+    len: usize, // chars in text
+    stops: BTreeMap<usize, Stop>,
+    variable: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn push(&mut self, text: &str) {
+        self.text.push_str(text);
+        self.len += text.chars().count();
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn add_range(&mut self, number: usize, range: (usize, usize), placeholder: bool) {
+        let stop = self.stops.entry(number).or_insert(Stop { number, ranges: vec![], choices: vec![] });
+        // the one with the text is the one typed in
+        if placeholder && stop.ranges.first().is_some_and(|r| r.0 == r.1) {
+            stop.ranges.insert(0, range);
+        } else {
+            stop.ranges.push(range);
+        }
+    }
+
+    // the text a tabstop has so far, for mirrors of it
+    fn stop_text(&self, number: usize) -> String {
+        let range = match self.stops.get(&number).and_then(|s| s.ranges.first()) {
+            Some(range) => *range,
+            None => return String::new(),
+        };
+        self.text.chars().skip(range.0).take(range.1 - range.0).collect()
+    }
+
+    // reads up to an unescaped closing brace, or to the end
+    fn parse(&mut self, nested: bool) {
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '}' if nested => return,
+                '\\' => match self.peek() {
+                    Some(next @ ('$' | '}' | '\\' | ',' | '|')) => {
+                        self.pos += 1;
+                        self.push(&next.to_string());
+                    },
+                    _ => self.push("\\"),
+                },
+                '$' => self.dollar(),
+                c => self.push(&c.to_string()),
+            }
+        }
+    }
+
+    fn dollar(&mut self) {
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if let Ok(number) = digits.parse::<usize>() {
+            let text = self.stop_text(number);
+            let start = self.len;
+            self.push(&text);
+            self.add_range(number, (start, self.len), false);
+            return;
+        }
+
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !name.is_empty() {
+            let value = (self.variable)(&name).unwrap_or_default();
+            self.push(&value);
+            return;
+        }
+
+        if !self.eat('{') {
+            self.push("$");
+            return;
+        }
+
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if let Ok(number) = digits.parse::<usize>() {
+            let start = self.len;
+            if self.eat(':') {
+                self.parse(true);
+                self.add_range(number, (start, self.len), true);
+            } else if self.eat('|') {
+                let choices = self.choices();
+                self.push(choices.first().map_or("", |c| c.as_str()));
+                self.add_range(number, (start, self.len), true);
+                if let Some(stop) = self.stops.get_mut(&number) {
+                    stop.choices = choices;
+                }
+            } else {
+                self.eat('}');
+                let text = self.stop_text(number);
+                self.push(&text);
+                self.add_range(number, (start, self.len), false);
+            }
+            return;
+        }
+
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let value = (self.variable)(&name);
+        if !self.eat(':') {
+            self.eat('}');
+            self.push(&value.unwrap_or_default());
+            return;
+        }
+
+        // the default only counts when the variable isn't set, tabstops in it go with it
+        let (text, len, stops) = (self.text.clone(), self.len, self.stops.clone());
+        self.parse(true);
+        if let Some(value) = value {
+            (self.text, self.len, self.stops) = (text, len, stops);
+            self.push(&value);
+        }
+    }
+
+    // the texts in ${1|one,two|}, after the first bar
+    fn choices(&mut self) -> Vec<String> {
+        let mut choices = vec![];
+        let mut current = String::new();
+
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    if let Some(next) = self.peek() {
+                        self.pos += 1;
+                        current.push(next);
+                    }
+                },
+                ',' => choices.push(std::mem::take(&mut current)),
+                '|' if self.eat('}') => break,
+                c => current.push(c),
+            }
+        }
+        choices.push(current);
+        choices
+    }
+}
+
+// the text of a snippet body and where its tabstops are in it, a snippet
+// without $0 ends after its text
+pub fn expand(body: &str, variable: &dyn Fn(&str) -> Option<String>) -> Expansion {
+    let mut parser = Parser {
+        chars: body.chars().collect(),
+        pos: 0,
+        text: String::new(),
+        len: 0,
+        stops: BTreeMap::new(),
+        variable,
+    };
+    parser.parse(false);
+
+    let last = parser.stops.remove(&0).unwrap_or(Stop {
+        number: 0,
+        ranges: vec![(parser.len, parser.len)],
+        choices: vec![],
+    });
+    let mut stops: Vec<Stop> = parser.stops.into_values().collect();
+    stops.push(last);
+
+    Expansion { text: parser.text, stops }
+}
+
+// an expanded snippet being filled in
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub stops: Vec<Stop>, // ranges are in the buffer
+    pub current: usize,
+    pub fresh: bool, // the placeholder at the cursor goes once something is typed
+}
+
+// chars of the text replaced by others, a key in insert mode can make a few of these close
+// together and they are merged into one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edit {
+    pub at: usize,
+    pub removed: usize,
+    pub inserted: usize,
+}
+
+impl Edit {
+    // the edit covering this one and the next, made on the text this one left
+    pub fn then(self, next: Edit) -> Edit {
+        let at = self.at.min(next.at);
+        let end = (self.at + self.inserted).max(next.at + next.removed);
+
+        Edit {
+            at,
+            removed: end + self.removed - self.inserted - at,
+            inserted: end + next.inserted - next.removed - at,
+        }
+    }
+}
+
+impl Session {
+    // the buffer has the expansion at start already
+    pub fn new(expansion: Expansion, start: usize) -> Self {
+        let mut stops = expansion.stops;
+        for range in stops.iter_mut().flat_map(|s| s.ranges.iter_mut()) {
+            *range = (range.0 + start, range.1 + start);
+        }
+
+        Session { stops, current: 0, fresh: false }
+    }
+
+    pub fn stop(&self) -> Option<&Stop> {
+        self.stops.get(self.current)
+    }
+
+    // the range being typed in, once the session is on it
+    pub fn range(&self) -> Option<(usize, usize)> {
+        self.stop().and_then(|s| s.ranges.first().copied())
+    }
+
+    // moves to a tabstop, giving where the cursor goes, None past either end
+    pub fn jump_to(&mut self, idx: usize) -> Option<usize> {
+        self.current = idx;
+        let stop = self.stops.get(idx)?;
+        let (start, end) = stop.ranges[0];
+
+        // a choice is picked from a list, the cursor goes after it
+        self.fresh = start < end && stop.choices.is_empty();
+        Some(if stop.choices.is_empty() { start } else { end })
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.current + 1 >= self.stops.len()
+    }
+
+    // follows an edit to the text, the tabstop it was made in grows and its mirrors are
+    // rewritten, false when the edit was somewhere else and the session is over
+    pub fn sync(&mut self, rope: &mut Rope, cursor: &mut usize, edit: Edit) -> bool {
+        let Edit { at, removed, inserted } = edit;
+        let (start, end) = match self.range() {
+            Some(range) => range,
+            None => return false,
+        };
+        if at < start || at + removed > end {
+            return false;
+        }
+        self.fresh = false;
+        self.shift(at, removed, inserted);
+
+        let (start, end) = self.stops[self.current].ranges[0];
+        let text: String = rope.slice(start..end).to_string();
+        for mirror in 1..self.stops[self.current].ranges.len() {
+            let (from, to) = self.stops[self.current].ranges[mirror];
+            if rope.slice(from..to) == text.as_str() {
+                continue;
+            }
+
+            rope.remove(from..to);
+            rope.insert(from, &text);
+            if *cursor >= to {
+                *cursor = *cursor + text.chars().count() - (to - from);
+            }
+            self.shift_mirror(mirror, from, to - from, text.chars().count());
+        }
+        true
+    }
+
+    // ranges after an edit made in the current tabstop
+    fn shift(&mut self, at: usize, removed: usize, inserted: usize) {
+        let current = self.current;
+        for (i, stop) in self.stops.iter_mut().enumerate() {
+            for (j, range) in stop.ranges.iter_mut().enumerate() {
+                *range = match (i == current && j == 0, *range) {
+                    (true, (start, end)) => (start, end + inserted - removed),
+                    (false, range) => moved(range, at, removed, inserted),
+                };
+            }
+        }
+    }
+
+    fn shift_mirror(&mut self, mirror: usize, at: usize, removed: usize, inserted: usize) {
+        let current = self.current;
+        for (i, stop) in self.stops.iter_mut().enumerate() {
+            for (j, range) in stop.ranges.iter_mut().enumerate() {
+                *range = match (i == current && j == mirror, *range) {
+                    (true, (start, _)) => (start, start + inserted),
+                    (false, range) => moved(range, at, removed, inserted),
+                };
+            }
+        }
+    }
+}
+
+// a range after text at `at` was replaced, ranges around the edit grow with it
+fn moved(range: (usize, usize), at: usize, removed: usize, inserted: usize) -> (usize, usize) {
+    let (start, end) = range;
+    let shift = |idx: usize| idx + inserted - removed;
+
+    if end <= at {
+        range
+    } else if start >= at + removed {
+        (shift(start), shift(end))
+    } else {
+        let start = if start <= at { start } else { at + inserted };
+        let end = if end >= at + removed { shift(end) } else { at + inserted };
+        (start, end.max(start))
+    }
+}
+
+
+#[test]
+fn test_expand() {
+    let variable = |name: &str| match name {
+        "TM_FILENAME" => Some(String::from("main.rs")),
+        _ => None,
+    };
+
+    let expansion = expand("fn ${1:name}($2) -> ${3|i32,u8|} {\n\t$1\\$ ${TM_FILENAME} ${TM_NONE:none}$0\n}", &variable);
+    assert_eq!(expansion.text, "fn name() -> i32 {\n\tname$ main.rs none\n}");
+    let numbers: Vec<usize> = expansion.stops.iter().map(|s| s.number).collect();
+    assert_eq!(numbers, vec![1, 2, 3, 0]);
+    assert_eq!(expansion.stops[0].ranges, vec![(3, 7), (20, 24)]);
+    assert_eq!(expansion.stops[1].ranges, vec![(8, 8)]);
+    assert_eq!(expansion.stops[2].choices, vec!["i32", "u8"]);
+
+    // nested placeholders and no $0
+    let expansion = expand("${1:a ${2:b}}", &variable);
+    assert_eq!(expansion.text, "a b");
+    assert_eq!((expansion.stops[0].ranges[0], expansion.stops[1].ranges[0]), ((0, 3), (2, 3)));
+    assert_eq!(expansion.stops[2].ranges, vec![(3, 3)]);
+}
+
+#[test]
+fn test_session() {
+    let expansion = expand("let ${1:x} = $2; $1;", &|_| None);
+    let mut rope = Rope::from(format!("{}\n", expansion.text));
+    let mut session = Session::new(expansion, 0);
+    assert_eq!(session.jump_to(0), Some(4));
+    assert!(session.fresh);
+
+    // typing over the placeholder rewrites the mirror
+    let mut cursor = 6;
+    rope.remove(4..5);
+    rope.insert(4, "ab");
+    assert!(session.sync(&mut rope, &mut cursor, Edit { at: 4, removed: 1, inserted: 2 }));
+    assert_eq!(rope.to_string(), "let ab = ; ab;\n");
+    assert_eq!((cursor, session.range()), (6, Some((4, 6))));
+
+    // a char typed at its end still goes in the tabstop, even before the same one
+    cursor = 7;
+    rope.insert(6, " ");
+    assert!(session.sync(&mut rope, &mut cursor, Edit { at: 6, removed: 0, inserted: 1 }));
+    assert_eq!(rope.to_string(), "let ab  = ; ab ;\n");
+    assert_eq!(session.range(), Some((4, 7)));
+    rope.remove(6..7);
+    cursor = 6;
+    assert!(session.sync(&mut rope, &mut cursor, Edit { at: 6, removed: 1, inserted: 0 }));
+
+    assert_eq!(session.jump_to(1), Some(9));
+    cursor = 10;
+    rope.insert(9, "1");
+    assert!(session.sync(&mut rope, &mut cursor, Edit { at: 9, removed: 0, inserted: 1 }));
+    assert_eq!(session.stops[0].ranges, vec![(4, 6), (12, 14)]);
+
+    // an edit outside the tabstop ends it
+    rope.insert(0, "x");
+    assert!(!session.sync(&mut rope, &mut cursor, Edit { at: 0, removed: 0, inserted: 1 }));
+
+    // a pair typed and a char typed in it, then a closer reindented on the line before
+    let pair = Edit { at: 4, removed: 0, inserted: 2 }.then(Edit { at: 5, removed: 0, inserted: 1 });
+    assert_eq!(pair, Edit { at: 4, removed: 0, inserted: 3 });
+    assert_eq!(pair.then(Edit { at: 0, removed: 2, inserted: 4 }), Edit { at: 0, removed: 4, inserted: 9 });
+}
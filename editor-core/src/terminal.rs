@@ -0,0 +1,336 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use ratatui::{
+    prelude::{Line, Span},
+    style::{Color, Modifier, Style},
+};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/*
+ * a shell running in a pty, its output is read on another thread into a vt100
+ * screen that gets drawn in place of the buffer's text
+ *
+ * the buffer's own text is only used for normal mode, where it holds a copy of
+ * the scrollback and screen to move around in and yank from
+ */
+
+// lines kept above the screen
+const SCROLLBACK: usize = 10_000;
+
+pub struct Terminal {
+    pub command: String,
+    parser: Arc<Mutex<vt100::Parser>>,
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    exited: Arc<AtomicBool>,
+}
+
+impl Terminal {
+    // runs cmd through the shell, or just the shell when there is no cmd
+    pub fn spawn(cmd: Option<&str>, dir: &Path, size: (u16, u16)) -> io::Result<Self> {
+        let (cols, rows) = (size.0.max(1), size.1.max(1));
+        let pair = native_pty_system()
+            .openpty(PtySize { rows, cols, pixel_width: 0, pixel_height: 0 })
+            .map_err(io::Error::other)?;
+
+        let shell = std::env::var("SHELL").unwrap_or(String::from("sh"));
+        let mut builder = CommandBuilder::new(&shell);
+        if let Some(cmd) = cmd {
+            builder.args(["-c", cmd]);
+        }
+        builder.cwd(dir);
+        builder.env("TERM", "xterm-256color");
+
+        let child = pair.slave.spawn_command(builder).map_err(io::Error::other)?;
+        // the reader only sees the end of the output once nothing else holds the slave
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().map_err(io::Error::other)?;
+        let writer = pair.master.take_writer().map_err(io::Error::other)?;
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, SCROLLBACK)));
+        let exited = Arc::new(AtomicBool::new(false));
+
+        let (screen, done) = (parser.clone(), exited.clone());
+        std::thread::spawn(move || {
+            let mut bytes = [0; 4096];
+            loop {
+                match reader.read(&mut bytes) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if let Ok(mut parser) = screen.lock() {
+                            parser.process(&bytes[..n]);
+                        }
+                    },
+                }
+            }
+            done.store(true, Ordering::SeqCst);
+        });
+
+        Ok(Terminal {
+            command: cmd.map_or(shell, String::from),
+            parser,
+            master: pair.master,
+            writer,
+            child,
+            exited,
+        })
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.writer.flush()
+    }
+
+    pub fn send_key(&mut self, key: KeyEvent) -> io::Result<()> {
+        let bytes = key_bytes(key, self.application_cursor());
+        self.write(&bytes)
+    }
+
+    // pasted text is marked as such for programs that ask for it
+    pub fn paste(&mut self, text: &str) -> io::Result<()> {
+        let bracketed = self.parser.lock().is_ok_and(|p| p.screen().bracketed_paste());
+        if bracketed {
+            self.write(format!("\x1b[200~{}\x1b[201~", text).as_bytes())
+        } else {
+            self.write(text.as_bytes())
+        }
+    }
+
+    pub fn resize(&mut self, size: (u16, u16)) {
+        let (cols, rows) = (size.0.max(1), size.1.max(1));
+        if let Ok(mut parser) = self.parser.lock() {
+            if parser.screen().size() == (rows, cols) {
+                return;
+            }
+            parser.set_size(rows, cols);
+        }
+        let _ = self.master.resize(PtySize { rows, cols, pixel_width: 0, pixel_height: 0 });
+    }
+
+    // the output has ended, the process is gone or about to be
+    pub fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::SeqCst)
+    }
+
+    // exit code once the process is done
+    pub fn exit_code(&mut self) -> Option<u32> {
+        match self.child.try_wait() {
+            Ok(Some(status)) => Some(status.exit_code()),
+            _ => None,
+        }
+    }
+
+    fn application_cursor(&self) -> bool {
+        self.parser.lock().is_ok_and(|p| p.screen().application_cursor())
+    }
+
+    // row and column of the cursor on the screen, None while it is hidden
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        let parser = self.parser.lock().ok()?;
+        let screen = parser.screen();
+        if screen.hide_cursor() {
+            None
+        } else {
+            Some(screen.cursor_position())
+        }
+    }
+
+    // the screen as it would look in a terminal
+    pub fn lines<'a>(&self) -> Vec<Line<'a>> {
+        let parser = match self.parser.lock() {
+            Ok(parser) => parser,
+            Err(_) => return vec![],
+        };
+        let screen = parser.screen();
+        let (rows, cols) = screen.size();
+
+        (0..rows)
+            .map(|row| {
+                let spans: Vec<Span> = (0..cols)
+                    .filter_map(|col| screen.cell(row, col))
+                    .filter(|cell| !cell.is_wide_continuation())
+                    .map(|cell| {
+                        let text = if cell.has_contents() { cell.contents() } else { String::from(" ") };
+                        Span::styled(text, cell_style(cell))
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect()
+    }
+
+    // the scrollback then the screen as plain text, with the line the cursor is on
+    pub fn text(&self) -> (String, usize) {
+        let mut parser = match self.parser.lock() {
+            Ok(parser) => parser,
+            Err(_) => return (String::from("\n"), 0),
+        };
+
+        let cols = parser.screen().size().1;
+        let mut lines = vec![];
+
+        // scrolled back n rows, the top row of the screen is the nth line from the end
+        parser.set_scrollback(usize::MAX);
+        let len = parser.screen().scrollback();
+        for offset in (1..=len).rev() {
+            parser.set_scrollback(offset);
+            lines.push(parser.screen().rows(0, cols).next().unwrap_or_default());
+        }
+        parser.set_scrollback(0);
+
+        let screen = parser.screen();
+        lines.extend(screen.rows(0, cols));
+        let cursor = len + usize::from(screen.cursor_position().0);
+
+        // blank rows under the cursor are just the empty part of the screen
+        while lines.len() > cursor + 1 && lines.last().is_some_and(|l| l.trim().is_empty()) {
+            lines.pop();
+        }
+
+        let mut text: String = lines.iter().map(|l| format!("{}\n", l.trim_end())).collect();
+        if text.is_empty() {
+            text.push('\n');
+        }
+        (text, cursor.min(lines.len().saturating_sub(1)))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if !self.has_exited() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+fn color(color: vt100::Color) -> Color {
+    match color {
+        vt100::Color::Default => Color::Reset,
+        vt100::Color::Idx(i) => Color::Indexed(i),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+fn cell_style(cell: &vt100::Cell) -> Style {
+    let mut style = Style::default().fg(color(cell.fgcolor())).bg(color(cell.bgcolor()));
+
+    if cell.bold() {
+        style = style.add_modifier(Modifier::BOLD);
+    }
+    if cell.italic() {
+        style = style.add_modifier(Modifier::ITALIC);
+    }
+    if cell.underline() {
+        style = style.add_modifier(Modifier::UNDERLINED);
+    }
+    if cell.inverse() {
+        style = style.add_modifier(Modifier::REVERSED);
+    }
+
+    style
+}
+
+// what a terminal sends for a key, arrows change when the program asks for
+// application cursor keys
+pub fn key_bytes(key: KeyEvent, application_cursor: bool) -> Vec<u8> {
+    let arrow = |c: char| {
+        if application_cursor {
+            format!("\x1bO{}", c).into_bytes()
+        } else {
+            format!("\x1b[{}", c).into_bytes()
+        }
+    };
+
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match c.to_ascii_lowercase() {
+                c @ ('a'..='z' | '@' | '[' | '\\' | ']' | '^' | '_') => vec![(c as u8) & 0x1f],
+                ' ' => vec![0],
+                c => c.to_string().into_bytes(),
+            }
+        },
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => arrow('A'),
+        KeyCode::Down => arrow('B'),
+        KeyCode::Right => arrow('C'),
+        KeyCode::Left => arrow('D'),
+        KeyCode::Home => arrow('H'),
+        KeyCode::End => arrow('F'),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(n @ 1..=4) => format!("\x1bO{}", (b'P' + n - 1) as char).into_bytes(),
+        KeyCode::F(n @ 5..=12) => {
+            let code = [15, 17, 18, 19, 20, 21, 23, 24][usize::from(n - 5)];
+            format!("\x1b[{}~", code).into_bytes()
+        },
+        _ => vec![],
+    };
+
+    // alt sends an escape first
+    if key.modifiers.contains(KeyModifiers::ALT) && !bytes.is_empty() {
+        bytes.insert(0, 0x1b);
+    }
+
+    bytes
+}
+
+
+#[test]
+fn test_key_bytes() {
+    let key = |code, modifiers| KeyEvent::new(code, modifiers);
+
+    assert_eq!(key_bytes(key(KeyCode::Char('a'), KeyModifiers::NONE), false), b"a");
+    assert_eq!(key_bytes(key(KeyCode::Char('c'), KeyModifiers::CONTROL), false), vec![3]);
+    assert_eq!(key_bytes(key(KeyCode::Char('b'), KeyModifiers::ALT), false), b"\x1bb");
+    assert_eq!(key_bytes(key(KeyCode::Enter, KeyModifiers::NONE), false), b"\r");
+    assert_eq!(key_bytes(key(KeyCode::Up, KeyModifiers::NONE), false), b"\x1b[A");
+    assert_eq!(key_bytes(key(KeyCode::Up, KeyModifiers::NONE), true), b"\x1bOA");
+    assert_eq!(key_bytes(key(KeyCode::F(5), KeyModifiers::NONE), false), b"\x1b[15~");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_terminal() {
+    let mut terminal = Terminal::spawn(Some("printf 'one\\n\\033[31mtwo\\033[0m\\n'; read line; echo got $line"), Path::new("."), (20, 3)).unwrap();
+
+    let wait = |terminal: &Terminal, text: &str| {
+        for _ in 0..200 {
+            if terminal.text().0.contains(text) {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("never saw {:?} in {:?}", text, terminal.text().0);
+    };
+
+    wait(&terminal, "two");
+    let lines = terminal.lines();
+    assert_eq!(lines[1].spans[0].style.fg, Some(Color::Indexed(1)));
+
+    terminal.write(b"hi\r").unwrap();
+    wait(&terminal, "got hi");
+    for _ in 0..200 {
+        if terminal.has_exited() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert!(terminal.has_exited());
+
+    // lines that scrolled off of the screen are still in the text,
+    // the empty one at the end is where the cursor was left
+    let (text, cursor) = terminal.text();
+    assert_eq!(text, "one\ntwo\nhi\ngot hi\n\n");
+    assert_eq!(cursor, 4);
+}
@@ -0,0 +1,133 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+/*
+ * small changes to a line's text for normal mode commands: case for ~ and the
+ * g~, gu and gU operators, numbers for ctrl-a and ctrl-x and joining for J
+ *
+ * a char whose other case is more than one char, like ß, is left as it is so
+ * the columns after it don't move
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Case {
+    Toggle,
+    Lower,
+    Upper,
+}
+
+impl Case {
+    // the operator for g~, gu and gU
+    pub fn from_operator(operator: &str) -> Option<Self> {
+        match operator {
+            "g~" => Some(Case::Toggle),
+            "gu" => Some(Case::Lower),
+            "gU" => Some(Case::Upper),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, text: &str) -> String {
+        text.chars().map(|c| self.apply_char(c)).collect()
+    }
+
+    fn apply_char(self, c: char) -> char {
+        let upper = match self {
+            Case::Toggle => c.is_lowercase(),
+            Case::Lower => false,
+            Case::Upper => true,
+        };
+        let mut changed = match upper {
+            true => c.to_uppercase().collect::<Vec<char>>(),
+            false => c.to_lowercase().collect::<Vec<char>>(),
+        };
+        match changed.len() {
+            1 => changed.remove(0),
+            _ => c,
+        }
+    }
+}
+
+// the line with the number at or after col moved by an amount, and the column of its last
+// digit. hex and binary numbers keep their width, and a hex number the case of its letters
+pub fn increment(line: &str, col: usize, by: i64) -> Option<(String, usize)> {
+    static NUMBER: OnceLock<Regex> = OnceLock::new();
+    let number = NUMBER.get_or_init(|| Regex::new(r"0[xX][0-9a-fA-F]+|0[bB][01]+|-?[0-9]+").unwrap());
+    let at = line.char_indices().nth(col).map_or(line.len(), |(i, _)| i);
+    let found = number.find_iter(line).find(|m| m.end() > at)?;
+    let text = found.as_str();
+
+    let changed = if let Some(digits) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        let value = u64::from_str_radix(digits, 16).ok()?.wrapping_add_signed(by);
+        let hex = match digits.chars().any(|c| c.is_ascii_uppercase()) {
+            true => format!("{:0width$X}", value, width = digits.len()),
+            false => format!("{:0width$x}", value, width = digits.len()),
+        };
+        format!("{}{}", &text[..2], hex)
+    } else if let Some(digits) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        let value = u64::from_str_radix(digits, 2).ok()?.wrapping_add_signed(by);
+        format!("{}{:0width$b}", &text[..2], value, width = digits.len())
+    } else {
+        let value = text.parse::<i64>().ok()?.saturating_add(by);
+        // leading zeros keep the number as wide as it was
+        let digits = text.trim_start_matches('-');
+        match digits.len() > 1 && digits.starts_with('0') {
+            true if value < 0 => format!("-{:0width$}", value.unsigned_abs(), width = digits.len()),
+            true => format!("{:0width$}", value, width = digits.len()),
+            false => value.to_string(),
+        }
+    };
+
+    let start = line[..found.start()].chars().count();
+    let line = format!("{}{}{}", &line[..found.start()], changed, &line[found.end()..]);
+    Some((line, start + changed.chars().count() - 1))
+}
+
+// two lines made one and the column they were joined at, J takes out the next line's indent
+// and puts a space between them unless the first ends in one or the next starts with a ),
+// gJ leaves both as they are
+pub fn join(first: &str, next: &str, spaces: bool) -> (String, usize) {
+    let first = first.trim_end_matches('\n');
+    if !spaces {
+        return (format!("{}{}", first, next), first.chars().count());
+    }
+
+    let next = next.trim_start_matches([' ', '\t']);
+    let rest = next.trim_end_matches('\n');
+    let space = match rest.is_empty() || rest.starts_with(')') || first.ends_with([' ', '\t']) || first.is_empty() {
+        true => "",
+        false => " ",
+    };
+    (format!("{}{}{}", first, space, next), first.chars().count())
+}
+
+
+#[test]
+fn test_case() {
+    assert_eq!(Case::Toggle.apply("Hello, World ß"), "hELLO, wORLD ß");
+    assert_eq!(Case::Lower.apply("ÀB c"), "àb c");
+    assert_eq!(Case::from_operator("gU").map(|c| c.apply("x1y")), Some(String::from("X1Y")));
+}
+
+#[test]
+fn test_increment() {
+    assert_eq!(increment("x = 9;", 0, 1), Some((String::from("x = 10;"), 5)));
+    assert_eq!(increment("x = 1;", 4, -3), Some((String::from("x = -2;"), 5)));
+    assert_eq!(increment("a -5 b", 0, 7), Some((String::from("a 2 b"), 2)));
+    assert_eq!(increment("0x0fF", 3, 1), Some((String::from("0x100"), 4)));
+    assert_eq!(increment("0xff", 0, 1), Some((String::from("0x100"), 4)));
+    assert_eq!(increment("0xA9", 0, 1), Some((String::from("0xAA"), 3)));
+    assert_eq!(increment("mask 0b0011", 0, 1), Some((String::from("mask 0b0100"), 10)));
+    assert_eq!(increment("007", 0, 1), Some((String::from("008"), 2)));
+    assert_eq!(increment("v1 v2", 2, 1), Some((String::from("v1 v3"), 4)));
+    assert_eq!(increment("none", 0, 1), None);
+}
+
+#[test]
+fn test_join() {
+    assert_eq!(join("let x =\n", "    1;\n", true), (String::from("let x = 1;\n"), 7));
+    assert_eq!(join("f(a\n", ")\n", true), (String::from("f(a)\n"), 3));
+    assert_eq!(join("x \n", "y\n", true), (String::from("x y\n"), 2));
+    assert_eq!(join("x\n", "\n", true), (String::from("x\n"), 1));
+    assert_eq!(join("x\n", "    y\n", false), (String::from("x    y\n"), 1));
+}
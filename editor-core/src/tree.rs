@@ -0,0 +1,244 @@
+use crate::explorer::{list_dir, EntryKind};
+use crate::git::{self, GitStatus};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/*
+ * file tree for the side panel
+ *
+ * the tree is kept flattened as the rows that are on screen, expanding or
+ * collapsing a directory rebuilds the rows from the set of open directories
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub path: PathBuf,
+    pub name: String,
+    pub depth: usize,
+    pub kind: EntryKind,
+    pub is_dir: bool,
+    pub expanded: bool,
+}
+
+pub struct FileTree {
+    pub root: PathBuf,
+    pub nodes: Vec<Node>,
+    pub cursor: usize,
+    pub offset: usize, // first row on screen
+    pub show_hidden: bool,
+    expanded: HashSet<PathBuf>,
+    git: HashMap<PathBuf, GitStatus>,
+    git_root: Option<PathBuf>, // root as git sees it, links resolved
+}
+
+impl FileTree {
+    pub fn new(root: &Path) -> io::Result<Self> {
+        let mut tree = FileTree {
+            root: root.to_path_buf(),
+            nodes: vec![],
+            cursor: 0,
+            offset: 0,
+            show_hidden: false,
+            expanded: HashSet::new(),
+            git: HashMap::new(),
+            git_root: None,
+        };
+
+        tree.refresh()?;
+        Ok(tree)
+    }
+
+    // reads the open directories and git status again, keeping the cursor on the same path
+    pub fn refresh(&mut self) -> io::Result<()> {
+        self.git = git::status(&self.root);
+        self.git_root = self.root.canonicalize().ok();
+        self.rebuild()
+    }
+
+    fn rebuild(&mut self) -> io::Result<()> {
+        let selected = self.selected().map(|n| n.path.clone());
+
+        let mut nodes = vec![];
+        self.build(&self.root.clone(), 0, &mut nodes)?;
+        self.nodes = nodes;
+
+        self.cursor = selected
+            .and_then(|path| self.nodes.iter().position(|n| n.path == path))
+            .unwrap_or(self.cursor)
+            .min(self.nodes.len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn build(&self, dir: &Path, depth: usize, nodes: &mut Vec<Node>) -> io::Result<()> {
+        for entry in list_dir(dir, self.show_hidden)? {
+            let path = dir.join(&entry.name);
+            let expanded = entry.is_dir && self.expanded.contains(&path);
+
+            nodes.push(Node {
+                path: path.clone(),
+                name: entry.name,
+                depth,
+                kind: entry.kind,
+                is_dir: entry.is_dir,
+                expanded,
+            });
+
+            // unreadable directories just show up empty
+            if expanded {
+                let _ = self.build(&path, depth + 1, nodes);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn selected(&self) -> Option<&Node> {
+        self.nodes.get(self.cursor)
+    }
+
+    pub fn git_status(&self, node: &Node) -> Option<GitStatus> {
+        let relative = node.path.strip_prefix(&self.root).ok()?;
+        self.git.get(&self.git_root.as_ref()?.join(relative)).copied()
+    }
+
+    pub fn move_down(&mut self, height: usize) {
+        if self.cursor + 1 < self.nodes.len() {
+            self.cursor += 1;
+        }
+        self.scroll(height);
+    }
+
+    pub fn move_up(&mut self, height: usize) {
+        self.cursor = self.cursor.saturating_sub(1);
+        self.scroll(height);
+    }
+
+    pub fn select(&mut self, row: usize, height: usize) {
+        self.cursor = row.min(self.nodes.len().saturating_sub(1));
+        self.scroll(height);
+    }
+
+    // keeps the cursor on screen
+    pub fn scroll(&mut self, height: usize) {
+        let height = height.max(1);
+
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + height {
+            self.offset = self.cursor + 1 - height;
+        }
+    }
+
+    // opens or closes the directory under the cursor, a file is handed back to be opened
+    pub fn toggle(&mut self) -> io::Result<Option<PathBuf>> {
+        let node = match self.selected() {
+            Some(node) => node.clone(),
+            None => return Ok(None),
+        };
+
+        if !node.is_dir {
+            return Ok(Some(node.path));
+        }
+
+        if node.expanded {
+            self.expanded.remove(&node.path);
+        } else {
+            self.expanded.insert(node.path);
+        }
+        self.rebuild()?;
+        Ok(None)
+    }
+
+    // closes the directory under the cursor, or moves up to the one it is in
+    pub fn collapse(&mut self, height: usize) -> io::Result<()> {
+        let node = match self.selected() {
+            Some(node) => node.clone(),
+            None => return Ok(()),
+        };
+
+        if node.expanded {
+            self.expanded.remove(&node.path);
+            self.rebuild()?;
+        } else if let Some(parent) = self.nodes[..self.cursor].iter().rposition(|n| n.depth < node.depth) {
+            self.cursor = parent;
+        }
+
+        self.scroll(height);
+        Ok(())
+    }
+
+    pub fn toggle_hidden(&mut self) -> io::Result<()> {
+        self.show_hidden = !self.show_hidden;
+        self.rebuild()
+    }
+
+    // opens every directory down to path and puts the cursor on it,
+    // false when path isn't inside of the tree
+    pub fn reveal(&mut self, path: &Path, height: usize) -> io::Result<bool> {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+            _ => return Ok(false),
+        };
+
+        let mut dir = self.root.clone();
+        for component in relative.parent().into_iter().flat_map(|p| p.components()) {
+            dir.push(component);
+            self.expanded.insert(dir.clone());
+        }
+
+        // hidden files can only be found when they are listed
+        if relative.components().any(|c| c.as_os_str().to_string_lossy().starts_with('.')) {
+            self.show_hidden = true;
+        }
+        self.rebuild()?;
+
+        match self.nodes.iter().position(|n| n.path == path) {
+            Some(row) => {
+                self.select(row, height);
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+}
+
+
+#[test]
+fn test_file_tree() {
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("editor-tree-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src/deep")).unwrap();
+    fs::write(dir.join("src/deep/lib.rs"), "").unwrap();
+    fs::write(dir.join("src/main.rs"), "").unwrap();
+    fs::write(dir.join("README.md"), "").unwrap();
+
+    let names = |tree: &FileTree| -> Vec<String> {
+        tree.nodes.iter().map(|n| format!("{}{}", "  ".repeat(n.depth), n.name)).collect()
+    };
+
+    let mut tree = FileTree::new(&dir).unwrap();
+    assert_eq!(names(&tree), vec!["src", "README.md"]);
+
+    assert_eq!(tree.toggle().unwrap(), None);
+    assert_eq!(names(&tree), vec!["src", "  deep", "  main.rs", "README.md"]);
+
+    tree.move_down(10);
+    tree.move_down(10);
+    assert_eq!(tree.toggle().unwrap(), Some(dir.join("src/main.rs")));
+
+    // collapsing a file goes to its directory, then closes it
+    tree.collapse(10).unwrap();
+    assert_eq!(tree.selected().unwrap().name, "src");
+    tree.collapse(10).unwrap();
+    assert_eq!(names(&tree), vec!["src", "README.md"]);
+
+    assert!(tree.reveal(&dir.join("src/deep/lib.rs"), 2).unwrap());
+    assert_eq!(tree.selected().unwrap().path, dir.join("src/deep/lib.rs"));
+    assert_eq!(tree.offset, 1);
+    assert!(!tree.reveal(Path::new("/elsewhere"), 2).unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -17,7 +17,7 @@ impl CharKind {
     }
 }
 
-pub fn find_word_start_forward(line: &String, start_col: usize) -> Option<usize> {
+pub fn find_word_start_forward(line: &str, start_col: usize) -> Option<usize> {
     let mut it = line.chars().enumerate().skip(start_col);
     let mut prev = CharKind::new(it.next()?.1);
     for (col, c) in it {
@@ -30,7 +30,7 @@ pub fn find_word_start_forward(line: &String, start_col: usize) -> Option<usize>
     None
 }
 
-pub fn find_word_end_forward(line: &String, start_col: usize) -> Option<usize> {
+pub fn find_word_end_forward(line: &str, start_col: usize) -> Option<usize> {
     let mut it = line.chars().enumerate().skip(start_col + 1);
     let mut prev = CharKind::new(it.next()?.1);
     for (col, c) in it {
@@ -43,7 +43,7 @@ pub fn find_word_end_forward(line: &String, start_col: usize) -> Option<usize> {
     None
 }
 
-pub fn find_word_start_backward(line: &String, start_col: usize) -> Option<usize> {
+pub fn find_word_start_backward(line: &str, start_col: usize) -> Option<usize> {
     let idx = line
         .char_indices()
         .nth(start_col)
@@ -58,11 +58,11 @@ pub fn find_word_start_backward(line: &String, start_col: usize) -> Option<usize
         }
         cur = next;
     }
-    (cur != CharKind::Space).then(|| 0)
+    (cur != CharKind::Space).then_some(0)
 }
 
 #[warn(dead_code)]
-pub fn find_next_occur_forward (line: &String, start_col: usize, target: char) -> Option<usize> {
+pub fn find_next_occur_forward (line: &str, start_col: usize, target: char) -> Option<usize> {
     let it = line.chars().enumerate().skip(start_col);
     for (col, c) in it {
         if c == target {
@@ -73,7 +73,7 @@ pub fn find_next_occur_forward (line: &String, start_col: usize, target: char) -
 }
 
 #[warn(dead_code)]
-pub fn find_next_occur_backward (line: &String, start_col: usize, target: char) -> Option<usize> {
+pub fn find_next_occur_backward (line: &str, start_col: usize, target: char) -> Option<usize> {
     let idx = line
         .char_indices()
         .nth(start_col)
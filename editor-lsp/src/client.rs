@@ -0,0 +1,578 @@
+use crate::protocol::{
+    content_change, hover_text, path_to_uri, uri_to_path,
+    CodeAction, CompletionItem, Diagnostic, Location, Position, Range, WorkspaceEdit,
+};
+use crate::transport::{read_message, write_message};
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+use tokio::process::Child;
+use tokio::sync::{mpsc, oneshot};
+
+/*
+ * a connection to one language server for one workspace root
+ *
+ * nothing here blocks, requests are queued for a writer task and answers come
+ * back as events through the sink from a reader task, both on the tokio runtime
+ *
+ * until the server answers initialize everything else waits in the queue, as
+ * the protocol asks
+ */
+
+// what the server sent back, or said on its own
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Ready,
+    Diagnostics { path: PathBuf, diagnostics: Vec<Diagnostic> },
+    Hover(String), // empty when there is nothing to show
+    Definition(Vec<Location>),
+    References(Vec<Location>),
+    Rename(WorkspaceEdit),
+    CodeActions(Vec<CodeAction>),
+    Completion(Vec<CompletionItem>),
+    ApplyEdit(WorkspaceEdit), // the server wants these edits made, usually after a command
+    Message(String),
+    Error(String),
+    Exited,
+}
+
+pub type Sink = Arc<dyn Fn(Event) + Send + Sync>;
+
+// what a request was for, so its response can be read
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Request {
+    Initialize,
+    Hover,
+    Definition,
+    References,
+    Rename,
+    CodeActions,
+    Completion,
+    Shutdown,
+    Other,
+}
+
+// request errors that only mean the answer is out of date
+const REQUEST_CANCELLED: i64 = -32800;
+const CONTENT_MODIFIED: i64 = -32801;
+
+#[derive(Default)]
+struct Shared {
+    pending: HashMap<u64, Request>,
+    capabilities: Option<Value>, // None until initialized
+    diagnostics: HashMap<PathBuf, Vec<Diagnostic>>,
+}
+
+struct Document {
+    version: i64,
+    rope: Rope, // the text the server has
+}
+
+pub struct Client {
+    pub command: String,
+    pub root: PathBuf,
+    outgoing: mpsc::UnboundedSender<Value>,
+    shared: Arc<Mutex<Shared>>,
+    documents: HashMap<PathBuf, Document>,
+    next_id: u64,
+    child: Option<Child>,
+}
+
+impl Client {
+    // runs the server command in root and talks to it over stdio, has to be called on the runtime
+    pub fn start(command: &str, root: &Path, sink: Sink) -> io::Result<Client> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or(io::Error::new(io::ErrorKind::InvalidInput, "no server command"))?;
+
+        let mut child = tokio::process::Command::new(program)
+            .args(words)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or(io::Error::other("no stdin"))?;
+        let stdout = child.stdout.take().ok_or(io::Error::other("no stdout"))?;
+
+        let mut client = Client::connect(stdout, stdin, command, root, sink);
+        client.child = Some(child);
+        Ok(client)
+    }
+
+    // talks to a server over any pair of streams, sending initialize right away
+    pub fn connect<R, W>(reader: R, writer: W, command: &str, root: &Path, sink: Sink) -> Client
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (outgoing, mut queue) = mpsc::unbounded_channel::<Value>();
+        let (ready_sender, ready) = oneshot::channel::<()>();
+        let shared = Arc::new(Mutex::new(Shared::default()));
+
+        if let Ok(mut shared) = shared.lock() {
+            shared.pending.insert(0, Request::Initialize);
+        }
+        let initialize = request(0, "initialize", json!({
+            "processId": std::process::id(),
+            "clientInfo": { "name": "editor" },
+            "rootUri": path_to_uri(root),
+            "rootPath": root.to_string_lossy(),
+            "workspaceFolders": [{
+                "uri": path_to_uri(root),
+                "name": root.file_name().map_or(String::new(), |n| n.to_string_lossy().to_string()),
+            }],
+            "capabilities": capabilities(),
+        }));
+
+        tokio::spawn(async move {
+            let mut writer = writer;
+            if write_message(&mut writer, &initialize).await.is_err() || ready.await.is_err() {
+                return;
+            }
+            if write_message(&mut writer, &notification("initialized", json!({}))).await.is_err() {
+                return;
+            }
+
+            while let Some(message) = queue.recv().await {
+                if write_message(&mut writer, &message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (state, responses) = (shared.clone(), outgoing.clone());
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            let mut ready = Some(ready_sender);
+
+            while let Ok(Some(message)) = read_message(&mut reader).await {
+                handle_message(message, &state, &responses, &sink, &mut ready);
+            }
+            sink(Event::Exited);
+        });
+
+        Client {
+            command: command.to_string(),
+            root: root.to_path_buf(),
+            outgoing,
+            shared,
+            documents: HashMap::new(),
+            next_id: 1,
+            child: None,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.shared.lock().is_ok_and(|s| s.capabilities.is_some())
+    }
+
+    // whether the server has a capability like hoverProvider, assumed until it says
+    pub fn supports(&self, capability: &str) -> bool {
+        let shared = match self.shared.lock() {
+            Ok(shared) => shared,
+            Err(_) => return false,
+        };
+
+        match &shared.capabilities {
+            Some(capabilities) => !matches!(capabilities[capability], Value::Null | Value::Bool(false)),
+            None => true,
+        }
+    }
+
+    // chars that start a completion when typed, like . or ::
+    pub fn trigger_characters(&self) -> Vec<String> {
+        let shared = match self.shared.lock() {
+            Ok(shared) => shared,
+            Err(_) => return vec![],
+        };
+
+        shared.capabilities.as_ref()
+            .and_then(|c| c["completionProvider"]["triggerCharacters"].as_array().cloned())
+            .map_or(vec![], |chars| chars.iter().filter_map(|c| c.as_str().map(String::from)).collect())
+    }
+
+    // changes are only sent as ranges once the server has said it takes them
+    fn incremental(&self) -> bool {
+        let shared = match self.shared.lock() {
+            Ok(shared) => shared,
+            Err(_) => return false,
+        };
+
+        let sync = shared.capabilities.as_ref().map(|c| &c["textDocumentSync"]);
+        match sync {
+            Some(Value::Number(kind)) => kind.as_u64() == Some(2),
+            Some(Value::Object(options)) => options.get("change").and_then(Value::as_u64) == Some(2),
+            _ => false,
+        }
+    }
+
+    // what the server last sent about path
+    pub fn diagnostics(&self, path: &Path) -> Vec<Diagnostic> {
+        self.shared.lock().ok()
+            .and_then(|s| s.diagnostics.get(path).cloned())
+            .unwrap_or_default()
+    }
+
+    // the files the server has found something in
+    pub fn diagnostic_paths(&self) -> Vec<PathBuf> {
+        self.shared.lock().map_or(vec![], |s| s.diagnostics.keys().cloned().collect())
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        let _ = self.outgoing.send(notification(method, params));
+    }
+
+    fn send_request(&mut self, method: &str, params: Value, kind: Request) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.pending.insert(id, kind);
+        }
+        let _ = self.outgoing.send(request(id, method, params));
+    }
+
+    // NOTE: documents
+
+    pub fn is_open(&self, path: &Path) -> bool {
+        self.documents.contains_key(path)
+    }
+
+    pub fn did_open(&mut self, path: &Path, language_id: &str, rope: &Rope) {
+        if self.is_open(path) {
+            return;
+        }
+
+        self.notify("textDocument/didOpen", json!({
+            "textDocument": {
+                "uri": path_to_uri(path),
+                "languageId": language_id,
+                "version": 0,
+                "text": rope.to_string(),
+            },
+        }));
+        self.documents.insert(path.to_path_buf(), Document { version: 0, rope: rope.clone() });
+    }
+
+    // sends what changed since the server last saw the text, nothing when it is the same
+    pub fn did_change(&mut self, path: &Path, rope: &Rope) {
+        let incremental = self.incremental();
+        let document = match self.documents.get_mut(path) {
+            Some(document) => document,
+            None => return,
+        };
+
+        let change = match content_change(&document.rope, rope) {
+            Some(_) if !incremental => json!({ "text": rope.to_string() }),
+            Some(change) => change,
+            None => return,
+        };
+
+        document.version += 1;
+        document.rope = rope.clone();
+        let params = json!({
+            "textDocument": { "uri": path_to_uri(path), "version": document.version },
+            "contentChanges": [change],
+        });
+        self.notify("textDocument/didChange", params);
+    }
+
+    pub fn did_save(&self, path: &Path) {
+        if self.is_open(path) {
+            self.notify("textDocument/didSave", json!({ "textDocument": { "uri": path_to_uri(path) } }));
+        }
+    }
+
+    pub fn did_close(&mut self, path: &Path) {
+        if self.documents.remove(path).is_some() {
+            self.notify("textDocument/didClose", json!({ "textDocument": { "uri": path_to_uri(path) } }));
+        }
+    }
+
+    // NOTE: requests, answered with an event
+
+    pub fn hover(&mut self, path: &Path, position: Position) {
+        self.send_request("textDocument/hover", position_params(path, position), Request::Hover);
+    }
+
+    pub fn definition(&mut self, path: &Path, position: Position) {
+        self.send_request("textDocument/definition", position_params(path, position), Request::Definition);
+    }
+
+    pub fn references(&mut self, path: &Path, position: Position) {
+        let mut params = position_params(path, position);
+        params["context"] = json!({ "includeDeclaration": true });
+        self.send_request("textDocument/references", params, Request::References);
+    }
+
+    pub fn completion(&mut self, path: &Path, position: Position) {
+        self.send_request("textDocument/completion", position_params(path, position), Request::Completion);
+    }
+
+    pub fn rename(&mut self, path: &Path, position: Position, new_name: &str) {
+        let mut params = position_params(path, position);
+        params["newName"] = json!(new_name);
+        self.send_request("textDocument/rename", params, Request::Rename);
+    }
+
+    // actions for a range, the diagnostics on its lines go along so fixes for them come back
+    pub fn code_actions(&mut self, path: &Path, range: Range) {
+        let diagnostics: Vec<Value> = self.diagnostics(path)
+            .into_iter()
+            .filter(|d| d.range.start.line <= range.end.line && d.range.end.line >= range.start.line)
+            .map(|d| d.raw)
+            .collect();
+
+        let params = json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "range": range.to_value(),
+            "context": { "diagnostics": diagnostics },
+        });
+        self.send_request("textDocument/codeAction", params, Request::CodeActions);
+    }
+
+    // runs a command from a code action, its edits come back as an ApplyEdit
+    pub fn execute_command(&mut self, command: &Value) {
+        let params = json!({
+            "command": command["command"],
+            "arguments": command.get("arguments").cloned().unwrap_or(json!([])),
+        });
+        self.send_request("workspace/executeCommand", params, Request::Other);
+    }
+
+    // asks the server to stop, it is sent exit once it agrees
+    pub fn shutdown(&mut self) {
+        self.send_request("shutdown", Value::Null, Request::Shutdown);
+    }
+
+    // until the server's process is gone, one connected over other streams has none
+    pub async fn wait(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.wait().await;
+        }
+    }
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn position_params(path: &Path, position: Position) -> Value {
+    json!({
+        "textDocument": { "uri": path_to_uri(path) },
+        "position": position.to_value(),
+    })
+}
+
+// what the editor can handle, servers leave out anything not listed
+fn capabilities() -> Value {
+    json!({
+        "general": { "positionEncodings": ["utf-16"] },
+        "textDocument": {
+            "synchronization": { "didSave": true },
+            "hover": { "contentFormat": ["plaintext", "markdown"] },
+            "definition": { "linkSupport": true },
+            "references": {},
+            "rename": {},
+            "completion": {
+                "completionItem": { "documentationFormat": ["plaintext", "markdown"], "snippetSupport": true },
+            },
+            "codeAction": {
+                "codeActionLiteralSupport": {
+                    "codeActionKind": {
+                        "valueSet": ["", "quickfix", "refactor", "refactor.extract", "refactor.inline",
+                            "refactor.rewrite", "source", "source.organizeImports"],
+                    },
+                },
+            },
+            "publishDiagnostics": {},
+        },
+        "workspace": {
+            "applyEdit": true,
+            "workspaceEdit": { "documentChanges": true },
+            "configuration": true,
+        },
+    })
+}
+
+fn handle_message(
+    message: Value,
+    shared: &Mutex<Shared>,
+    outgoing: &mpsc::UnboundedSender<Value>,
+    sink: &Sink,
+    ready: &mut Option<oneshot::Sender<()>>,
+) {
+    let params = &message["params"];
+
+    match (message["method"].as_str(), message.get("id")) {
+        // requests from the server always get an answer so it doesn't wait on us
+        (Some(method), Some(id)) => {
+            let result = match method {
+                "workspace/applyEdit" => {
+                    sink(Event::ApplyEdit(WorkspaceEdit::from_value(&params["edit"])));
+                    json!({ "applied": true })
+                },
+                "workspace/configuration" => {
+                    let items = params["items"].as_array().map_or(0, Vec::len);
+                    Value::Array(vec![Value::Null; items])
+                },
+                _ => Value::Null,
+            };
+            let _ = outgoing.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+        },
+        (Some("textDocument/publishDiagnostics"), None) => {
+            let path = match params["uri"].as_str().and_then(uri_to_path) {
+                Some(path) => path,
+                None => return,
+            };
+            let diagnostics: Vec<Diagnostic> = params["diagnostics"].as_array()
+                .map_or(vec![], |d| d.iter().filter_map(Diagnostic::from_value).collect());
+
+            if let Ok(mut shared) = shared.lock() {
+                match diagnostics.is_empty() {
+                    true => shared.diagnostics.remove(&path),
+                    false => shared.diagnostics.insert(path.clone(), diagnostics.clone()),
+                };
+            }
+            sink(Event::Diagnostics { path, diagnostics });
+        },
+        // only errors and warnings, the rest is chatter
+        (Some("window/showMessage"), None) if params["type"].as_u64().is_some_and(|t| t <= 2) => {
+            sink(Event::Message(params["message"].as_str().unwrap_or("").to_string()));
+        },
+        (Some(_), None) => {},
+        (None, Some(id)) => {
+            let kind = match shared.lock() {
+                Ok(mut shared) => id.as_u64().and_then(|id| shared.pending.remove(&id)),
+                Err(_) => None,
+            };
+            let kind = match kind {
+                Some(kind) => kind,
+                None => return,
+            };
+
+            if let Some(error) = message.get("error") {
+                let code = error["code"].as_i64().unwrap_or(0);
+                if code != REQUEST_CANCELLED && code != CONTENT_MODIFIED {
+                    sink(Event::Error(error["message"].as_str().unwrap_or("request failed").to_string()));
+                }
+                return;
+            }
+
+            let result = &message["result"];
+            match kind {
+                Request::Initialize => {
+                    if let Ok(mut shared) = shared.lock() {
+                        shared.capabilities = Some(result["capabilities"].clone());
+                    }
+                    if let Some(ready) = ready.take() {
+                        let _ = ready.send(());
+                    }
+                    sink(Event::Ready);
+                },
+                Request::Hover => sink(Event::Hover(hover_text(result))),
+                Request::Definition => sink(Event::Definition(Location::list(result))),
+                Request::References => sink(Event::References(Location::list(result))),
+                Request::Rename => sink(Event::Rename(WorkspaceEdit::from_value(result))),
+                Request::CodeActions => {
+                    let actions = result.as_array()
+                        .map_or(vec![], |a| a.iter().filter_map(CodeAction::from_value).collect());
+                    sink(Event::CodeActions(actions));
+                },
+                Request::Completion => sink(Event::Completion(CompletionItem::list(result))),
+                Request::Shutdown => {
+                    let _ = outgoing.send(notification("exit", Value::Null));
+                },
+                Request::Other => {},
+            }
+        },
+        _ => {},
+    }
+}
+
+
+#[tokio::test]
+async fn test_client() {
+    use crate::mock::MockServer;
+
+    let (events, mut received) = mpsc::unbounded_channel();
+    let sink: Sink = Arc::new(move |event| { let _ = events.send(event); });
+    let root = Path::new("/work");
+    let file = root.join("src/main.rs");
+    let (mut client, mut server) = MockServer::connect("mock", root, sink);
+
+    // opened before the server is ready, it waits behind initialize
+    client.did_open(&file, "rust", &Rope::from("fn main() {}\n"));
+    let init = server.expect("initialize").await;
+    assert_eq!(init["params"]["rootUri"], "file:///work");
+    server.respond(&init, json!({ "capabilities": { "textDocumentSync": 2, "hoverProvider": true } })).await;
+    assert_eq!(received.recv().await, Some(Event::Ready));
+    assert!(client.supports("hoverProvider") && !client.supports("renameProvider"));
+
+    server.expect("initialized").await;
+    let open = server.expect("textDocument/didOpen").await;
+    assert_eq!(open["params"]["textDocument"]["text"], "fn main() {}\n");
+
+    // only the changed part goes out, with the version going up
+    client.did_change(&file, &Rope::from("fn main() { run(); }\n"));
+    client.did_change(&file, &Rope::from("fn main() { run(); }\n"));
+    let change = server.expect("textDocument/didChange").await;
+    assert_eq!(change["params"]["textDocument"]["version"], 1);
+    assert_eq!(change["params"]["contentChanges"][0]["text"], " run(); ");
+
+    client.hover(&file, Position { line: 0, character: 4 });
+    let hover = server.expect("textDocument/hover").await;
+    assert_eq!(hover["params"]["position"], json!({ "line": 0, "character": 4 }));
+    server.respond(&hover, json!({ "contents": { "kind": "plaintext", "value": "fn main()" } })).await;
+    assert_eq!(received.recv().await, Some(Event::Hover(String::from("fn main()"))));
+
+    client.completion(&file, Position { line: 0, character: 12 });
+    let completion = server.expect("textDocument/completion").await;
+    server.respond(&completion, json!([{ "label": "run", "detail": "fn()" }])).await;
+    let event = received.recv().await.unwrap();
+    assert!(matches!(event, Event::Completion(ref items) if items[0].label == "run" && items[0].insert_text == "run"));
+
+    // diagnostics are kept to send back with code actions
+    server.notify("textDocument/publishDiagnostics", json!({
+        "uri": "file:///work/src/main.rs",
+        "diagnostics": [{ "range": Range::default().to_value(), "severity": 2, "message": "unused" }],
+    })).await;
+    let event = received.recv().await.unwrap();
+    assert!(matches!(event, Event::Diagnostics { ref diagnostics, .. } if diagnostics[0].message == "unused"));
+
+    client.code_actions(&file, Range::default());
+    let actions = server.expect("textDocument/codeAction").await;
+    assert_eq!(actions["params"]["context"]["diagnostics"][0]["message"], "unused");
+    server.error(&actions, CONTENT_MODIFIED, "content modified").await;
+
+    // the server asking for edits gets an answer
+    server.request(7, "workspace/applyEdit", json!({ "edit": { "changes": { "file:///work/src/main.rs": [] } } })).await;
+    let event = received.recv().await.unwrap();
+    assert!(matches!(event, Event::ApplyEdit(ref edit) if edit.changes[0].0 == file));
+    let answer = server.recv().await.unwrap();
+    assert_eq!((answer["id"].clone(), answer["result"]["applied"].clone()), (json!(7), json!(true)));
+
+    client.rename(&file, Position::default(), "start");
+    let rename = server.expect("textDocument/rename").await;
+    server.error(&rename, -32602, "can't rename here").await;
+    assert_eq!(received.recv().await, Some(Event::Error(String::from("can't rename here"))));
+
+    client.shutdown();
+    let shutdown = server.expect("shutdown").await;
+    server.respond(&shutdown, Value::Null).await;
+    server.expect("exit").await;
+
+    drop(server);
+    assert_eq!(received.recv().await, Some(Event::Exited));
+}
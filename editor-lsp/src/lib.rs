@@ -0,0 +1,10 @@
+mod client;
+pub mod mock;
+pub mod protocol;
+mod transport;
+
+pub use client::{
+    Client,
+    Event,
+    Sink,
+};
@@ -0,0 +1,59 @@
+use crate::client::{Client, Sink};
+use crate::transport::{read_message, write_message};
+use serde_json::{json, Value};
+use std::path::Path;
+use tokio::io::{BufReader, DuplexStream, ReadHalf, WriteHalf};
+
+/*
+ * the server end of a client's streams, for tests to play a language server
+ * by reading what the client sent and writing back what a server would
+ */
+
+pub struct MockServer {
+    reader: BufReader<ReadHalf<DuplexStream>>,
+    writer: WriteHalf<DuplexStream>,
+}
+
+impl MockServer {
+    // a client connected to a new mock server
+    pub fn connect(command: &str, root: &Path, sink: Sink) -> (Client, MockServer) {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (client_reader, client_writer) = tokio::io::split(client);
+        let (reader, writer) = tokio::io::split(server);
+
+        let client = Client::connect(client_reader, client_writer, command, root, sink);
+        (client, MockServer { reader: BufReader::new(reader), writer })
+    }
+
+    pub async fn recv(&mut self) -> Option<Value> {
+        read_message(&mut self.reader).await.ok().flatten()
+    }
+
+    // the next message, which has to be for method
+    pub async fn expect(&mut self, method: &str) -> Value {
+        let message = self.recv().await.unwrap_or(Value::Null);
+        assert_eq!(message["method"], method, "got {}", message);
+        message
+    }
+
+    pub async fn respond(&mut self, request: &Value, result: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })).await;
+    }
+
+    pub async fn error(&mut self, request: &Value, code: i64, message: &str) {
+        let error = json!({ "code": code, "message": message });
+        self.send(json!({ "jsonrpc": "2.0", "id": request["id"], "error": error })).await;
+    }
+
+    pub async fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params })).await;
+    }
+
+    pub async fn request(&mut self, id: u64, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).await;
+    }
+
+    pub async fn send(&mut self, message: Value) {
+        let _ = write_message(&mut self.writer, &message).await;
+    }
+}
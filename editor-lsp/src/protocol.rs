@@ -0,0 +1,469 @@
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/*
+ * the parts of the language server protocol the editor uses, read out of
+ * serde_json values instead of a full set of protocol types
+ *
+ * positions count lines from 0 and characters in utf-16 code units, ropes
+ * count chars, so everything going in or out is converted here
+ */
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize, // utf-16 code units
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub range: Range,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Hint,
+    Information,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub message: String,
+    pub source: Option<String>,
+    pub raw: Value, // sent back as is when asking for code actions
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+// edits to make by file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorkspaceEdit {
+    pub changes: Vec<(PathBuf, Vec<TextEdit>)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeAction {
+    pub title: String,
+    pub edit: Option<WorkspaceEdit>,
+    pub command: Option<Value>, // run on the server with workspace/executeCommand
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompletionItem {
+    pub label: String,
+    pub insert_text: String, // the edit's text, insertText or else the label
+    pub range: Option<Range>, // what the textEdit replaces, or else the word before the cursor
+    pub detail: Option<String>,
+    pub documentation: Option<String>,
+    pub snippet: bool, // the text has tabstops to expand
+}
+
+impl Position {
+    pub fn to_value(self) -> Value {
+        json!({ "line": self.line, "character": self.character })
+    }
+
+    pub fn from_value(value: &Value) -> Option<Self> {
+        Some(Position {
+            line: value["line"].as_u64()? as usize,
+            character: value["character"].as_u64()? as usize,
+        })
+    }
+
+    // where a char index of the rope is
+    pub fn from_char(rope: &Rope, idx: usize) -> Self {
+        let idx = idx.min(rope.len_chars());
+        let line = rope.char_to_line(idx);
+        let line_start = rope.line_to_char(line);
+
+        Position {
+            line,
+            character: rope.char_to_utf16_cu(idx) - rope.char_to_utf16_cu(line_start),
+        }
+    }
+
+    // the char index of the rope the position points at, clamped to the line it is on
+    pub fn to_char(self, rope: &Rope) -> usize {
+        if self.line >= rope.len_lines() {
+            return rope.len_chars();
+        }
+
+        let line_start = rope.line_to_char(self.line);
+        let line = rope.line(self.line);
+        let mut len = line.len_chars();
+        if line.chars().last() == Some('\n') {
+            len -= 1;
+        }
+
+        let units = rope.char_to_utf16_cu(line_start) + self.character;
+        let idx = rope.utf16_cu_to_char(units.min(rope.len_utf16_cu()));
+        idx.clamp(line_start, line_start + len)
+    }
+}
+
+impl Range {
+    pub fn to_value(self) -> Value {
+        json!({ "start": self.start.to_value(), "end": self.end.to_value() })
+    }
+
+    pub fn from_value(value: &Value) -> Option<Self> {
+        Some(Range {
+            start: Position::from_value(&value["start"])?,
+            end: Position::from_value(&value["end"])?,
+        })
+    }
+
+    // lines are end exclusive, a range ending at the start of a line doesn't cover it
+    pub fn covers_line(&self, line: usize) -> bool {
+        if line == self.end.line && self.end.character == 0 && self.end.line > self.start.line {
+            return false;
+        }
+        self.start.line <= line && line <= self.end.line
+    }
+}
+
+impl Location {
+    // a Location or a LocationLink
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let uri = value["uri"].as_str().or(value["targetUri"].as_str())?;
+        let range = if value["uri"].is_string() { &value["range"] } else { &value["targetSelectionRange"] };
+
+        Some(Location {
+            path: uri_to_path(uri)?,
+            range: Range::from_value(range)?,
+        })
+    }
+
+    // one location, a list of them or null
+    pub fn list(value: &Value) -> Vec<Self> {
+        match value {
+            Value::Array(values) => values.iter().filter_map(Location::from_value).collect(),
+            Value::Object(_) => Location::from_value(value).into_iter().collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let severity = match value["severity"].as_u64() {
+            Some(2) => Severity::Warning,
+            Some(3) => Severity::Information,
+            Some(4) => Severity::Hint,
+            // servers leave it out to mean an error
+            _ => Severity::Error,
+        };
+
+        Some(Diagnostic {
+            range: Range::from_value(&value["range"])?,
+            severity,
+            message: value["message"].as_str()?.to_string(),
+            source: value["source"].as_str().map(String::from),
+            raw: value.clone(),
+        })
+    }
+}
+
+impl TextEdit {
+    pub fn from_value(value: &Value) -> Option<Self> {
+        Some(TextEdit {
+            range: Range::from_value(&value["range"])?,
+            new_text: value["newText"].as_str()?.to_string(),
+        })
+    }
+}
+
+impl WorkspaceEdit {
+    // reads both the changes map and documentChanges, file operations are skipped
+    pub fn from_value(value: &Value) -> Self {
+        let edits = |value: &Value| -> Vec<TextEdit> {
+            value.as_array().map_or(vec![], |edits| edits.iter().filter_map(TextEdit::from_value).collect())
+        };
+
+        let mut changes = vec![];
+        if let Some(map) = value["changes"].as_object() {
+            for (uri, value) in map {
+                if let Some(path) = uri_to_path(uri) {
+                    changes.push((path, edits(value)));
+                }
+            }
+        }
+        if let Some(documents) = value["documentChanges"].as_array() {
+            for document in documents {
+                if let Some(path) = document["textDocument"]["uri"].as_str().and_then(uri_to_path) {
+                    changes.push((path, edits(&document["edits"])));
+                }
+            }
+        }
+
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        WorkspaceEdit { changes }
+    }
+}
+
+impl CodeAction {
+    // a CodeAction, or a bare Command which servers may send instead
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let title = value["title"].as_str()?.to_string();
+
+        if value["command"].is_string() {
+            return Some(CodeAction { title, edit: None, command: Some(value.clone()) });
+        }
+
+        Some(CodeAction {
+            title,
+            edit: value.get("edit").map(WorkspaceEdit::from_value),
+            command: value.get("command").cloned(),
+        })
+    }
+}
+
+impl CompletionItem {
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let label = value["label"].as_str()?.to_string();
+        let insert_text = value["textEdit"]["newText"].as_str()
+            .or(value["insertText"].as_str())
+            .unwrap_or(&label)
+            .to_string();
+        let documentation = Some(markup_text(&value["documentation"])).filter(|d| !d.is_empty());
+        // an InsertReplaceEdit has two ranges, inserting leaves the rest of the word alone
+        let range = Range::from_value(&value["textEdit"]["range"]).or(Range::from_value(&value["textEdit"]["insert"]));
+
+        Some(CompletionItem {
+            label,
+            insert_text,
+            range,
+            detail: value["detail"].as_str().map(String::from),
+            documentation,
+            snippet: value["insertTextFormat"].as_u64() == Some(2),
+        })
+    }
+
+    // a list of items, or one that says it is incomplete
+    pub fn list(value: &Value) -> Vec<Self> {
+        let items = match value {
+            Value::Array(items) => items,
+            _ => match value["items"].as_array() {
+                Some(items) => items,
+                None => return vec![],
+            },
+        };
+        items.iter().filter_map(CompletionItem::from_value).collect()
+    }
+}
+
+// text that can be a plain string, a MarkupContent or a list of MarkedStrings
+fn markup_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(values) => values.iter().map(markup_text).filter(|t| !t.is_empty()).collect::<Vec<_>>().join("\n\n"),
+        Value::Object(_) => value["value"].as_str().unwrap_or("").to_string(),
+        _ => String::new(),
+    }
+}
+
+// the text of a hover, whichever of the shapes the server sent it in
+pub fn hover_text(value: &Value) -> String {
+    markup_text(&value["contents"]).trim().to_string()
+}
+
+// the didChange event that turns old into new, just the part in between what
+// they start and end with, None when nothing changed
+pub fn content_change(old: &Rope, new: &Rope) -> Option<Value> {
+    if old == new {
+        return None;
+    }
+
+    let prefix = old.chars().zip(new.chars()).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len_chars().min(new.len_chars()) - prefix;
+    let suffix = old.chars_at(old.len_chars()).reversed()
+        .zip(new.chars_at(new.len_chars()).reversed())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let range = Range {
+        start: Position::from_char(old, prefix),
+        end: Position::from_char(old, old.len_chars() - suffix),
+    };
+    let text = new.slice(prefix..new.len_chars() - suffix).to_string();
+
+    Some(json!({ "range": range.to_value(), "text": text }))
+}
+
+// the name the protocol uses for one of the editor's file types
+pub fn language_id(file_type: &str) -> &str {
+    match file_type {
+        "sh" => "shellscript",
+        "make" => "makefile",
+        other => other,
+    }
+}
+
+// nearest directory above path holding one of the markers, tried in order,
+// falling back to the directory path is in
+pub fn find_root(path: &Path, markers: &[&str]) -> PathBuf {
+    let start = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+
+    for marker in markers.iter().chain([".git"].iter()) {
+        if let Some(dir) = start.ancestors().find(|dir| dir.join(marker).exists()) {
+            return dir.to_path_buf();
+        }
+    }
+
+    start.to_path_buf()
+}
+
+// bytes that can go in a uri's path as they are
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~' | b'/')
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from(if path.starts_with('/') { "file://" } else { "file:///" });
+
+    for b in path.bytes() {
+        if is_unreserved(b) || b == b':' {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    uri
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = vec![];
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    let path = String::from_utf8(decoded).ok()?;
+    // windows paths come as /C:/...
+    if cfg!(windows) {
+        return Some(PathBuf::from(path.trim_start_matches('/')));
+    }
+    Some(PathBuf::from(path))
+}
+
+
+#[test]
+fn test_positions() {
+    let rope = Rope::from("let a = \"😀é\";\nx\n");
+
+    // the emoji is two utf-16 units but one char
+    let quote = rope.to_string().find('😀').unwrap();
+    let idx = rope.byte_to_char(quote) + 1;
+    assert_eq!(Position::from_char(&rope, idx), Position { line: 0, character: 11 });
+    assert_eq!(Position { line: 0, character: 11 }.to_char(&rope), idx);
+
+    // past the end of a line stays on it
+    assert_eq!(Position { line: 1, character: 9 }.to_char(&rope), rope.line_to_char(1) + 1);
+    assert_eq!(Position { line: 7, character: 0 }.to_char(&rope), rope.len_chars());
+}
+
+#[test]
+fn test_content_change() {
+    let old = Rope::from("fn main() {\n    old();\n}\n");
+    let new = Rope::from("fn main() {\n    new_call();\n}\n");
+
+    let change = content_change(&old, &new).unwrap();
+    assert_eq!(change["range"], Range {
+        start: Position { line: 1, character: 4 },
+        end: Position { line: 1, character: 7 },
+    }.to_value());
+    assert_eq!(change["text"], "new_call");
+
+    // a deleted line
+    let change = content_change(&new, &Rope::from("fn main() {\n}\n")).unwrap();
+    assert_eq!(change["range"]["start"], Position { line: 1, character: 0 }.to_value());
+    assert_eq!(change["range"]["end"], Position { line: 2, character: 0 }.to_value());
+    assert_eq!(change["text"], "");
+
+    // repeated characters only count once
+    let change = content_change(&Rope::from("aa\n"), &Rope::from("aaa\n")).unwrap();
+    assert_eq!(change["text"], "a");
+    assert!(content_change(&old, &old.clone()).is_none());
+}
+
+#[test]
+fn test_uris() {
+    let path = Path::new("/tmp/my project/src/main.rs");
+    assert_eq!(path_to_uri(path), "file:///tmp/my%20project/src/main.rs");
+    assert_eq!(uri_to_path(&path_to_uri(path)).unwrap(), path);
+    assert!(uri_to_path("https://example.com").is_none());
+}
+
+#[test]
+fn test_parse_values() {
+    let edit = WorkspaceEdit::from_value(&json!({
+        "documentChanges": [{
+            "textDocument": { "uri": "file:///b.rs", "version": 1 },
+            "edits": [{ "range": Range::default().to_value(), "newText": "x" }],
+        }],
+        "changes": { "file:///a.rs": [] },
+    }));
+    assert_eq!(edit.changes.len(), 2);
+    assert_eq!(edit.changes[0].0, PathBuf::from("/a.rs"));
+    assert_eq!(edit.changes[1].1[0].new_text, "x");
+
+    let hover = json!({ "contents": [{ "language": "rust", "value": "fn main()" }, "docs"] });
+    assert_eq!(hover_text(&hover), "fn main()\n\ndocs");
+    assert_eq!(hover_text(&json!({ "contents": { "kind": "markdown", "value": "*hi*" } })), "*hi*");
+
+    let link = json!([{ "targetUri": "file:///a.rs", "targetRange": Range::default().to_value(),
+        "targetSelectionRange": { "start": { "line": 3, "character": 1 }, "end": { "line": 3, "character": 4 } } }]);
+    assert_eq!(Location::list(&link)[0].range.start.line, 3);
+
+    let action = CodeAction::from_value(&json!({ "title": "Fix", "command": "fix.all", "arguments": [] })).unwrap();
+    assert!(action.edit.is_none() && action.command.is_some());
+
+    let range = Range { start: Position { line: 1, character: 2 }, end: Position { line: 3, character: 0 } };
+    assert!(range.covers_line(1) && range.covers_line(2) && !range.covers_line(3));
+
+    let items = CompletionItem::list(&json!({ "isIncomplete": false, "items": [
+        { "label": "len", "detail": "fn(&self) -> usize", "documentation": { "kind": "markdown", "value": "Returns the length" } },
+        { "label": "push", "insertText": "push()" },
+        { "label": "pop", "textEdit": { "range": Range::default().to_value(), "newText": "pop()" } },
+        { "label": "insert", "insertText": "insert(${1:index}, ${2:element})", "insertTextFormat": 2 },
+    ] }));
+    assert_eq!(items[0].documentation.as_deref(), Some("Returns the length"));
+    assert_eq!((items[1].insert_text.as_str(), items[2].insert_text.as_str()), ("push()", "pop()"));
+    assert!(items[3].snippet && !items[1].snippet);
+    assert_eq!((items[1].range, items[2].range), (None, Some(Range::default())));
+
+    let replace = Range { start: Position { line: 0, character: 2 }, end: Position { line: 0, character: 5 } };
+    let items = CompletionItem::list(&json!([
+        { "label": "len", "textEdit": { "insert": Range::default().to_value(), "replace": replace.to_value(), "newText": "len" } },
+    ]));
+    assert_eq!(items[0].range, Some(Range::default()));
+}
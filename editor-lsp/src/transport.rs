@@ -0,0 +1,79 @@
+use serde_json::Value;
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/*
+ * json-rpc messages over a byte stream, each one is a Content-Length header,
+ * a blank line and then that many bytes of json
+ */
+
+pub fn encode(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    let mut bytes = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    bytes.extend(body.as_bytes());
+    bytes
+}
+
+pub async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Value) -> io::Result<()> {
+    writer.write_all(&encode(message)).await?;
+    writer.flush().await
+}
+
+// the next message, None once the stream has ended
+pub async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            // a blank line before any header is left over from the last message
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+
+        // other headers like Content-Type are only ever the default
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                let value = value.trim().parse::<usize>()
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad Content-Length"))?;
+                length = Some(value);
+            }
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or(0)];
+    reader.read_exact(&mut body).await?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+
+#[tokio::test]
+async fn test_messages() {
+    use serde_json::json;
+
+    let first = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"text": "é"}});
+    let second = json!({"jsonrpc": "2.0", "method": "exit"});
+
+    // the length is in bytes, not chars
+    let mut bytes = encode(&first);
+    let header = format!("Content-Length: {}\r\n\r\n", first.to_string().len());
+    assert!(bytes.starts_with(header.as_bytes()));
+    assert_eq!(bytes.len() - header.len(), first.to_string().chars().count() + 1);
+    bytes.extend(b"Content-Type: application/vscode-jsonrpc; charset=utf-8\r\n");
+    bytes.extend(encode(&second));
+
+    let mut reader = tokio::io::BufReader::new(bytes.as_slice());
+    assert_eq!(read_message(&mut reader).await.unwrap(), Some(first));
+    assert_eq!(read_message(&mut reader).await.unwrap(), Some(second));
+    assert_eq!(read_message(&mut reader).await.unwrap(), None);
+}
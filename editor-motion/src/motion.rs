@@ -44,27 +44,24 @@ impl MotionHandler {
     pub fn handle_char(&mut self, c: Option<char>) {
         if let Some(c) = c {
             let x = self.state_machine.recv(c);
-            match x {
-                States::End => {
-                    let finished_motion = self.state_machine.fetch();
-                    self.state_machine.refresh();
-                    let _res = self.send(finished_motion);
-                }
-                _ => {},
+            if x == States::End {
+                let finished_motion = self.state_machine.fetch();
+                self.state_machine.refresh();
+                let _res = self.send(finished_motion);
             }
         }
     }
 
     fn send(&mut self, a: Vec<String>) -> Option<Vec<String>> {
         let res = self.output.send(a);
-        if let Ok(_) = res {
+        if res.is_ok() {
             return None;
         }
         Some(res.unwrap_err().0)
     }
 
     pub fn get_text(&self) -> Option<String> {
-        if self.state_machine.input.len() != 0 {
+        if !self.state_machine.input.is_empty() {
             return Some(self.state_machine.to_string());
         }
         None
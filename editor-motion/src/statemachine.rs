@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::fmt;
 
 macro_rules! hashmap {
     (@single $($x:tt)*) => (());
@@ -34,9 +35,17 @@ pub struct StateMachine {
     queue: String,
     pub input: Vec<String>,
     map: HashMap<String, FunctionType>,
+    operators: Vec<&'static str>, // take a text object like ip as well as a motion
+    leader_map: Vec<&'static str>, // sequences that can follow the leader key
 }
 
 
+impl Default for StateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // TODO: add functions from state machine
 //
 // reset to start from motions and 'functions'
@@ -64,17 +73,40 @@ impl StateMachine {
                 "A".to_string() => FunctionType::Final,
                 "0".to_string() => FunctionType::Final,
                 "o".to_string() => FunctionType::Final,
+                "u".to_string() => FunctionType::Final,
+                "p".to_string() => FunctionType::Final,
+                "K".to_string() => FunctionType::Final,
+                "%".to_string() => FunctionType::Final,
+                "~".to_string() => FunctionType::Final,
+                "J".to_string() => FunctionType::Final,
+                "R".to_string() => FunctionType::Final,
+                "V".to_string() => FunctionType::Final,
+                "\u{1}".to_string() => FunctionType::Final, // ctrl-a
+                "\u{18}".to_string() => FunctionType::Final, // ctrl-x
 
                 "d".to_string() => FunctionType::NeedsParam,
+                "y".to_string() => FunctionType::NeedsParam,
+                "!".to_string() => FunctionType::NeedsParam,
+                ">".to_string() => FunctionType::NeedsParam,
+                "<".to_string() => FunctionType::NeedsParam,
+                "=".to_string() => FunctionType::NeedsParam,
                 "f".to_string() => FunctionType::NeedsParam,
+                "r".to_string() => FunctionType::NeedsParam,
                 "g".to_string() => FunctionType::NeedsParam,
+                "m".to_string() => FunctionType::NeedsParam,
+                "'".to_string() => FunctionType::NeedsParam,
+                "`".to_string() => FunctionType::NeedsParam,
+                "]".to_string() => FunctionType::NeedsParam,
+                "[".to_string() => FunctionType::NeedsParam,
             },
+            operators: vec!["d", "y", "!", ">", "<", "=", "gc", "g~", "gu", "gU"],
+            leader_map: vec!["ff", "fb", "fh", "fm"],
         }
     }
 
     // TODO: impl this function
     pub fn push(&mut self, c: char) {
-        if c.is_digit(10) {
+        if c.is_ascii_digit() {
             self.queue.push(c);
         } else {
             if !self.queue.is_empty() {
@@ -101,7 +133,7 @@ impl StateMachine {
         let leader = ' ';
         match &self.state {
             States::Start => {
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     self.push(c);
                     self.state = States::Start;
                 } else if let Some(t) = &self.map.get(&c.clone().to_string()){
@@ -123,7 +155,23 @@ impl StateMachine {
                 }
             },
             States::NeedsParam => {
-                if c.is_digit(10) {
+                let last = self.input.last().map_or("", |l| l.as_str());
+                if c.is_ascii_digit() {
+                    self.push(c);
+                    self.state = States::NeedsParam;
+                } else if last == "i" || last == "a" {
+                    // the object goes in with the i or a before it, like ip
+                    if let Some(last) = self.input.last_mut() {
+                        last.push(c);
+                    }
+                    self.state = States::End;
+                } else if self.operators.contains(&format!("{}{}", last, c).as_str()) {
+                    // operators that take more than one key, like gc
+                    if let Some(last) = self.input.last_mut() {
+                        last.push(c);
+                    }
+                    self.state = States::NeedsParam;
+                } else if (c == 'i' || c == 'a') && self.operators.contains(&last) {
                     self.push(c);
                     self.state = States::NeedsParam;
                 } else {
@@ -132,27 +180,30 @@ impl StateMachine {
                 }
             },
             States::Leader => {
-                // TODO: leader functions
+                // the whole sequence ends up as one input, like <leader>ff
+                if let Some(last) = self.input.last_mut() {
+                    last.push(c);
+                }
+
+                let sequence = self.input.last()
+                    .and_then(|l| l.strip_prefix("<leader>"))
+                    .unwrap_or("")
+                    .to_string();
+
+                if self.leader_map.contains(&sequence.as_str()) {
+                    self.state = States::End;
+                } else if !self.leader_map.iter().any(|l| l.starts_with(&sequence)) {
+                    self.refresh();
+                }
             }
             States::End => self.state = States::Start,
         }
 
-        self.state.clone()
+        self.state
     }
 
     pub fn fetch(&self) -> Vec<String> {
-        return self.input.clone();
-    }
-
-    pub fn to_string(&self) -> String {
-        let mut s = String::new();
-
-        for i in &self.input {
-            s.push_str(i);
-        }
-
-        s.extend(self.queue.clone().chars());
-        s
+        self.input.clone()
     }
 
     pub fn refresh(&mut self) {
@@ -162,6 +213,16 @@ impl StateMachine {
     }
 }
 
+impl fmt::Display for StateMachine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in &self.input {
+            write!(f, "{}", i)?;
+        }
+
+        write!(f, "{}", self.queue)
+    }
+}
+
 #[test]
 fn test_motion() {
     let mut sm = StateMachine::new();
@@ -173,3 +234,47 @@ fn test_motion() {
     sm.refresh();
     assert!(sm.queue.is_empty());
 }
+
+#[test]
+fn test_text_object() {
+    let mut sm = StateMachine::new();
+
+    sm.recv('=');
+    assert_eq!(sm.recv('i'), States::NeedsParam);
+    assert_eq!(sm.recv('p'), States::End);
+    assert_eq!(sm.fetch(), vec!["=".to_string(), "ip".to_string()]);
+
+    sm.refresh();
+    sm.recv('g');
+    assert_eq!(sm.recv('c'), States::NeedsParam);
+    sm.recv('a');
+    assert_eq!(sm.recv('p'), States::End);
+    assert_eq!(sm.fetch(), vec!["gc".to_string(), "ap".to_string()]);
+
+    sm.refresh();
+    sm.recv('g');
+    sm.recv('U');
+    assert_eq!(sm.recv('U'), States::End);
+    assert_eq!(sm.fetch(), vec!["gU".to_string(), "U".to_string()]);
+
+    // only operators take one
+    sm.refresh();
+    sm.recv('f');
+    assert_eq!(sm.recv('i'), States::End);
+}
+
+#[test]
+fn test_leader() {
+    let mut sm = StateMachine::new();
+
+    assert_eq!(sm.recv(' '), States::Leader);
+    assert_eq!(sm.recv('f'), States::Leader);
+    assert_eq!(sm.recv('f'), States::End);
+    assert_eq!(sm.fetch(), vec!["<leader>ff".to_string()]);
+
+    // sequences that lead nowhere are dropped
+    sm.refresh();
+    sm.recv(' ');
+    assert_eq!(sm.recv('x'), States::Start);
+    assert!(sm.input.is_empty());
+}
@@ -1,8 +1,8 @@
 // rgb code
-#[warn(dead_code)]
+#[allow(dead_code)]
 type Color = (u8, u8, u8);
 
-#[warn(dead_code)]
+#[allow(dead_code)]
 struct ColorConfig {
     background: Color,
     foreground: Color,
@@ -10,7 +10,7 @@ struct ColorConfig {
     active: bool,
 }
 
-#[warn(dead_code)]
+#[allow(dead_code)]
 impl ColorConfig {
     // will read in from a config
     fn new() -> Self {
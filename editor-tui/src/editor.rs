@@ -1,12 +1,35 @@
 use editor_core::{
     buffer::{Buffer, BufferType, Mode},
-    command::{Command, CommandKey},
+    command::{Address, Command, CommandKey, LineRef, Range},
+    completion::{self, Completion},
+    diagnostic,
+    explorer::{self, Change},
+    file::{complete_path, display_name, normalize},
+    filetype,
+    format,
+    grep,
+    make::{self, ErrorFormat},
+    picker::{project_files, Item, Picker, PickerKind},
+    quickfix::{Entry, Quickfix, QuickfixKind, Severity},
+    shell,
+    snippet::{self, Snippet},
+    text::Case,
+    tree::FileTree,
     X_OFFSET,
 };
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent};
+use editor_lsp::{
+    protocol::{find_root, language_id, Diagnostic, Location, Position, Range as LspRange, WorkspaceEdit},
+    Client, Event as LspEvent, Sink,
+};
+use crate::prompt::{Prompt, PromptAction};
+use crate::task::{AfterFormat, TaskOutput};
+use crate::widgets::picker_area;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use color_eyre::eyre::Result;
+use regex::Regex;
+use ropey::Rope;
 use std::{
-    io::Write, net::TcpStream, usize
+    collections::HashMap, io::Write, net::TcpStream, path::{Path, PathBuf}, sync::Arc
 };
 use ratatui::{
     prelude::Style,
@@ -39,26 +62,68 @@ pub struct Editor {
     // pub windows: Vec<Window>,
     // pub win_ptr: usize,
     pub buf_ptr: usize,
+    pub alt_buf: Option<usize>, // id of the alternate buffer
+    pub next_buf_id: usize,
     pub command: Command,
     pub should_quit: bool,
     pub size: (u16, u16),
     pub logger: Option<TcpStream>,
     pub message: Option<String>,
+    pub prompt: Option<Prompt>,
+    pub tree: Option<FileTree>, // kept while hidden so it opens the way it was left
+    pub show_tree: bool,
+    pub focus: Focus,
+    pub tree_width: u16,
+    pub picker: Option<Picker>,
+    pub quickfix: Option<Quickfix>,
+    pub show_quickfix: bool,
+    pub make_command: String, // run by :make
+    pub error_format: String, // how :make output is read
+    pub trash: Option<PathBuf>, // where deleted files go
+    window_key: bool, // ctrl-w was pressed, the next key picks a window
+    visual_g: bool, // g was pressed in visual mode, c after it comments the lines
+    terminal_escape: bool, // ctrl-\ was pressed in a terminal
+    pub register: Option<String>, // last yanked text
+    pub start_servers: bool, // language servers are only started once this is set
+    servers: Vec<Client>,
+    attached: HashMap<usize, Attached>, // by buffer id
+    code_actions: Option<(String, PathBuf, Vec<editor_lsp::protocol::CodeAction>)>, // from the last :codeaction
+    pub float: Option<String>, // shown by the cursor until the next key
+    pub completion: Option<Completion>, // the insert mode popup
 
     pub motion_sender: UnboundedSender<char>,
     pub clear_sender: UnboundedSender<bool>,
     pub motion_listener: UnboundedReceiver<Vec<String>>,
+    task_sender: UnboundedSender<TaskOutput>,
+}
+
+// a buffer sent to a language server, by the server's command and root, with
+// the revision the server last got
+#[derive(Clone, Debug)]
+struct Attached {
+    command: String,
+    root: PathBuf,
+    path: PathBuf,
+    revision: usize,
+}
+
+// part of the screen that gets the keys
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Focus {
+    Buffer,
+    Tree,
+    Quickfix,
 }
 
 impl Editor {
-    pub fn new(motion_sender: mpsc::UnboundedSender<char>, clear_sender: mpsc::UnboundedSender<bool>, motion_buffer_listener: mpsc::UnboundedReceiver<Vec<String>>) -> Result<Editor> {
+    pub fn new(motion_sender: mpsc::UnboundedSender<char>, clear_sender: mpsc::UnboundedSender<bool>, motion_buffer_listener: mpsc::UnboundedReceiver<Vec<String>>, task_sender: mpsc::UnboundedSender<TaskOutput>) -> Result<Editor> {
         // port address for logger
         let port = match std::env::args().nth(2) {
             Some(value) => value,
             None => "".to_string()
         };
 
-        if port != "" {
+        if !port.is_empty() {
             let stream = TcpStream::connect(format!("127.0.0.1:{}", port));
 
             if let Ok(mut stream) = stream {
@@ -66,11 +131,35 @@ impl Editor {
                     return Ok(Editor {
                         buffers: vec![],
                         buf_ptr: 0,
+                        alt_buf: None,
+                        next_buf_id: 1,
                         command: Command::new(),
                         should_quit: false,
                         size: (0, 0),
                         logger: Some(stream),
                         message: None,
+                        prompt: None,
+                        tree: None,
+                        show_tree: false,
+                        focus: Focus::Buffer,
+                        quickfix: None,
+                        show_quickfix: false,
+                        make_command: String::from(make::DEFAULT_MAKE),
+                        error_format: String::from(make::DEFAULT_ERROR_FORMAT),
+                        task_sender,
+                        tree_width: 0,
+                        picker: None,
+                        window_key: false,
+                        visual_g: false,
+                        terminal_escape: false,
+                        register: None,
+                        start_servers: false,
+                        servers: vec![],
+                        attached: HashMap::new(),
+                        code_actions: None,
+                        trash: explorer::trash_dir(),
+                        float: None,
+                        completion: None,
 
                         motion_listener: motion_buffer_listener,
                         motion_sender,
@@ -80,22 +169,46 @@ impl Editor {
             }
         } 
 
-        return Ok(Editor {
+        Ok(Editor {
             buffers: vec![],
             buf_ptr: 0,
+            alt_buf: None,
+            next_buf_id: 1,
             command: Command::new(),
             should_quit: false,
             size: (0, 0),
             logger: None,
             message: None,
+            prompt: None,
+            tree: None,
+            show_tree: false,
+            focus: Focus::Buffer,
+            quickfix: None,
+            show_quickfix: false,
+            make_command: String::from(make::DEFAULT_MAKE),
+            error_format: String::from(make::DEFAULT_ERROR_FORMAT),
+            task_sender,
+            tree_width: 0,
+            picker: None,
+            window_key: false,
+            visual_g: false,
+            terminal_escape: false,
+            register: None,
+            start_servers: false,
+            servers: vec![],
+            attached: HashMap::new(),
+            code_actions: None,
+            trash: explorer::trash_dir(),
+            float: None,
+            completion: None,
 
             motion_listener: motion_buffer_listener,
             motion_sender,
             clear_sender,
-        });
+        })
     }
 
-    fn current_buffer(&self) -> Option<&Buffer> {
+    pub fn current_buffer(&self) -> Option<&Buffer> {
         self.buffers.get(self.buf_ptr)
     }
 
@@ -109,7 +222,7 @@ impl Editor {
         }
 
         match mode {
-            Mode::Insert | Mode::Command => {
+            Mode::Insert | Mode::Replace | Mode::Command => {
                 self.set_message(None);
                 self.command.clear();
             }
@@ -120,19 +233,30 @@ impl Editor {
     // NOTE: display functions
     //
     // TODO: find way to get motion string
-    pub fn mode_display(&mut self) -> Paragraph {
+    pub fn mode_display(&mut self) -> Paragraph<'_> {
+        if let Some(prompt) = &self.prompt {
+            return Paragraph::new(prompt.text())
+                .block(Block::default().borders(Borders::TOP).border_style(Style::new().yellow()));
+        }
+
         if let Some(buffer) = self.current_buffer() {
             match &buffer.mode {
+                Mode::Insert if buffer.terminal.is_some() => {
+                    Paragraph::new("-- Terminal --").block(Block::default().borders(Borders::TOP))
+                }
                 Mode::Insert => {
                     Paragraph::new("-- Insert --").block(Block::default().borders(Borders::TOP))
                 }
+                Mode::Replace => {
+                    Paragraph::new("-- Replace --").block(Block::default().borders(Borders::TOP))
+                }
                 Mode::Normal => {
                     let status = match &mut self.message {
                         Some(value) => value.to_owned(),
                         None => "-- Normal --".to_string(),
                     };
 
-                    let status = Paragraph::new(format!("{}", status))
+                    let status = Paragraph::new(status.to_string())
                         .block(Block::default().borders(Borders::TOP).border_style(Style::new().blue()));
 
                     status
@@ -141,13 +265,51 @@ impl Editor {
                     Paragraph::new(format!(":{}", self.command.text))
                         .block(Block::default().borders(Borders::TOP))
                 }
-                Mode::Visual { .. } => todo!("impl visual mode for ui"),
+                Mode::Visual { block: false, .. } => {
+                    Paragraph::new("-- Visual Line --").block(Block::default().borders(Borders::TOP))
+                }
+                Mode::Visual { block: true, .. } => {
+                    Paragraph::new("-- Visual Block --").block(Block::default().borders(Borders::TOP))
+                }
             }
         } else {
             Paragraph::new("")
         }
     }
 
+    // text shown in the command view instead of the mode
+    pub fn status_text(&self) -> Option<String> {
+        match &self.prompt {
+            Some(prompt) => Some(prompt.text()),
+            None => self.message.clone(),
+        }
+    }
+
+    // name of the current buffer for the status line, with where it links to,
+    // why it couldn't be read and [+] when it has unsaved changes
+    pub fn file_display(&self) -> String {
+        let buffer = match self.current_buffer() {
+            Some(buffer) => buffer,
+            None => return String::new(),
+        };
+
+        let mut display = buffer.name();
+        if let Some(target) = &buffer.link_target {
+            display.push_str(&format!(" -> {}", display_name(target)));
+        }
+        if let Some(error) = &buffer.error {
+            display.push_str(&format!(" [{}]", error));
+        }
+        if buffer.is_modified() {
+            display.push_str(" [+]");
+        }
+        if buffer.terminal.as_ref().is_some_and(|t| t.has_exited()) {
+            display.push_str(" [exited]");
+        }
+
+        display
+    }
+
     // NOTE: event functions
     /*
      * idea:
@@ -155,12 +317,56 @@ impl Editor {
      * empty will pretty much be same as file, will need modifications for saving
      */
     pub fn key_press(&mut self, key: KeyEvent) {
+        if let Some(prompt) = self.prompt.take() {
+            self.prompt_key(prompt, key);
+            return;
+        }
+
+        if self.picker.is_some() {
+            self.picker_key(key);
+            return;
+        }
+
+        // long messages only stay up until the next key
+        if self.message.as_ref().is_some_and(|m| m.contains('\n')) {
+            self.set_message(None);
+        }
+        self.float = None;
+
+        let mode = self.current_buffer().map(|b| b.mode);
+        if mode != Some(Mode::Insert) {
+            self.completion = None;
+        }
+        if self.window_key {
+            self.window_key = false;
+            self.window_command(key);
+            return;
+        } else if key.code == KeyCode::Char('w') && key.modifiers == KeyModifiers::CONTROL && mode != Some(Mode::Insert) && mode != Some(Mode::Command) {
+            self.window_key = true;
+            return;
+        }
+
+        if mode != Some(Mode::Command) {
+            match self.focus {
+                Focus::Tree if self.show_tree => {
+                    self.tree_key_press(key);
+                    return;
+                },
+                Focus::Quickfix if self.show_quickfix => {
+                    self.quickfix_key_press(key);
+                    return;
+                },
+                _ => {},
+            }
+        }
+
         let buffer_type = self.current_buffer().map(|b| b.buffer_type);
         if let Some(buffer_type) = buffer_type {
             match buffer_type {
                 BufferType::Directory => self.directory_key_press(key),
-                BufferType::File => self.file_key_press(key),
-                BufferType::Empty => {} // FIX: add same to file, does nothing because saving not implemented
+                BufferType::File | BufferType::Empty => self.file_key_press(key),
+                BufferType::Terminal if mode == Some(Mode::Insert) => self.terminal_key_press(key),
+                BufferType::Terminal => self.file_key_press(key),
             }
         }
     }
@@ -174,26 +380,49 @@ impl Editor {
         };
 
         match mode {
+            Mode::Insert => self.insert_key(key),
             Mode::Command => self.command_line_key(key),
             Mode::Normal => {
                 // will have to create functions to handle operations
                 match key.code {
                     KeyCode::Enter => {
+                        if let Err(err) = self.check_listing() {
+                            self.set_message(Some(err));
+                            return;
+                        }
+
                         // open file/directory
                         let file_name = self.current_buffer_mut().map(|b| b.get_hover_file());
 
-                        if let Some(file_name) = file_name {
+                        if file_name.as_deref() == Some("../") {
+                            self.open_parent_dir();
+                        } else if let Some(file_name) = file_name {
                             self.send(format!("Opening {file_name}"));
-                            if let Some(b) = self.current_buffer_mut() {
-                                let _ = b.open(&file_name);
+                            let res = self.current_buffer_mut().map(|b| b.open(&file_name));
+
+                            match res {
+                                Some(Err(err)) => self.set_message(Some(format!("Can't open \"{}\": {}", file_name, err))),
+                                Some(Ok(_)) => self.show_open_error(),
+                                None => {},
                             }
                         }
                     }
                     KeyCode::Char(value) => {
                         if value == 'c' && key.modifiers == KeyModifiers::CONTROL {
                             let _ = self.clear_sender.send(true);
-                        } else {
+                        } else if value == 's' && key.modifiers == KeyModifiers::CONTROL {
+                            self.write_buffer(false, false);
+                        } else if value == 'r' && key.modifiers == KeyModifiers::CONTROL {
+                            let redone = self.current_buffer_mut().is_some_and(|b| b.redo());
+                            if !redone {
+                                self.set_message(Some(String::from("Already at newest change")));
+                            }
+                        } else if (value == '^' || value == '6') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                            self.alternate_buf();
+                        } else if key.modifiers.contains(KeyModifiers::CONTROL) {
                             let _ = self.motion_sender.send(value);
+                        } else {
+                            self.explorer_key(value);
                         }
                     }
                     _ => {}
@@ -203,6 +432,106 @@ impl Editor {
         }
     }
 
+    // keys go to the program in the terminal, ctrl-\ ctrl-n goes back to normal mode
+    fn terminal_key_press(&mut self, key: KeyEvent) {
+        let escape = std::mem::take(&mut self.terminal_escape);
+        let ctrl = |c| key.code == KeyCode::Char(c) && key.modifiers.contains(KeyModifiers::CONTROL);
+
+        let terminal = match self.current_buffer_mut().and_then(|b| b.terminal.as_mut()) {
+            Some(terminal) => terminal,
+            None => return,
+        };
+
+        if terminal.has_exited() {
+            let message = match terminal.exit_code() {
+                Some(code) => format!("Process exited with {}", code),
+                None => String::from("Process exited"),
+            };
+            self.leave_terminal();
+            self.set_message(Some(message));
+            return;
+        }
+
+        let res = if escape && ctrl('n') {
+            self.leave_terminal();
+            return;
+        } else if ctrl('\\') && !escape {
+            self.terminal_escape = true;
+            return;
+        } else if escape {
+            // it wasn't for us after all
+            terminal.write(&[0x1c]).and_then(|_| terminal.send_key(key))
+        } else {
+            terminal.send_key(key)
+        };
+
+        if let Err(err) = res {
+            self.set_message(Some(format!("Can't write to terminal: {}", err)));
+        }
+    }
+
+    // normal mode in a terminal moves through a copy of its output
+    fn leave_terminal(&mut self) {
+        if let Some(buffer) = self.current_buffer_mut() {
+            buffer.sync_terminal();
+        }
+        self.change_mode(Mode::Normal);
+    }
+
+    pub fn open_terminal(&mut self, cmd: Option<&str>) {
+        match Buffer::new_terminal(cmd, self.size) {
+            Ok(buffer) => {
+                self.add_buffer(buffer);
+                self.set_message(None);
+            },
+            Err(err) => self.set_message(Some(format!("Can't start terminal: {}", err))),
+        }
+    }
+
+    // keys that act on the entry under the cursor, the rest go to the motions
+    fn explorer_key(&mut self, value: char) {
+        let hovered = self.current_buffer().and_then(|b| b.hovered_entry());
+
+        // these read the directory again, which would throw away edits to the listing
+        if matches!(value, '-' | '.' | '%' | 'R' | 'C' | 'D' | 'M') {
+            if let Err(err) = self.check_listing() {
+                self.set_message(Some(err));
+                return;
+            }
+        }
+
+        match value {
+            '-' => self.open_parent_dir(),
+            '.' => {
+                let res = self.current_buffer_mut().map_or(Ok(()), |b| b.toggle_hidden());
+                if let Err(err) = res {
+                    self.set_message(Some(format!("Can't read directory: {}", err)));
+                }
+            },
+            '%' => self.prefill_command(String::from("create ")),
+            'R' | 'C' | 'D' | 'M' if hovered.is_none() => {
+                self.set_message(Some(String::from("No entry under the cursor")));
+            },
+            'R' => self.prefill_command(format!("rename {}", hovered.unwrap_or_default())),
+            'C' => self.prefill_command(format!("copy {}", hovered.unwrap_or_default())),
+            'D' => self.handle_command(Some(CommandKey::Delete)),
+            'M' => {
+                let mode = self.hovered_path()
+                    .and_then(|path| explorer::mode(&path))
+                    .map_or(String::new(), |mode| format!("{:o}", mode));
+                self.prefill_command(format!("chmod {}", mode));
+            },
+            _ => {
+                let _ = self.motion_sender.send(value);
+            },
+        }
+    }
+
+    fn prefill_command(&mut self, text: String) {
+        self.change_mode(Mode::Command);
+        self.command.text = text;
+    }
+
     fn file_key_press(&mut self, key: KeyEvent) {
         let mode = match self.current_buffer() {
             Some(b) => b.mode,
@@ -213,25 +542,98 @@ impl Editor {
             Mode::Command => self.command_line_key(key),
             Mode::Normal => {
                 // this will create issue moving forward
-                match key.code {
-                    KeyCode::Char(value) => {
-                        if value == 's' && key.modifiers == KeyModifiers::CONTROL {
-                            let update = self.save();
-                            self.set_message(Some(update.clone()));
-                        } else if value == 'c' && key.modifiers == KeyModifiers::CONTROL {
-                            let _ = self.clear_sender.send(true);
-                        } else {
-                            let _ = self.motion_sender.send(value);
+                if let KeyCode::Char(value) = key.code {
+                    if value == 's' && key.modifiers == KeyModifiers::CONTROL {
+                        self.write_buffer(false, false);
+                    } else if value == 'c' && key.modifiers == KeyModifiers::CONTROL {
+                        let _ = self.clear_sender.send(true);
+                    } else if value == 'r' && key.modifiers == KeyModifiers::CONTROL {
+                        let redone = self.current_buffer_mut().is_some_and(|b| b.redo());
+                        if !redone {
+                            self.set_message(Some(String::from("Already at newest change")));
                         }
+                    } else if (value == '^' || value == '6') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                        self.alternate_buf();
+                    } else if value == 'v' && key.modifiers == KeyModifiers::CONTROL {
+                        self.start_visual(true);
+                    } else if (value == 'a' || value == 'x') && key.modifiers == KeyModifiers::CONTROL {
+                        // the control char itself, so a count can go in front of it
+                        let _ = self.motion_sender.send(char::from(value as u8 & 0x1f));
+                    } else {
+                        let _ = self.motion_sender.send(value);
                     }
-                    _ => {}
                 }
             }
-            Mode::Visual { .. } => todo!("work on visual mode for file key press"),
+            Mode::Replace => {
+                let size = self.size;
+                if let Some(buffer) = self.current_buffer_mut() {
+                    buffer.replace_key(key, size);
+                    buffer.sync_snippet();
+                }
+            }
+            Mode::Visual { .. } => self.visual_key(key),
+        }
+    }
+
+    fn start_visual(&mut self, block: bool) {
+        if let Some(buffer) = self.current_buffer() {
+            let (start, col) = (buffer.ptr_y + buffer.cursor.current.1, buffer.cursor.current.0 + buffer.ptr_x);
+            self.change_mode(Mode::Visual { start, col, block });
+        }
+    }
+
+    // visual mode selects whole lines, or a block with ctrl-v. hjkl with a count move the end of
+    // it, gc comments the lines and ctrl-a or ctrl-x change the numbers in it, after g by one more
+    // on each line. escape, V or ctrl-v leaves it
+    fn visual_key(&mut self, key: KeyEvent) {
+        let g = std::mem::take(&mut self.visual_g);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('V') => self.change_mode(Mode::Normal),
+            KeyCode::Char('c' | 'v') if key.modifiers == KeyModifiers::CONTROL => self.change_mode(Mode::Normal),
+            KeyCode::Char(c @ ('a' | 'x')) if key.modifiers == KeyModifiers::CONTROL => {
+                self.increment_selection(if c == 'a' { 1 } else { -1 }, g);
+            },
+            KeyCode::Char('g') => self.visual_g = true,
+            KeyCode::Char('c') if g => {
+                let lines = self.current_buffer().and_then(|b| b.visual_lines());
+                self.change_mode(Mode::Normal);
+                if let Some((start, end)) = lines {
+                    self.comment_lines(start, end);
+                }
+            },
+            KeyCode::Char(c) if "hjkl".contains(c) || c.is_ascii_digit() => {
+                let _ = self.motion_sender.send(c);
+            },
+            KeyCode::Down => self.motion_func("j"),
+            KeyCode::Up => self.motion_func("k"),
+            KeyCode::Left => self.motion_func("h"),
+            KeyCode::Right => self.motion_func("l"),
+            _ => {},
+        }
+    }
+
+    fn increment_selection(&mut self, by: i64, steps: bool) {
+        let buffer = match self.current_buffer_mut() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let (start, end) = match buffer.visual_lines() {
+            Some(lines) => lines,
+            None => return,
+        };
+        let cols = buffer.visual_cols().unwrap_or((0, usize::MAX));
+
+        buffer.change_mode(Mode::Normal);
+        if !buffer.increment_lines(start, end, cols, by, steps) {
+            self.set_message(Some(String::from("No numbers selected")));
         }
     }
 
     pub fn command_line_key(&mut self, key: KeyEvent) {
+        if key.code != KeyCode::Tab {
+            self.command.reset_completion();
+        }
+
         match key.code {
             KeyCode::Char(value) => {
                 if value == 'c' && key.modifiers == KeyModifiers::CONTROL {
@@ -249,13 +651,34 @@ impl Editor {
                 self.change_mode(Mode::Normal);
             },
             KeyCode::Backspace => {
-                if self.command.text.len() > 0 {
+                if !self.command.text.is_empty() {
                     // TODO: add movable cursor
                     self.command.text.pop();
                 } else {
                     self.change_mode(Mode::Normal);
                 }
             },
+            KeyCode::Tab => {
+                let candidates = match self.command.name() {
+                    "b" | "buffer" | "bd" | "bdelete" | "bw" | "bwipeout" => {
+                        self.buffers.iter().map(|b| b.name()).collect()
+                    },
+                    "e" | "edit" | "w" | "write" | "sav" | "saveas" => {
+                        let arg = self.command.text.split_once(' ').map_or("", |(_, arg)| arg);
+                        let cwd = std::env::current_dir().unwrap_or_default();
+                        complete_path(&cwd, arg)
+                    },
+                    "create" | "touch" | "mkdir" | "rename" | "mv" | "copy" | "cp" => {
+                        let arg = self.command.text.split_once(' ').map_or("", |(_, arg)| arg);
+                        match self.explorer_dir() {
+                            Some(dir) => complete_path(&dir, arg),
+                            None => vec![],
+                        }
+                    },
+                    _ => vec![],
+                };
+                self.command.complete(&candidates);
+            },
             _ => {}
         }
     }
@@ -265,6 +688,11 @@ impl Editor {
     // TODO: figure out what this is for
     pub fn insert_key(&mut self, key: KeyEvent) {
         let size = self.size;
+        let completes = self.current_buffer().is_some_and(|b| b.buffer_type != BufferType::Directory);
+        if completes && (self.completion_key(key) || self.snippet_key(key)) {
+            return;
+        }
+
         if let Some(buffer) = self.current_buffer_mut() {
             match &buffer.buffer_type {
                 BufferType::Directory => {
@@ -272,14 +700,26 @@ impl Editor {
                 }
                 _ => {
                     buffer.insert_key_file(key, size);
+                    buffer.sync_snippet();
                 }
             }
         }
+
+        if completes {
+            self.after_insert_key(key);
+        }
     }
 
     pub fn paste(&mut self, text: String) {
         if let Some(buffer) = self.current_buffer_mut() {
-            let _ = &buffer.paste(text);
+            match &mut buffer.terminal {
+                Some(terminal) if buffer.mode == Mode::Insert => {
+                    let _ = terminal.paste(&text);
+                },
+                _ => {
+                    let _ = &buffer.paste(text);
+                },
+            }
         }
     }
 
@@ -288,7 +728,7 @@ impl Editor {
     // TODO: needs to recalculate the viewpoint
     pub fn go_to_line(&mut self, line_idx: usize) {
         // adjust for 0 indexing
-        let line_idx = line_idx.checked_sub(1).unwrap_or(0);
+        let line_idx = line_idx.saturating_sub(1);
 
         let has_line = self
             .current_buffer()
@@ -306,8 +746,8 @@ impl Editor {
                 if line_idx > size.1.into() {
                     // account for UI
                     buffer.cursor.current.1 = size.1.into();
-                    buffer.cursor.current.1.checked_sub(3).unwrap_or(0);
-                    buffer.ptr_y = line_idx.checked_sub(size.1.into()).unwrap_or(0) + 3;
+                    buffer.cursor.current.1 = buffer.cursor.current.1.saturating_sub(3);
+                    buffer.ptr_y = line_idx.saturating_sub(size.1.into()) + 3;
                 } else {
                     buffer.cursor.current.1 = line_idx;
                 }
@@ -325,15 +765,15 @@ impl Editor {
     pub fn parse(&mut self, motion: Vec<String>) -> Result<u32, &str> {
         self.send(format!("recv: {}", make_motion_string(&motion)));
 
-        if motion.len() == 0 {
+        if motion.is_empty() {
             unreachable!("motion len 0");
         } else if motion.len() == 1 {
-            match motion.get(0) {
+            match motion.first() {
                 Some(m) => {
                     self.motion_func(m);
                 },
                 None => {
-                    self.send(format!("Error in parse: m == None"));
+                    self.send("Error in parse: m == None".to_string());
                 },
             }
         } else {
@@ -351,9 +791,38 @@ impl Editor {
                 }
             }
 
+            // the filter command is typed in after the motion
+            if f == "!" {
+                self.filter_motion(&m, number);
+                return Ok(0);
+            }
+            if matches!(f.as_str(), ">" | "<" | "=") {
+                self.indent_motion(&f, &m, number);
+                return Ok(0);
+            }
+            if f == "gc" {
+                self.comment_motion(&m, number);
+                return Ok(0);
+            }
+            // with a count % goes that far through the file instead
+            if f.is_empty() && m == "%" {
+                self.go_to_percent(number);
+                return Ok(0);
+            }
+            if let Some(case) = Case::from_operator(&f) {
+                self.case_motion(case, &f, &m, number);
+                return Ok(0);
+            }
+            if self.counted_func(&f, &m, number) {
+                return Ok(0);
+            }
+
             for _ in 0..number {
-                self.motion_func(&m);
-                self.action_func(&f, &String::from(" "));
+                // the last key is what these act on, not a motion
+                if !takes_char(&f) {
+                    self.motion_func(&m);
+                }
+                self.action_func(&f, &m);
             }
         }
 
@@ -362,62 +831,281 @@ impl Editor {
     }
 
     pub fn handle_command(&mut self, command: Option<CommandKey>){
-        match command {
-            Some(command) => {
-                match command {
-                    CommandKey::Save => {
-                        let update = self.save();
-                        self.set_message(Some(update))
-                    },
-                    CommandKey::Quit => self.close_buffer(),
-                    CommandKey::Line(number) => {
-                        self.go_to_line(number);
-                    },
-                    CommandKey::SaveAndQuit => {
-                        self.save();
-                        self.should_quit = true;
-                    },
-                    CommandKey::History => todo!(),
-                    CommandKey::Logger => {
-                        // TODO: finish this up
-                        let output = match &self.logger {
-                            Some(socket) => {
-                                let addr = socket.local_addr().unwrap().to_string();
-                                format!("Binded to {}", addr)
-                            },
-                            None => "Not Connected".to_string()
-                        };
-                        self.set_message(Some(output))
-                    },
-                    CommandKey::Send(message) => {
-                        self.send(message);
-                    },
-                    CommandKey::NextBuf => {
-                        self.next_buf();
-                        self.send(String::from(format!("buf: {}", self.buf_ptr)));
-                    },
-                    CommandKey::PrevBuf => {
-                        self.prev_buf();
-                        self.send(String::from(format!("buf: {}", self.buf_ptr)));
-                    },
-                    CommandKey::NewBuf => {
-                        self.send(String::from("New buffer"));
-                        self.new_buffer(&".".to_string());
-                    },
-                    CommandKey::BufCount => {
-                        // sent message to count of opened buffers
-                        let message = String::from(format!("{} open buffers", self.buffers.len()));
-                        self.set_message(Some(message))
+        if let Some(command) = command {
+            match command {
+                CommandKey::Save { path: None, force } => self.write_buffer(force, false),
+                CommandKey::Save { path: Some(path), force } => {
+                    // a buffer without a name takes the one it is written to
+                    match self.current_buffer() {
+                        Some(b) if b.file.is_none() => self.format_buffer(AfterFormat::SaveAs { path, force }),
+                        Some(_) => self.format_buffer(AfterFormat::WriteTo { path, force }),
+                        None => self.set_message(Some(String::from("No buffer to save"))),
+                    }
+                },
+                CommandKey::SaveAs { path, force } => {
+                    if path.is_empty() {
+                        self.set_message(Some(String::from("Argument required")));
+                        return;
+                    }
+
+                    match self.current_buffer() {
+                        Some(_) => self.format_buffer(AfterFormat::SaveAs { path, force }),
+                        None => self.set_message(Some(String::from("No buffer to save"))),
+                    }
+                },
+                CommandKey::WriteCommand(cmd) => {
+                    let update = self.write_command(&cmd);
+                    self.set_message(Some(update.unwrap_or_else(|err| err)))
+                },
+                CommandKey::Quit { force } => {
+                    if let Err(err) = self.quit(force) {
+                        self.set_message(Some(err))
+                    }
+                },
+                CommandKey::QuitAll { force } => {
+                    if let Err(err) = self.quit_all(force) {
+                        self.set_message(Some(err))
+                    }
+                },
+                CommandKey::Line(number) => {
+                    self.go_to_line(number);
+                },
+                CommandKey::SaveAndQuit { force } => self.write_buffer(force, true),
+                CommandKey::Edit { path: None, force } => {
+                    if let Err(err) = self.reload_buffer(force) {
+                        self.set_message(Some(err))
+                    }
+                },
+                CommandKey::Edit { path: Some(path), .. } => self.edit(&path),
+                CommandKey::EditNew => {
+                    match Buffer::new_empty(self.size) {
+                        Ok(buf) => self.add_buffer(buf),
+                        Err(_) => self.send("Can't make buffer".to_string()),
+                    }
+                },
+                CommandKey::History => todo!(),
+                CommandKey::Logger => {
+                    // TODO: finish this up
+                    let output = match &self.logger {
+                        Some(socket) => {
+                            let addr = socket.local_addr().unwrap().to_string();
+                            format!("Binded to {}", addr)
+                        },
+                        None => "Not Connected".to_string()
+                    };
+                    self.set_message(Some(output))
+                },
+                CommandKey::Send(message) => {
+                    self.send(message);
+                },
+                CommandKey::NextBuf => {
+                    self.next_buf();
+                    self.send(format!("buf: {}", self.buf_ptr));
+                },
+                CommandKey::PrevBuf => {
+                    self.prev_buf();
+                    self.send(format!("buf: {}", self.buf_ptr));
+                },
+                CommandKey::NewBuf => {
+                    self.send(String::from("New buffer"));
+                    self.new_buffer(&".".to_string());
+                },
+                CommandKey::BufCount => {
+                    // sent message to count of opened buffers
+                    let message = format!("{} open buffers", self.buffers.len());
+                    self.set_message(Some(message))
+                },
+                CommandKey::ListBuffers => {
+                    let list = self.list_buffers();
+                    self.set_message(Some(list))
+                },
+                CommandKey::Buffer(target) => {
+                    if target.is_empty() {
+                        return;
+                    }
+
+                    match self.find_buffer(&target) {
+                        Ok(idx) => self.switch_to(idx),
+                        Err(err) => self.set_message(Some(err)),
+                    }
+                },
+                CommandKey::DeleteBuf { target, force } => {
+                    let idx = match target {
+                        Some(target) => self.find_buffer(&target),
+                        None => Ok(self.buf_ptr),
+                    };
+
+                    let res = idx.and_then(|idx| self.delete_buffer(idx, force));
+                    if let Err(err) = res {
+                        self.set_message(Some(err))
+                    }
+                },
+                CommandKey::Create(name) => {
+                    let res = self.explorer_create(&name);
+                    self.set_message(Some(res.unwrap_or_else(|err| err)))
+                },
+                // the symbol under the cursor when not in a directory listing
+                CommandKey::Rename(name) => {
+                    let res = self.explorer_rename(&name);
+                    self.set_message(Some(res.unwrap_or_else(|err| err)))
+                },
+                CommandKey::Hover => self.lsp_hover(),
+                CommandKey::Definition => self.lsp_definition(),
+                CommandKey::References => self.lsp_references(),
+                CommandKey::LspRename(name) => self.lsp_rename(&name),
+                CommandKey::CodeAction(arg) => self.lsp_code_action(&arg),
+                CommandKey::Diagnostics => self.diagnostics_list(),
+                CommandKey::LspInfo => {
+                    let lines: Vec<String> = self.servers.iter()
+                        .map(|s| format!("{} {}{}", s.command, display_name(&s.root), if s.is_ready() { "" } else { " (starting)" }))
+                        .collect();
+                    let message = if lines.is_empty() { String::from("No language servers") } else { lines.join("\n") };
+                    self.set_message(Some(message));
+                },
+                CommandKey::Copy(name) => {
+                    let res = self.explorer_copy(&name);
+                    self.set_message(Some(res.unwrap_or_else(|err| err)))
+                },
+                CommandKey::Delete => {
+                    match self.explorer_target() {
+                        Ok((path, name)) => {
+                            let message = format!("Move \"{}\" to the trash?", name);
+                            self.prompt = Some(Prompt::new(message, PromptAction::Trash { path }));
+                        },
+                        Err(err) => self.set_message(Some(err)),
+                    }
+                },
+                CommandKey::Chmod(mode) => {
+                    let res = self.explorer_chmod(&mode);
+                    self.set_message(Some(res.unwrap_or_else(|err| err)))
+                },
+                CommandKey::Find(query) => {
+                    let root = self.project_root();
+                    self.list_files(root, query);
+                },
+                CommandKey::FindBuffer => self.open_picker(PickerKind::Buffers),
+                CommandKey::FindHistory => self.open_picker(PickerKind::History),
+                CommandKey::Marks => self.open_picker(PickerKind::Marks),
+                CommandKey::Grep { pattern, path } => self.grep(&pattern, path.as_deref()),
+                CommandKey::QuickfixNext => self.quickfix_next(true),
+                CommandKey::QuickfixPrev => self.quickfix_next(false),
+                CommandKey::QuickfixOpen => {
+                    if self.quickfix.is_some() {
+                        self.show_quickfix = true;
+                        self.focus = Focus::Quickfix;
+                    } else {
+                        self.set_message(Some(String::from("No quickfix list")));
+                    }
+                },
+                CommandKey::QuickfixClose => {
+                    self.show_quickfix = false;
+                    if self.focus == Focus::Quickfix {
+                        self.focus = Focus::Buffer;
+                    }
+                },
+                CommandKey::QuickfixDo(cmd) => self.quickfix_do(&cmd),
+                CommandKey::Terminal(cmd) if cmd.is_empty() => self.open_terminal(None),
+                CommandKey::Terminal(cmd) => self.open_terminal(Some(&cmd)),
+                CommandKey::Shell(cmd) => {
+                    let message = match self.shell_command(&cmd, None) {
+                        Ok(text) if text.is_empty() => String::from("Command finished without output"),
+                        Ok(text) => text,
+                        Err(err) => err,
+                    };
+                    self.set_message(Some(message));
+                },
+                CommandKey::ReadCommand { range, cmd } => {
+                    let res = self.read_line(range)
+                        .and_then(|line| Ok((line, self.shell_command(&cmd, None)?)));
+                    match res {
+                        Ok((line, text)) => self.read_text(line, &text),
+                        Err(err) => self.set_message(Some(err)),
+                    }
+                },
+                CommandKey::ReadFile { range, path } => self.read_file(range, &path),
+                CommandKey::Filter { range, cmd } => {
+                    if let Err(err) = self.filter(range, &cmd) {
+                        self.set_message(Some(err));
+                    }
+                },
+                CommandKey::Make(args) => self.make(&args),
+                CommandKey::MakePrg(cmd) if cmd.is_empty() => self.set_message(Some(format!("makeprg={}", self.make_command))),
+                CommandKey::MakePrg(cmd) => self.make_command = cmd,
+                CommandKey::Format => match self.current_buffer() {
+                    Some(buffer) if buffer.options.formatter.is_none() => {
+                        self.set_message(Some(String::from("No formatter for this file")));
                     },
-                }
-            },
-            None => {}
+                    Some(_) => self.format_buffer(AfterFormat::Nothing),
+                    None => {},
+                },
+                CommandKey::FormatPrg(cmd) => {
+                    let buffer = match self.current_buffer_mut() {
+                        Some(buffer) => buffer,
+                        None => return,
+                    };
+                    match cmd.as_str() {
+                        "" => {
+                            let current = buffer.options.formatter.clone().unwrap_or(String::from("off"));
+                            self.set_message(Some(format!("formatprg={}", current)));
+                        },
+                        "off" => buffer.options.set_formatter(None),
+                        _ => buffer.options.set_formatter(Some(cmd)),
+                    }
+                },
+                CommandKey::FileType(name) => {
+                    let buffer = match self.current_buffer_mut() {
+                        Some(buffer) => buffer,
+                        None => return,
+                    };
+                    if name.is_empty() {
+                        let current = buffer.file_type.map_or("none", |ft| ft.name);
+                        self.set_message(Some(format!("filetype={}", current)));
+                    } else if name == "none" {
+                        buffer.set_file_type(None);
+                    } else {
+                        match filetype::by_name(&name) {
+                            Some(file_type) => buffer.set_file_type(Some(file_type)),
+                            None => self.set_message(Some(format!("Unknown filetype: {}", name))),
+                        }
+                    }
+                    self.attach_buffer(self.buf_ptr);
+                },
+                CommandKey::Set(args) => {
+                    let res = match self.current_buffer_mut() {
+                        Some(buffer) => buffer.options.set(&args),
+                        None => Ok(None),
+                    };
+                    match res {
+                        Ok(shown) => self.set_message(shown),
+                        Err(err) => self.set_message(Some(err)),
+                    }
+                },
+                CommandKey::ErrorFormat(format) if format.is_empty() => self.set_message(Some(format!("errorformat={}", self.error_format))),
+                CommandKey::ErrorFormat(format) => match ErrorFormat::new(&format) {
+                    Ok(_) => self.error_format = format,
+                    Err(err) => self.set_message(Some(err)),
+                },
+                CommandKey::Substitute { pattern, replacement, global } => {
+                    let res = match Regex::new(&pattern) {
+                        Ok(regex) => Ok(self.current_buffer_mut().is_some_and(|b| b.substitute(&regex, &replacement, global))),
+                        Err(err) => Err(format!("Invalid pattern: {}", err)),
+                    };
+
+                    match res {
+                        Ok(true) => {},
+                        Ok(false) => self.set_message(Some(format!("Pattern not found: {}", pattern))),
+                        Err(err) => self.set_message(Some(err)),
+                    }
+                },
+                CommandKey::ToggleTree => self.toggle_tree(),
+                CommandKey::RevealInTree => self.reveal_in_tree(),
+            }
         }
     }
 
-    pub fn motion_func(&mut self, key: &String) {
+    pub fn motion_func(&mut self, key: &str) {
         let size = self.size;
-        match key.as_str() {
+        match key {
             ":" => self.change_mode(Mode::Command),
             "j" => {
                 if let Some(buffer) = self.current_buffer_mut() {
@@ -440,10 +1128,25 @@ impl Editor {
                 }
             }
             "i" => self.change_mode(Mode::Insert),
-            "v" => {
-                // TODO: grab current x and y coord, do I even need x and y or can i use byte
-                self.change_mode(Mode::Visual { start: 0, end: 0 })
+            "p" => self.put(),
+            "K" => self.lsp_hover(),
+            "R" if self.current_buffer().is_some_and(|b| b.terminal.is_none()) => self.change_mode(Mode::Replace),
+            "~" | "J" | CTRL_A | CTRL_X => {
+                self.counted_func("", key, 1);
+            }
+            "%" => {
+                let jumped = self.current_buffer_mut().is_some_and(|b| b.jump_to_match());
+                if !jumped {
+                    self.set_message(Some(String::from("No match")));
+                }
+            }
+            "u" => {
+                let undone = self.current_buffer_mut().is_some_and(|b| b.undo());
+                if !undone {
+                    self.set_message(Some(String::from("Already at oldest change")));
+                }
             }
+            "V" => self.start_visual(false),
             "a" => {
                 if let Some(buffer) = self.current_buffer_mut() {
                     buffer.change_mode(Mode::Insert);
@@ -497,20 +1200,83 @@ impl Editor {
                     buffer.move_end_of_line();
                 }
             }
+            "<leader>ff" => {
+                let root = self.project_root();
+                self.open_picker(PickerKind::Files { root });
+            }
+            "<leader>fb" => self.open_picker(PickerKind::Buffers),
+            "<leader>fh" => self.open_picker(PickerKind::History),
+            "<leader>fm" => self.open_picker(PickerKind::Marks),
             _ => {}
         }
     }
 
     // TODO: this will be used for actions, will need action_args
-    pub fn action_func(&mut self, key: &String, _args: &String){
-        match key.as_str() {
+    pub fn action_func(&mut self, key: &str, args: &str){
+        match key {
+            "d" if args == "d" => {
+                if let Some(buffer) = self.current_buffer_mut() {
+                    buffer.delete_line();
+                }
+            }
             "d" => {}
+            "y" if args == "y" => {
+                let line = self.current_buffer().map(|b| b.current_line_text());
+                if let Some(line) = line {
+                    self.register = Some(line);
+                }
+            }
+            "m" => {
+                let name = args.chars().next().filter(|c| c.is_ascii_alphabetic());
+                if let (Some(buffer), Some(name)) = (self.current_buffer_mut(), name) {
+                    buffer.set_mark(name);
+                }
+            }
+            "'" | "`" => {
+                let exact = key == "`";
+                let name = args.chars().next().unwrap_or(' ');
+                let jumped = self.current_buffer_mut().is_some_and(|b| b.jump_to_mark(name, exact));
+                if !jumped {
+                    self.set_message(Some(String::from("Mark not set")));
+                }
+            }
+            "g" if args == "d" => self.lsp_definition(),
+            "]" | "[" if args == "d" => self.jump_to_diagnostic(key == "]"),
+            "[" if args == "(" || args == "{" => self.jump_to_enclosing(args),
+            "]" if args == ")" || args == "}" => self.jump_to_enclosing(args),
             "s" => {}
             "f" => {}
             _ => {}
         }
     }
 
+    // puts the yanked line below the cursor, a terminal gets it typed in without the newline
+    fn put(&mut self) {
+        let text = match &self.register {
+            Some(text) => text.clone(),
+            None => {
+                self.set_message(Some(String::from("Nothing yanked")));
+                return;
+            },
+        };
+
+        let res = match self.current_buffer_mut() {
+            Some(buffer) if buffer.terminal.is_some() => {
+                buffer.change_mode(Mode::Insert);
+                buffer.terminal.as_mut().map_or(Ok(()), |t| t.paste(text.trim_end_matches('\n')))
+            },
+            Some(buffer) => {
+                buffer.put_below(&text);
+                Ok(())
+            },
+            None => Ok(()),
+        };
+
+        if let Err(err) = res {
+            self.set_message(Some(format!("Can't write to terminal: {}", err)));
+        }
+    }
+
     // TODO: add function for modifying what the buffer contains
 
     pub fn new_buffer(&mut self, path: &String){
@@ -521,39 +1287,255 @@ impl Editor {
 
         match buf {
             Ok(buf) => {
-                self.buffers.push(buf);
-                self.next_buf();
+                self.add_buffer(buf);
+                self.show_open_error();
             },
-            Err(_) => {
+            Err(err) => {
                 // log out error to tcp logger
                 self.send("Can't make buffer".to_string());
+                self.set_message(Some(format!("Can't open \"{}\": {}", path, err)));
             },
         }
     }
 
-    // TODO: figure how this should be handled if this is only buffer
-    // reset buf_ptr, ++/--
-    pub fn close_buffer(&mut self){
-        if self.buffers.len() == 1 {
-            self.should_quit = true;
-            return;
+    fn show_open_error(&mut self) {
+        let error = self.current_buffer().and_then(|b| b.error.clone());
+        if let Some(error) = error {
+            self.set_message(Some(format!("Can't read directory: {}", error)));
         }
+    }
 
-        self.buffers.remove(self.buf_ptr);
+    pub fn add_buffer(&mut self, mut buf: Buffer) {
+        buf.id = self.next_buf_id;
+        self.next_buf_id += 1;
 
-        if self.buf_ptr > 0 {
-            self.buf_ptr -= 1;
+        self.buffers.push(buf);
+        self.switch_to(self.buffers.len() - 1);
+        self.update_signs();
+        self.attach_buffer(self.buffers.len() - 1);
+        if let Some(path) = self.buffers[self.buffers.len() - 1].file.clone() {
+            self.show_lsp_diagnostics(&path);
         }
-
     }
 
-    pub fn next_buf(&mut self) {
-        // make cycling buffer wheel
-        let max = self.buffers.len();
+    // switches to the buffer for path, opening it when it isn't open yet,
+    // paths that don't exist become new files
+    pub fn edit(&mut self, path: &str) {
+        let full = match std::env::current_dir() {
+            Ok(cwd) => cwd.join(path),
+            Err(_) => PathBuf::from(path),
+        };
+
+        let open = self.buffers.iter().position(|b| {
+            let buffer_path = match b.buffer_type {
+                BufferType::Directory => b.parent_dir.as_ref(),
+                _ => b.file.as_ref(),
+            };
+            buffer_path == Some(&full)
+        });
+
+        match open {
+            Some(idx) => self.switch_to(idx),
+            None => self.new_buffer(&path.to_string()),
+        }
+
+        if let Some(buffer) = self.current_buffer() {
+            if buffer.buffer_type == BufferType::Empty {
+                let message = format!("\"{}\" [New]", buffer.name());
+                self.set_message(Some(message));
+            }
+        }
+    }
+
+    // reads the current buffer's file again, dropping changes only when forced
+    pub fn reload_buffer(&mut self, force: bool) -> Result<(), String> {
+        let buffer = match self.current_buffer_mut() {
+            Some(buffer) => buffer,
+            None => return Ok(()),
+        };
+
+        if buffer.is_modified() && !force {
+            return Err(unsaved_message(buffer));
+        }
+
+        let path = match buffer.buffer_type {
+            BufferType::Directory => buffer.parent_dir.clone(),
+            _ => buffer.file.clone(),
+        };
+
+        match path {
+            Some(path) => buffer
+                .open(&path.to_string_lossy().to_string())
+                .map_err(|err| format!("Can't open \"{}\": {}", buffer.name(), err.kind())),
+            None => Err(String::from("No file name")),
+        }
+    }
+
+    // closes the current buffer, refusing to throw away changes unless forced
+    pub fn quit(&mut self, force: bool) -> Result<(), String> {
+        if let Some(buffer) = self.current_buffer() {
+            if buffer.is_modified() && !force {
+                return Err(unsaved_message(buffer));
+            }
+        }
+
+        self.close_buffer();
+        Ok(())
+    }
+
+    // quits the editor, jumping to the first modified buffer if there is one
+    pub fn quit_all(&mut self, force: bool) -> Result<(), String> {
+        if !force {
+            if let Some(idx) = self.buffers.iter().position(|b| b.is_modified()) {
+                let err = unsaved_message(&self.buffers[idx]);
+                self.switch_to(idx);
+                return Err(err);
+            }
+        }
+
+        self.should_quit = true;
+        Ok(())
+    }
+
+    // TODO: figure how this should be handled if this is only buffer
+    // reset buf_ptr, ++/--
+    pub fn close_buffer(&mut self){
+        if self.buffers.len() == 1 {
+            self.should_quit = true;
+            return;
+        }
+
+        let _ = self.delete_buffer(self.buf_ptr, true);
+    }
+
+    // removes a buffer from the list, modified buffers are only dropped when forced
+    pub fn delete_buffer(&mut self, idx: usize, force: bool) -> Result<(), String> {
+        let buffer = match self.buffers.get(idx) {
+            Some(buffer) => buffer,
+            None => return Err(String::from("No such buffer")),
+        };
+
+        if buffer.is_modified() && !force {
+            return Err(unsaved_message(buffer));
+        }
+
+        let id = buffer.id;
+        if self.alt_buf == Some(id) {
+            self.alt_buf = None;
+        }
+        self.detach_buffer(id);
+
+        if self.buffers.len() == 1 {
+            // keep a buffer around to land in
+            self.buffers.clear();
+            self.buf_ptr = 0;
+            self.new_buffer(&".".to_string());
+            return Ok(());
+        }
+
+        let alt = self.alt_buf
+            .and_then(|id| self.buffers.iter().position(|b| b.id == id));
+
+        self.buffers.remove(idx);
+
+        if idx < self.buf_ptr {
+            self.buf_ptr -= 1;
+        } else if idx == self.buf_ptr {
+            // land on the alternate buffer if there is one
+            self.buf_ptr = match alt {
+                Some(alt) if alt > idx => alt - 1,
+                Some(alt) => alt,
+                None => self.buf_ptr.saturating_sub(1),
+            };
+            self.alt_buf = None;
+        }
+
+        Ok(())
+    }
+
+    // looks up a buffer by its number or by part of its name
+    pub fn find_buffer(&self, target: &str) -> Result<usize, String> {
+        if target == "#" {
+            return self.alt_buf
+                .and_then(|id| self.buffers.iter().position(|b| b.id == id))
+                .ok_or(String::from("No alternate file"));
+        }
+
+        if let Ok(id) = target.parse::<usize>() {
+            return self.buffers
+                .iter()
+                .position(|b| b.id == id)
+                .ok_or(format!("Buffer {} does not exist", id));
+        }
+
+        // an exact name wins over partial matches
+        if let Some(idx) = self.buffers.iter().position(|b| b.name() == target) {
+            return Ok(idx);
+        }
+
+        let matches: Vec<usize> = self.buffers
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.name().contains(target))
+            .map(|(i, _)| i)
+            .collect();
+
+        match matches.len() {
+            0 => Err(format!("No matching buffer for {}", target)),
+            1 => Ok(matches[0]),
+            _ => Err(format!("More than one match for {}", target)),
+        }
+    }
+
+    // one line per buffer: number, flags, name and cursor line
+    pub fn list_buffers(&self) -> String {
+        let alt = self.alt_buf;
+
+        self.buffers
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                let current = if i == self.buf_ptr {
+                    "%a"
+                } else if Some(b.id) == alt {
+                    "# "
+                } else {
+                    "h "
+                };
+                let modified = if b.is_modified() { "+" } else { " " };
+
+                format!("{:>3} {} {} \"{}\" line {}", b.id, current, modified, b.name(), b.cursor_line())
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // changes the current buffer, remembering the one being left as the alternate
+    pub fn switch_to(&mut self, idx: usize) {
+        if idx >= self.buffers.len() {
+            return;
+        }
+
+        if idx != self.buf_ptr {
+            self.alt_buf = self.current_buffer().map(|b| b.id);
+        }
+        self.buf_ptr = idx;
+    }
+
+    pub fn alternate_buf(&mut self) {
+        match self.find_buffer("#") {
+            Ok(idx) => self.switch_to(idx),
+            Err(err) => self.set_message(Some(err)),
+        }
+    }
+
+    pub fn next_buf(&mut self) {
+        // make cycling buffer wheel
+        let max = self.buffers.len();
 
         let current = self.buf_ptr.checked_add(1).unwrap_or(0) % max;
 
-        self.buf_ptr = current;
+        self.switch_to(current);
     }
 
     pub fn prev_buf(&mut self) {
@@ -561,10 +1543,10 @@ impl Editor {
 
         match next {
             Some(value) => {
-                self.buf_ptr = value;
+                self.switch_to(value);
             },
             None => {
-                self.buf_ptr = self.buffers.len().checked_sub(1).unwrap();
+                self.switch_to(self.buffers.len().checked_sub(1).unwrap());
             }
         }
     }
@@ -575,34 +1557,1213 @@ impl Editor {
             self.send(format!("Set Message {}", message.clone()));
         } else {
             self.message = None;
-            self.send(format!("Message was cleared"));
+            self.send("Message was cleared".to_string());
         }
     }
 
     // NOTE: saving functions
 
-    // TODO: make this safer by reading permissions
-    pub fn save(&mut self) -> String {
-        // FIX: too much extra memory
+    // writes the current buffer, asking before creating directories for new files
+    pub fn write_buffer(&mut self, force: bool, quit: bool) {
+        if self.explorer_dir().is_some() {
+            self.write_listing(quit);
+            return;
+        }
+
+        let missing = self.current_buffer().and_then(|b| b.missing_parent());
+
+        if let Some(dir) = missing {
+            let message = format!("Create directory \"{}\"?", display_name(&dir));
+            self.prompt = Some(Prompt::new(message, PromptAction::CreateDirs { force, quit }));
+            return;
+        }
+
+        match self.current_buffer() {
+            Some(_) => self.format_buffer(AfterFormat::Write { force, quit }),
+            None => self.set_message(Some(String::from("No buffer to save"))),
+        }
+    }
+
+    // runs the current buffer's formatter in the background and then does what it
+    // was run for, a buffer without a formatter goes straight on
+    fn format_buffer(&mut self, then: AfterFormat) {
+        let buffer = match self.current_buffer() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+
+        // the formatter gets the path the text is written to
+        let path = match &then {
+            AfterFormat::WriteTo { path, .. } | AfterFormat::SaveAs { path, .. } => Some(buffer.resolve_path(path)),
+            _ => buffer.file.clone(),
+        };
+        let (cmd, text, path) = match (buffer.formatter(), path) {
+            (Some((cmd, text)), Some(path)) if buffer.buffer_type != BufferType::Terminal => (cmd, text, path),
+            _ => {
+                self.after_format(self.buf_ptr, then, false);
+                return;
+            },
+        };
+
+        let (id, revision) = (buffer.id, buffer.revision);
+        let sender = self.task_sender.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = format::format(&cmd, &text, &path);
+            let _ = sender.send(TaskOutput::Format { id, revision, then, result });
+        });
+    }
+
+    // carries on once the buffer at idx is formatted, changed is whether the formatter changed it
+    fn after_format(&mut self, idx: usize, then: AfterFormat, changed: bool) {
+        let buffer = &mut self.buffers[idx];
+        let id = buffer.id;
+
+        let (force, quit) = match then {
+            AfterFormat::Nothing => {
+                let message = if changed { "Formatted" } else { "Already formatted" };
+                self.set_message(Some(String::from(message)));
+                return;
+            },
+            AfterFormat::WriteTo { path, force } => {
+                let update = buffer.write_to(&path, force);
+                self.set_message(Some(update.unwrap_or_else(|err| err)));
+                return;
+            },
+            AfterFormat::SaveAs { path, force } => {
+                let update = buffer.save_as(&path, force);
+                self.set_message(Some(update.unwrap_or_else(|err| err)));
+                self.attach_buffer(idx);
+                return;
+            },
+            AfterFormat::Write { force, quit } => (force, quit),
+        };
+
+        match buffer.write(force) {
+            Ok(update) => {
+                self.set_message(Some(update));
+                self.lsp_saved(id);
+
+                // new and changed files show up in the tree
+                if let Some(tree) = &mut self.tree {
+                    let _ = tree.refresh();
+                }
+
+                if quit {
+                    if let Err(err) = self.quit_all(false) {
+                        self.set_message(Some(err))
+                    }
+                }
+            },
+            Err(err) => self.set_message(Some(err)),
+        }
+    }
+
+    // shows what the edits to a directory listing will do before doing it
+    fn write_listing(&mut self, quit: bool) {
+        let changes = match self.current_buffer() {
+            Some(buffer) => buffer.listing_changes(),
+            None => return,
+        };
+
+        if changes.is_empty() {
+            // edits that cancel out still leave the listing marked as modified
+            self.refresh_listing(None);
+            self.set_message(Some(String::from("No changes")));
+            if quit {
+                if let Err(err) = self.quit_all(false) {
+                    self.set_message(Some(err))
+                }
+            }
+            return;
+        }
+
+        let mut message: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+        message.push(format!("Apply {} change{}?", changes.len(), if changes.len() == 1 { "" } else { "s" }));
+        self.prompt = Some(Prompt::new(message.join("\n"), PromptAction::ApplyListing { changes, quit }));
+    }
+
+    // carries out the changes in order, stopping at the first one that fails
+    fn apply_listing(&mut self, changes: &[Change]) -> Result<String, String> {
+        let dir = self.explorer_dir().ok_or(String::from("Only works in a directory"))?;
+
+        let trash = self.trash.clone().ok_or(String::from("No trash directory"))?;
+
+        for (i, change) in changes.iter().enumerate() {
+            if let Err(err) = explorer::apply(&dir, change, &trash) {
+                self.refresh_listing(None);
+                return Err(format!("Can't {}: {} ({} of {} changes applied)", change, err, i, changes.len()));
+            }
+        }
+
+        // keep the cursor on what was renamed or created last
+        let select = changes.iter().rev().find_map(|c| match c {
+            Change::Rename { to, .. } => Some(to.clone()),
+            Change::Create(name) => Some(name.clone()),
+            Change::Delete(_) => None,
+        });
+        let select = select.as_deref().map(|name| name.split('/').next().unwrap_or(name));
+        self.refresh_listing(select);
+
+        Ok(format!("Applied {} change{}", changes.len(), if changes.len() == 1 { "" } else { "s" }))
+    }
+
+    // NOTE: prompt functions
+
+    fn prompt_key(&mut self, prompt: Prompt, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => self.confirm_prompt(prompt.action),
+            _ => self.set_message(Some(String::from("Cancelled"))),
+        }
+    }
+
+    fn confirm_prompt(&mut self, action: PromptAction) {
+        match action {
+            PromptAction::CreateDirs { force, quit } => {
+                let res = self.current_buffer()
+                    .map_or(Ok(()), |b| b.create_parent_dirs());
+
+                match res {
+                    Ok(_) => self.write_buffer(force, quit),
+                    Err(err) => self.set_message(Some(err)),
+                }
+            },
+            PromptAction::Trash { path } => {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let res = match &self.trash {
+                    Some(trash) => explorer::trash_into(&path, trash)
+                        .map(|_| format!("Moved \"{}\" to the trash", name))
+                        .map_err(|err| format!("Can't delete \"{}\": {}", name, err)),
+                    None => Err(String::from("No trash directory")),
+                };
+
+                self.refresh_listing(None);
+                self.set_message(Some(res.unwrap_or_else(|err| err)))
+            },
+            PromptAction::ApplyListing { changes, quit } => {
+                match self.apply_listing(&changes) {
+                    Ok(update) => {
+                        self.set_message(Some(update));
+                        if quit {
+                            if let Err(err) = self.quit_all(false) {
+                                self.set_message(Some(err))
+                            }
+                        }
+                    },
+                    Err(err) => self.set_message(Some(err)),
+                }
+            },
+        }
+    }
+
+    // NOTE: explorer functions
+
+    // directory shown by the current buffer, if it is a directory buffer
+    fn explorer_dir(&self) -> Option<PathBuf> {
         self.current_buffer()
-            .map_or(String::from("No buffer to save"), |b| b.save())
+            .filter(|b| b.buffer_type == BufferType::Directory)
+            .and_then(|b| b.parent_dir.clone())
+    }
+
+    fn hovered_path(&self) -> Option<PathBuf> {
+        let name = self.current_buffer()?.hovered_entry()?;
+        Some(self.explorer_dir()?.join(name))
+    }
+
+    // directory of the current buffer when its listing hasn't been edited
+    fn check_listing(&self) -> Result<PathBuf, String> {
+        let dir = self.explorer_dir().ok_or(String::from("Only works in a directory"))?;
+
+        match self.current_buffer() {
+            Some(b) if b.is_modified() => Err(String::from("Listing has unsaved edits (:w to apply, :e! to discard)")),
+            _ => Ok(dir),
+        }
+    }
+
+    // path and name of the entry under the cursor
+    fn explorer_target(&self) -> Result<(PathBuf, String), String> {
+        self.check_listing()?;
+
+        let name = self.current_buffer()
+            .and_then(|b| b.hovered_entry())
+            .ok_or(String::from("No entry under the cursor"))?;
+        let path = self.hovered_path().ok_or(String::from("No entry under the cursor"))?;
+
+        Ok((path, name))
+    }
+
+    fn refresh_listing(&mut self, select: Option<&str>) {
+        let res = self.current_buffer_mut().map_or(Ok(()), |b| b.refresh_listing(select));
+        if let Err(err) = res {
+            self.set_message(Some(format!("Can't read directory: {}", err)));
+        }
+    }
+
+    // opens the parent of the directory, with the cursor on the one we came from
+    fn open_parent_dir(&mut self) {
+        let dir = match self.explorer_dir() {
+            Some(dir) => dir,
+            None => return,
+        };
+        let name = dir.file_name().map(|n| n.to_string_lossy().to_string());
+
+        let res = self.current_buffer_mut().map(|b| b.open(&"../".to_string()));
+        match res {
+            Some(Err(err)) => self.set_message(Some(format!("Can't open \"..\": {}", err))),
+            Some(Ok(_)) => {
+                self.show_open_error();
+                if let (Some(buffer), Some(name)) = (self.current_buffer_mut(), name) {
+                    buffer.select_entry(&name);
+                }
+            },
+            None => {},
+        }
+    }
+
+    fn explorer_create(&mut self, name: &str) -> Result<String, String> {
+        let dir = self.check_listing()?;
+        if name.is_empty() {
+            return Err(String::from("Argument required"));
+        }
+
+        explorer::create(&dir, name).map_err(|err| format!("Can't create \"{}\": {}", name, err))?;
+
+        // select what was made in this directory, even when it is nested
+        let first = name.split('/').next().unwrap_or(name);
+        self.refresh_listing(Some(first));
+        Ok(format!("Created \"{}\"", name))
+    }
+
+    fn explorer_rename(&mut self, new_name: &str) -> Result<String, String> {
+        let (path, name) = self.explorer_target()?;
+        if new_name.is_empty() {
+            return Err(String::from("Argument required"));
+        }
+
+        let dir = self.explorer_dir().unwrap_or_default();
+        explorer::rename(&path, &dir.join(new_name))
+            .map_err(|err| format!("Can't rename \"{}\": {}", name, err))?;
+
+        // buffers of the old file now point at the new one
+        let new_path = dir.join(new_name);
+        for buffer in self.buffers.iter_mut() {
+            if buffer.file.as_deref() == Some(path.as_path()) {
+                buffer.file = Some(new_path.clone());
+            }
+        }
+
+        let first = new_name.split('/').next().unwrap_or(new_name);
+        self.refresh_listing(Some(first));
+        Ok(format!("Renamed \"{}\" to \"{}\"", name, new_name))
+    }
+
+    fn explorer_copy(&mut self, new_name: &str) -> Result<String, String> {
+        let (path, name) = self.explorer_target()?;
+        if new_name.is_empty() {
+            return Err(String::from("Argument required"));
+        }
+
+        let dir = self.explorer_dir().unwrap_or_default();
+        explorer::copy(&path, &dir.join(new_name))
+            .map_err(|err| format!("Can't copy \"{}\": {}", name, err))?;
+
+        let first = new_name.split('/').next().unwrap_or(new_name);
+        self.refresh_listing(Some(first));
+        Ok(format!("Copied \"{}\" to \"{}\"", name, new_name))
+    }
+
+    fn explorer_chmod(&mut self, mode: &str) -> Result<String, String> {
+        let (path, name) = self.explorer_target()?;
+        let bits = u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|bits| *bits <= 0o7777)
+            .ok_or(format!("Invalid mode: {}", mode))?;
+
+        explorer::chmod(&path, bits).map_err(|err| format!("Can't chmod \"{}\": {}", name, err))?;
+
+        self.refresh_listing(Some(&name));
+        Ok(format!("Changed mode of \"{}\" to {:o}", name, bits))
+    }
+
+    // runs a shell command in the buffer's directory, returning only what it wrote to stdout,
+    // a failure comes back with what it wrote to stderr
+    pub fn shell_command(&self, cmd: &str, input: Option<&str>) -> Result<String, String> {
+        if cmd.is_empty() {
+            return Err(String::from("Argument required"));
+        }
+
+        let dir = self.current_buffer().and_then(|b| b.parent_dir.clone());
+        let output = shell::run(cmd, input, dir.as_deref())
+            .map_err(|err| format!("Can't run {}: {}", cmd, err.kind()))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stderr = stderr.trim_end_matches('\n');
+        match output.status.code() {
+            Some(0) => Ok(stdout),
+            Some(code) => Err(format!("{}\nshell returned {}", stderr, code).trim_start().to_string()),
+            None => Err(format!("{}\nshell was terminated", stderr).trim_start().to_string()),
+        }
+    }
+
+    // puts text under the cursor line
+    fn read_text(&mut self, line: Option<usize>, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        if let Some(buffer) = self.current_buffer_mut() {
+            if let Some(line) = line {
+                buffer.jump_to(line, 0);
+            }
+            buffer.put_below(text);
+        }
+    }
+
+    // the line a read goes below, None for the cursor's
+    fn read_line(&self, range: Option<Range>) -> Result<Option<usize>, String> {
+        match range {
+            Some(range) => Ok(Some(self.resolve_range(range)?.1)),
+            None => Ok(None),
+        }
+    }
+
+    fn read_file(&mut self, range: Option<Range>, name: &str) {
+        if name.is_empty() {
+            self.set_message(Some(String::from("Argument required")));
+            return;
+        }
+        let line = match self.read_line(range) {
+            Ok(line) => line,
+            Err(err) => {
+                self.set_message(Some(err));
+                return;
+            },
+        };
+
+        let path = match self.current_buffer() {
+            Some(buffer) => buffer.resolve_path(name),
+            None => return,
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(text) => self.read_text(line, &text),
+            Err(err) => self.set_message(Some(format!("Can't read \"{}\": {}", display_name(&path), err.kind()))),
+        }
+    }
+
+    // lines a range covers in the current buffer, from 0
+    fn resolve_range(&self, range: Range) -> Result<(usize, usize), String> {
+        let buffer = self.current_buffer().ok_or(String::from("No buffer"))?;
+        let last = buffer.line_count() - 1;
+
+        let resolve = |address: Address| -> Result<usize, String> {
+            let line = match address.line {
+                LineRef::Number(n) => n.saturating_sub(1),
+                LineRef::Current => buffer.ptr_y + buffer.cursor.current.1,
+                LineRef::Last => last,
+                LineRef::Mark(c) => buffer.marks.get(&c).ok_or(String::from("Mark not set"))?.0,
+            };
+
+            line.checked_add_signed(address.offset)
+                .filter(|line| *line <= last)
+                .ok_or(String::from("Invalid range"))
+        };
+
+        let (start, end) = match range {
+            Range::Whole => (0, last),
+            Range::Lines(start, end) => (resolve(start)?, resolve(end)?),
+        };
+
+        Ok((start.min(end), start.max(end)))
+    }
+
+    // pipes lines through a shell command and puts what it printed in their place,
+    // the lines are left alone when it fails
+    pub fn filter(&mut self, range: Range, cmd: &str) -> Result<(), String> {
+        let (start, end) = self.resolve_range(range)?;
+        let input = self.current_buffer().map(|b| b.lines_text(start, end)).unwrap_or_default();
+        let output = self.shell_command(cmd, Some(&input))?;
+
+        if let Some(buffer) = self.current_buffer_mut() {
+            buffer.replace_lines(start, end, &output);
+        }
+
+        let count = end - start + 1;
+        self.set_message(Some(format!("{} {} filtered", count, if count == 1 { "line" } else { "lines" })));
+        Ok(())
+    }
+
+    // the line the cursor is on and the one a line operator's motion goes to, the operator
+    // typed twice is count lines from the cursor
+    fn motion_lines(&mut self, operator: &str, motion: &str, count: usize) -> Option<(usize, usize)> {
+        let (start, col) = match self.current_buffer() {
+            Some(buffer) => (buffer.ptr_y + buffer.cursor.current.1, buffer.cursor.current.0),
+            None => return None,
+        };
+
+        if motion == operator {
+            let last = self.current_buffer().map_or(0, |b| b.line_count() - 1);
+            return Some((start, (start + count - 1).min(last)));
+        }
+        if motion == "ip" || motion == "ap" {
+            return self.current_buffer().map(|b| b.paragraph(start, motion == "ap"));
+        }
+
+        for _ in 0..count {
+            self.motion_func(motion);
+        }
+        let end = self.current_buffer().map_or(start, |b| b.ptr_y + b.cursor.current.1);
+        if let Some(buffer) = self.current_buffer_mut() {
+            buffer.jump_to(start, col);
+        }
+        Some((start, end))
+    }
+
+    // !{motion} starts a filter command for the lines from the cursor to where the motion goes,
+    // !! is the line the cursor is on
+    fn filter_motion(&mut self, motion: &str, count: usize) {
+        let (start, end) = match self.motion_lines("!", motion, count) {
+            Some(lines) => lines,
+            None => return,
+        };
+
+        let range = match end.cmp(&start) {
+            std::cmp::Ordering::Equal => String::from("."),
+            std::cmp::Ordering::Greater => format!(".,.+{}", end - start),
+            std::cmp::Ordering::Less => format!(".-{},.", start - end),
+        };
+        self.prefill_command(format!("{}!", range));
+    }
+
+    // >{motion} and <{motion} shift the lines by a shift width, ={motion} indents them for
+    // the code they are in
+    fn indent_motion(&mut self, operator: &str, motion: &str, count: usize) {
+        let (start, end) = match self.motion_lines(operator, motion, count) {
+            Some((start, end)) => (start.min(end), start.max(end)),
+            None => return,
+        };
+        let buffer = match self.current_buffer_mut() {
+            Some(buffer) if buffer.buffer_type != BufferType::Directory => buffer,
+            _ => return,
+        };
+
+        match operator {
+            ">" => buffer.shift_lines(start, end, 1),
+            "<" => buffer.shift_lines(start, end, -1),
+            _ => buffer.reindent_lines(start, end),
+        }
+
+        let count = end - start + 1;
+        if count > 2 {
+            let verb = match operator {
+                ">" => "indented",
+                "<" => "outdented",
+                _ => "reindented",
+            };
+            self.set_message(Some(format!("{} lines {}", count, verb)));
+        }
+    }
+
+    // commands that take the count themselves instead of being repeated, as one undo step
+    fn counted_func(&mut self, key: &str, args: &str, count: usize) -> bool {
+        let buffer = match self.current_buffer_mut() {
+            Some(buffer) if buffer.buffer_type != BufferType::Directory => buffer,
+            _ => return false,
+        };
+
+        match (key, args) {
+            ("", "~") => buffer.toggle_case(count),
+            ("", "J") | ("g", "J") => {
+                buffer.join_lines(count, key.is_empty());
+            },
+            ("", CTRL_A) => {
+                buffer.increment(count as i64);
+            },
+            ("", CTRL_X) => {
+                buffer.increment(-(count as i64));
+            },
+            ("r", _) => {
+                if let Some(c) = args.chars().next() {
+                    buffer.replace_chars(c, count);
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    // g~{motion}, gu{motion} and gU{motion} change the case from the cursor to where the motion
+    // goes, whole lines for the operator doubled, like guu, for ip and ap and for j and k, the
+    // cursor ends up at the start of what changed
+    fn case_motion(&mut self, case: Case, operator: &str, motion: &str, count: usize) {
+        let doubled = operator.ends_with(motion);
+        if doubled || matches!(motion, "ip" | "ap" | "j" | "k") {
+            let motion = if doubled { operator } else { motion };
+            let (start, end) = match self.motion_lines(operator, motion, count) {
+                Some((start, end)) => (start.min(end), start.max(end)),
+                None => return,
+            };
+            if let Some(buffer) = self.current_buffer_mut() {
+                let rope = &buffer.lines.rope;
+                let (from, to) = (rope.line_to_char(start), rope.line_to_char(end + 1));
+                buffer.change_case(from, to, case);
+            }
+            return;
+        }
+
+        let start = match self.current_buffer() {
+            Some(buffer) => buffer.cursor_char(),
+            None => return,
+        };
+        for _ in 0..count {
+            self.motion_func(motion);
+        }
+        let end = self.current_buffer().map_or(start, |b| b.cursor_char());
+
+        // e and $ take in the char they end on
+        let (from, to) = (start.min(end), start.max(end) + usize::from(matches!(motion, "e" | "$")));
+        if let Some(buffer) = self.current_buffer_mut() {
+            buffer.change_case(from, to, case);
+            buffer.move_to_char(from);
+        }
+    }
+
+    // gc{motion} comments the lines from the cursor to where the motion goes in or out, gcc
+    // is count lines from the cursor
+    fn comment_motion(&mut self, motion: &str, count: usize) {
+        let motion = if motion == "c" { "gc" } else { motion };
+        let (start, end) = match self.motion_lines("gc", motion, count) {
+            Some((start, end)) => (start.min(end), start.max(end)),
+            None => return,
+        };
+        self.comment_lines(start, end);
+    }
+
+    // comments lines start through end, or takes the comments off
+    fn comment_lines(&mut self, start: usize, end: usize) {
+        let res = match self.current_buffer_mut() {
+            Some(buffer) if buffer.buffer_type != BufferType::Directory => buffer.toggle_comment(start, end),
+            _ => return,
+        };
+
+        let count = end - start + 1;
+        match res {
+            Ok(commented) if count > 2 => {
+                let verb = if commented { "commented" } else { "uncommented" };
+                self.set_message(Some(format!("{} lines {}", count, verb)));
+            },
+            Ok(_) => {},
+            Err(err) => self.set_message(Some(err)),
+        }
+    }
+
+    // pipes the buffer into a shell command and returns what it printed
+    pub fn write_command(&self, cmd: &str) -> Result<String, String> {
+        let buffer = match self.current_buffer() {
+            Some(buffer) => buffer,
+            None => return Err(String::from("No buffer to write")),
+        };
+
+        let input = buffer.lines.rope.to_string();
+        let output = shell::run(cmd, Some(&input), buffer.parent_dir.as_deref())
+            .map_err(|err| format!("Can't run {}: {}", cmd, err.kind()))?;
+
+        let text = shell::output_text(&output);
+        match output.status.code() {
+            Some(0) => Ok(text),
+            Some(code) => Err(format!("{}\nshell returned {}", text, code).trim_start().to_string()),
+            None => Err(format!("{}\nshell was terminated", text).trim_start().to_string()),
+        }
     }
 
     // NOTE: functions for logging
 
     pub fn send(&mut self, message: String) {
-        match &mut self.logger {
-            Some(stream) => {
-                let _ = stream.write(message.as_bytes());
+        if let Some(stream) = &mut self.logger {
+            let _ = stream.write(message.as_bytes());
+        }
+    }
+
+    // NOTE: mouse functions
+    pub fn handle_mouse(&mut self, mut mouse_event: MouseEvent) {
+        if mouse_event.column < self.tree_width {
+            if let (Some(tree), MouseEventKind::Up(MouseButton::Left)) = (&mut self.tree, mouse_event.kind) {
+                let height = usize::from(self.size.1);
+                tree.select(tree.offset + usize::from(mouse_event.row), height);
+                self.focus = Focus::Tree;
+            }
+            return;
+        }
+
+        // the quickfix list sits under the buffer
+        if self.show_quickfix && mouse_event.row >= self.size.1 {
+            return;
+        }
+
+        mouse_event.column -= self.tree_width;
+        if let MouseEventKind::Up(MouseButton::Left) = mouse_event.kind {
+            self.focus = Focus::Buffer;
+        }
+
+        if let Some(buffer) = self.current_buffer_mut() {
+            buffer.mouse_handler(&mouse_event);
+        }
+    }
+
+    // NOTE: picker functions
+
+    // where the files picker lists files from, the current buffer's directory
+    fn project_root(&self) -> PathBuf {
+        self.current_buffer()
+            .and_then(|b| b.parent_dir.clone())
+            .or(std::env::current_dir().ok())
+            .unwrap_or_default()
+    }
+
+    // files are listed in the background, the picker opens when they are all found
+    pub fn open_picker(&mut self, kind: PickerKind) {
+        let (kind, items) = match kind {
+            PickerKind::Files { root } => {
+                self.list_files(root, String::new());
+                return;
+            },
+            PickerKind::Buffers => {
+                let items = self.buffers.iter()
+                    .map(|b| Item { label: b.name(), id: b.id })
+                    .collect();
+                (kind, items)
+            },
+            PickerKind::History => {
+                // most recent first, each command once
+                let mut items: Vec<Item> = vec![];
+                for command in self.command.history.iter().rev() {
+                    if !command.is_empty() && !items.iter().any(|i| &i.label == command) {
+                        items.push(Item { label: command.clone(), id: items.len() });
+                    }
+                }
+                (kind, items)
+            },
+            PickerKind::Marks => {
+                let items = match self.current_buffer() {
+                    Some(buffer) => buffer.marks.iter()
+                        .map(|(name, (line, col))| {
+                            let text = buffer.lines.rope.get_line(*line)
+                                .map(|l| l.to_string().trim().to_string())
+                                .unwrap_or_default();
+                            let label = format!("{} {:>4}:{:<3} {}", name, line + 1, col + 1, text);
+                            Item { label, id: *name as usize }
+                        })
+                        .collect(),
+                    None => vec![],
+                };
+                (kind, items)
+            },
+        };
+
+        self.show_picker(kind, items, "");
+    }
+
+    fn show_picker(&mut self, kind: PickerKind, items: Vec<Item>, query: &str) {
+        if items.is_empty() {
+            self.set_message(Some(format!("Nothing to pick from in {}", kind.title().to_lowercase())));
+            return;
+        }
+
+        let mut picker = Picker::new(kind, items);
+        if !query.is_empty() {
+            picker.set_query(query);
+        }
+        self.picker = Some(picker);
+        self.update_preview();
+    }
+
+    // walks root off of the main loop, the picker opens with query typed in
+    fn list_files(&mut self, root: PathBuf, query: String) {
+        let sender = self.task_sender.clone();
+        tokio::task::spawn_blocking(move || {
+            let files = project_files(&root);
+            let _ = sender.send(TaskOutput::Files { root, query, files });
+        });
+    }
+
+    fn picker_key(&mut self, key: KeyEvent) {
+        let picker = match &mut self.picker {
+            Some(picker) => picker,
+            None => return,
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let rows = picker.rows;
+
+        match key.code {
+            KeyCode::Esc => self.picker = None,
+            KeyCode::Char('c') if ctrl => self.picker = None,
+            KeyCode::Enter => self.accept_picker(),
+            KeyCode::Down => picker.move_down(rows),
+            KeyCode::Char('n') | KeyCode::Char('j') if ctrl => picker.move_down(rows),
+            KeyCode::Up => picker.move_up(),
+            KeyCode::Char('p') | KeyCode::Char('k') if ctrl => picker.move_up(),
+            KeyCode::Backspace => picker.pop(),
+            KeyCode::Char(c) if !ctrl => picker.push(c),
+            _ => {},
+        }
+
+        self.update_preview();
+    }
+
+    fn accept_picker(&mut self) {
+        let picker = match self.picker.take() {
+            Some(picker) => picker,
+            None => return,
+        };
+        let item = match picker.selected_item() {
+            Some(item) => item.clone(),
+            None => return,
+        };
+
+        match picker.kind {
+            PickerKind::Files { root } => self.edit(&root.join(&item.label).to_string_lossy()),
+            PickerKind::Buffers => {
+                if let Some(idx) = self.buffers.iter().position(|b| b.id == item.id) {
+                    self.switch_to(idx);
+                }
+            },
+            PickerKind::History => {
+                self.command.text = item.label;
+                let command = self.command.confirm();
+                self.handle_command(command);
+            },
+            PickerKind::Marks => {
+                let name = char::from_u32(item.id as u32).unwrap_or(' ');
+                if let Some(buffer) = self.current_buffer_mut() {
+                    buffer.jump_to_mark(name, true);
+                }
+            },
+        }
+    }
+
+    // lines shown beside the matches for whatever is selected
+    fn update_preview(&mut self) {
+        const PREVIEW_LINES: usize = 200;
+
+        let (kind, selected, item) = match &self.picker {
+            Some(p) => match p.selected_item() {
+                Some(item) => (p.kind.clone(), p.matches[p.selected].item, item.clone()),
+                None => {
+                    self.picker.as_mut().unwrap().preview = None;
+                    return;
+                },
+            },
+            None => return,
+        };
+
+        if self.picker.as_ref().and_then(|p| p.preview.as_ref()).is_some_and(|(s, _)| *s == selected) {
+            return;
+        }
+
+        let buffer_lines = |buffer: &Buffer, from: usize| -> Vec<String> {
+            buffer.lines.rope.lines()
+                .skip(from)
+                .take(PREVIEW_LINES)
+                .map(|l| l.to_string().trim_end_matches('\n').to_string())
+                .collect()
+        };
+
+        let lines = match kind {
+            PickerKind::Files { root } => preview_file(&root.join(&item.label), PREVIEW_LINES),
+            PickerKind::Buffers => self.buffers.iter()
+                .find(|b| b.id == item.id)
+                .map_or(vec![], |b| buffer_lines(b, 0)),
+            PickerKind::History => vec![],
+            PickerKind::Marks => {
+                let name = char::from_u32(item.id as u32).unwrap_or(' ');
+                match self.current_buffer() {
+                    Some(b) => {
+                        let line = b.marks.get(&name).map_or(0, |m| m.0);
+                        buffer_lines(b, line.saturating_sub(2))
+                    },
+                    None => vec![],
+                }
+            },
+        };
+
+        if let Some(picker) = &mut self.picker {
+            picker.preview = Some((selected, lines));
+        }
+    }
+
+    // NOTE: quickfix functions
+
+    // searches in the background, the list is filled in when it is done
+    pub fn grep(&mut self, pattern: &str, path: Option<&str>) {
+        let regex = match Regex::new(pattern) {
+            Ok(regex) => regex,
+            Err(err) => {
+                self.set_message(Some(format!("Invalid pattern: {}", err)));
+                return;
+            },
+        };
+
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let path = cwd.join(path.unwrap_or("."));
+        let title = format!("/{}/", pattern);
+        let sender = self.task_sender.clone();
+
+        self.set_message(Some(format!("Searching for {}", title)));
+        tokio::task::spawn_blocking(move || {
+            let result = if path.exists() {
+                Ok(grep::grep(&regex, &path, &cwd))
+            } else {
+                Err(format!("Can't search \"{}\": not found", display_name(&path)))
+            };
+            let _ = sender.send(TaskOutput::Quickfix { title, result });
+        });
+    }
+
+    // runs the build command in the background, its errors fill the quickfix list
+    pub fn make(&mut self, args: &str) {
+        let format = match ErrorFormat::new(&self.error_format) {
+            Ok(format) => format,
+            Err(err) => {
+                self.set_message(Some(err));
+                return;
+            },
+        };
+
+        let command = format!("{} {}", self.make_command, args).trim().to_string();
+        let cwd = std::env::current_dir().ok();
+        let sender = self.task_sender.clone();
+
+        self.set_message(Some(format!("Running {}", command)));
+        tokio::task::spawn_blocking(move || {
+            let task = match shell::run(&command, None, cwd.as_deref()) {
+                Ok(output) => {
+                    let text = shell::output_text(&output);
+                    let entries = make::parse_output(&command, &format, &text);
+                    TaskOutput::Make { success: output.status.success(), result: Ok((entries, text)), command }
+                },
+                Err(err) => {
+                    let result = Err(format!("Can't run \"{}\": {}", command, err));
+                    TaskOutput::Make { command, success: false, result }
+                },
+            };
+            let _ = sender.send(task);
+        });
+    }
+
+    pub fn set_quickfix(&mut self, kind: QuickfixKind, title: String, entries: Vec<Entry>) {
+        let rows = self.quickfix.as_ref().map_or(10, |q| q.rows);
+        let mut list = Quickfix::new(kind, title, entries);
+        list.rows = rows;
+
+        self.quickfix = Some(list);
+        self.show_quickfix = true;
+        self.update_signs();
+    }
+
+    // gives open buffers the compiler messages the quickfix list has for them,
+    // a list of plain matches, like one from :grep, leaves them alone and so does
+    // :diagnostics, whose entries the buffers already have from the servers
+    fn update_signs(&mut self) {
+        let list = match &self.quickfix {
+            Some(list) if list.kind == QuickfixKind::Diagnostics => return,
+            Some(list) if list.entries.is_empty() || list.entries.iter().any(|e| e.severity.is_some()) => list,
+            _ => return,
+        };
+
+        let cwd = std::env::current_dir().unwrap_or_default();
+        for buffer in self.buffers.iter_mut() {
+            let path = match &buffer.file {
+                Some(path) => path,
+                None => continue,
+            };
+
+            let rope = &buffer.lines.rope;
+            let found = list.entries.iter()
+                .filter(|e| e.severity.is_some() && e.line < rope.len_lines())
+                .filter(|e| normalize(&cwd.join(&e.path)) == *path)
+                .map(|entry| {
+                    // the word the column points at, or just the char
+                    let start = rope.line_to_char(entry.line) + entry.col.min(rope.line(entry.line).len_chars());
+                    let word = rope.chars_at(start).take_while(|c| c.is_alphanumeric() || *c == '_').count();
+                    diagnostic::Diagnostic {
+                        start,
+                        end: start + word,
+                        severity: entry.severity.unwrap_or(Severity::Info),
+                        message: entry.text.clone(),
+                        source: None,
+                    }
+                })
+                .collect();
+            buffer.set_diagnostics("quickfix", found);
+        }
+    }
+
+    // moves the ranges of every buffer's diagnostics over the edits made since the last call
+    pub fn sync_diagnostics(&mut self) {
+        for buffer in self.buffers.iter_mut() {
+            buffer.sync_diagnostics();
+        }
+    }
+
+    fn jump_to_enclosing(&mut self, bracket: &str) {
+        let bracket = bracket.chars().next().unwrap_or('(');
+        let jumped = self.current_buffer_mut().is_some_and(|b| b.jump_to_enclosing(bracket));
+        if !jumped {
+            self.set_message(Some(format!("No unmatched {}", bracket)));
+        }
+    }
+
+    // {count}% goes to the line that far through the file
+    fn go_to_percent(&mut self, percent: usize) {
+        if percent > 100 {
+            return;
+        }
+        if let Some(buffer) = self.current_buffer_mut() {
+            let line = (percent * buffer.line_count()).div_ceil(100);
+            buffer.jump_to(line.saturating_sub(1), 0);
+        }
+    }
+
+    // ]d and [d, the full messages go in a float by the cursor
+    pub fn jump_to_diagnostic(&mut self, forward: bool) {
+        let message = self.current_buffer_mut().and_then(|b| b.jump_to_diagnostic(forward));
+        match message {
+            Some(message) => self.float = Some(message),
+            None => self.set_message(Some(String::from("No diagnostics"))),
+        }
+    }
+
+    // height of the quickfix list on a screen this tall, with its title line
+    pub fn quickfix_height(&mut self, height: u16) -> u16 {
+        match &mut self.quickfix {
+            Some(list) if self.show_quickfix => {
+                let rows = (list.entries.len() as u16).clamp(1, 10).min(height / 3);
+                list.rows = usize::from(rows.max(1));
+                rows + 1
+            },
+            _ => 0,
+        }
+    }
+
+    // opens the file of the current entry with the cursor on it
+    fn jump_to_quickfix(&mut self) {
+        let entry = match self.quickfix.as_ref().and_then(|q| q.current_entry()) {
+            Some(entry) => entry.clone(),
+            None => return,
+        };
+
+        self.edit(&entry.path.to_string_lossy());
+        if let Some(buffer) = self.current_buffer_mut() {
+            buffer.jump_to(entry.line, entry.col);
+        }
+
+        let (current, len) = self.quickfix.as_ref().map_or((0, 0), |q| (q.current, q.entries.len()));
+        self.set_message(Some(format!("({} of {}) {}", current + 1, len, entry.text)));
+    }
+
+    pub fn quickfix_next(&mut self, forward: bool) {
+        let moved = match &mut self.quickfix {
+            Some(list) if forward => list.next_entry().is_some(),
+            Some(list) => list.prev_entry().is_some(),
+            None => {
+                self.set_message(Some(String::from("No quickfix list")));
+                return;
+            },
+        };
+
+        if moved {
+            self.jump_to_quickfix();
+        } else {
+            self.set_message(Some(String::from("No more items")));
+        }
+    }
+
+    // runs a command at every entry of the list
+    pub fn quickfix_do(&mut self, cmd: &str) {
+        let len = match &self.quickfix {
+            Some(list) => list.entries.len(),
+            None => {
+                self.set_message(Some(String::from("No quickfix list")));
+                return;
+            },
+        };
+
+        if Command::parse(cmd).is_none() {
+            self.set_message(Some(format!("Not a command: {}", cmd)));
+            return;
+        }
+
+        for idx in 0..len {
+            if let Some(list) = &mut self.quickfix {
+                list.select(idx);
+            }
+            self.jump_to_quickfix();
+            self.handle_command(Command::parse(cmd));
+        }
+
+        self.set_message(Some(format!("Ran \"{}\" on {} entries", cmd, len)));
+    }
+
+    fn quickfix_key_press(&mut self, key: KeyEvent) {
+        let list = match &mut self.quickfix {
+            Some(list) => list,
+            None => return,
+        };
+
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => list.select(list.current + 1),
+            KeyCode::Char('k') | KeyCode::Up => list.select(list.current.saturating_sub(1)),
+            KeyCode::Char('g') => list.select(0),
+            KeyCode::Char('G') => list.select(usize::MAX),
+            KeyCode::Enter => {
+                self.jump_to_quickfix();
+                self.focus = Focus::Buffer;
+            },
+            KeyCode::Char('q') => {
+                self.show_quickfix = false;
+                self.focus = Focus::Buffer;
+            },
+            KeyCode::Char(':') => self.change_mode(Mode::Command),
+            _ => {},
+        }
+    }
+
+    // NOTE: file tree functions
+
+    // width of the file tree on a screen this wide, 0 while it is hidden
+    pub fn resize_tree(&mut self, width: u16) -> u16 {
+        self.tree_width = if self.show_tree && self.tree.is_some() {
+            (width / 4).clamp(16, 32).min(width / 2)
+        } else {
+            0
+        };
+        self.tree_width
+    }
+
+    // the tree starts at the working directory the first time it is shown
+    fn open_tree(&mut self) -> Result<(), String> {
+        match &mut self.tree {
+            Some(tree) => tree.refresh(),
+            None => {
+                let cwd = std::env::current_dir().unwrap_or_default();
+                FileTree::new(&cwd).map(|tree| self.tree = Some(tree))
+            },
+        }
+        .map_err(|err| format!("Can't read directory: {}", err))?;
+
+        self.show_tree = true;
+        self.focus = Focus::Tree;
+        Ok(())
+    }
+
+    pub fn toggle_tree(&mut self) {
+        if self.show_tree {
+            self.show_tree = false;
+            self.focus = Focus::Buffer;
+        } else if let Err(err) = self.open_tree() {
+            self.set_message(Some(err));
+        }
+    }
+
+    // shows the current buffer's file in the tree
+    pub fn reveal_in_tree(&mut self) {
+        let path = match self.current_buffer() {
+            Some(b) if b.buffer_type == BufferType::Directory => b.parent_dir.clone(),
+            Some(b) => b.file.clone(),
+            None => None,
+        };
+
+        if let Err(err) = self.open_tree() {
+            self.set_message(Some(err));
+            return;
+        }
+
+        let height = usize::from(self.size.1);
+        let found = match (&mut self.tree, path) {
+            (Some(tree), Some(path)) => tree.reveal(&path, height).unwrap_or(false),
+            _ => false,
+        };
+
+        if !found {
+            self.set_message(Some(String::from("File isn't in the tree")));
+        }
+    }
+
+    // ctrl-w followed by a direction moves between the tree, the buffer and the quickfix list
+    fn window_command(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('h') | KeyCode::Left if self.show_tree => self.focus = Focus::Tree,
+            KeyCode::Char('l') | KeyCode::Right => self.focus = Focus::Buffer,
+            KeyCode::Char('j') | KeyCode::Down if self.show_quickfix => self.focus = Focus::Quickfix,
+            KeyCode::Char('k') | KeyCode::Up => self.focus = Focus::Buffer,
+            KeyCode::Char('w') | KeyCode::Char('p') => {
+                // cycle through whatever is on screen
+                let order = [
+                    (Focus::Tree, self.show_tree),
+                    (Focus::Buffer, true),
+                    (Focus::Quickfix, self.show_quickfix),
+                ];
+                let start = order.iter().position(|(f, _)| *f == self.focus).unwrap_or(1);
+                self.focus = (1..=order.len())
+                    .map(|i| order[(start + i) % order.len()])
+                    .find(|(_, shown)| *shown)
+                    .map_or(Focus::Buffer, |(f, _)| f);
+            },
+            _ => {},
+        }
+    }
+
+    fn tree_key_press(&mut self, key: KeyEvent) {
+        let height = usize::from(self.size.1);
+        let tree = match &mut self.tree {
+            Some(tree) => tree,
+            None => return,
+        };
+
+        let res = match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                tree.move_down(height);
+                Ok(())
             },
-            None => {}
-        }
-    }
+            KeyCode::Char('k') | KeyCode::Up => {
+                tree.move_up(height);
+                Ok(())
+            },
+            KeyCode::Char('g') => {
+                tree.select(0, height);
+                Ok(())
+            },
+            KeyCode::Char('G') => {
+                tree.select(usize::MAX, height);
+                Ok(())
+            },
+            KeyCode::Char('h') | KeyCode::Left => tree.collapse(height),
+            KeyCode::Char('.') => tree.toggle_hidden(),
+            KeyCode::Char('R') => tree.refresh(),
+            KeyCode::Char('l') | KeyCode::Char('o') | KeyCode::Enter | KeyCode::Right => {
+                match tree.toggle() {
+                    Ok(Some(path)) => {
+                        self.edit(&path.to_string_lossy());
+                        self.focus = Focus::Buffer;
+                        Ok(())
+                    },
+                    Ok(None) => Ok(()),
+                    Err(err) => Err(err),
+                }
+            },
+            KeyCode::Char('q') => {
+                self.toggle_tree();
+                Ok(())
+            },
+            KeyCode::Char(':') => {
+                self.change_mode(Mode::Command);
+                Ok(())
+            },
+            _ => Ok(()),
+        };
 
-    // NOTE: mouse functions
-    pub fn handle_mouse(&mut self, mouse_event: MouseEvent) {
-        if let Some(buffer) = self.current_buffer_mut() {
-            buffer.mouse_handler(&mouse_event);
+        if let Err(err) = res {
+            self.set_message(Some(format!("Can't read directory: {}", err)));
         }
     }
 
@@ -619,6 +2780,12 @@ impl Editor {
     // TODO: Swap interface with frame interface
     // this function might just get deleted
     pub fn set_cursor(&self, f: &mut Frame<'_>) {
+        if let Some(picker) = &self.picker {
+            let area = picker_area(f.size());
+            f.set_cursor(area.x + 3 + picker.query.chars().count() as u16, area.y + 1);
+            return;
+        }
+
         if let Some(buffer) = self.current_buffer() {
             match &buffer.mode {
                 Mode::Command => {
@@ -628,8 +2795,29 @@ impl Editor {
                     );
                 }
                 _ => {
+                    if let (Focus::Quickfix, true, Some(list)) = (self.focus, self.show_quickfix, &self.quickfix) {
+                        f.set_cursor(0, self.size.1 + 1 + (list.current - list.offset) as u16);
+                        return;
+                    }
+
+                    if let (Focus::Tree, true, Some(tree)) = (self.focus, self.show_tree, &self.tree) {
+                        let depth = tree.selected().map_or(0, |n| n.depth);
+                        f.set_cursor(
+                            (depth * 2 + 2).try_into().unwrap_or(0),
+                            (tree.cursor - tree.offset).try_into().unwrap_or(0),
+                        );
+                        return;
+                    }
+
+                    if let (Some(terminal), Mode::Insert) = (&buffer.terminal, buffer.mode) {
+                        if let Some((row, col)) = terminal.cursor() {
+                            f.set_cursor(col + X_OFFSET as u16 + self.tree_width, row);
+                        }
+                        return;
+                    }
+
                     f.set_cursor(
-                        (buffer.cursor.current.0 + X_OFFSET).try_into().unwrap(),
+                        (buffer.cursor_col() + X_OFFSET + buffer.text_offset()).try_into().unwrap_or(0) + self.tree_width,
                         (buffer.cursor.current.1).try_into().unwrap(),
                     );
                 }
@@ -637,6 +2825,681 @@ impl Editor {
         }
     }
 
+    // takes in what a background task handed back to the main loop
+    pub fn finish_task(&mut self, task: TaskOutput) {
+        match task {
+            TaskOutput::Quickfix { title, result: Ok(entries) } if entries.is_empty() => {
+                self.set_message(Some(format!("No matches for {}", title)));
+            },
+            TaskOutput::Quickfix { title, result: Ok(entries) } => {
+                // the first match is where :cn goes on from
+                let message = format!("{} matches for {}", entries.len(), title);
+                self.set_quickfix(QuickfixKind::Grep, title, entries);
+                self.jump_to_quickfix();
+                self.set_message(Some(message));
+            },
+            TaskOutput::Quickfix { result: Err(err), .. } => self.set_message(Some(err)),
+            TaskOutput::Make { command, success, result: Ok((entries, output)) } => {
+                let count = |severity| entries.iter().filter(|e| e.severity == Some(severity)).count();
+                let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+
+                let message = if !entries.is_empty() {
+                    format!("{}: {} error{}, {} warning{}", command,
+                        errors, if errors == 1 { "" } else { "s" }, warnings, if warnings == 1 { "" } else { "s" })
+                } else if success {
+                    format!("{}: finished", command)
+                } else {
+                    // nothing matched, so what went wrong is only in the output
+                    let lines: Vec<&str> = output.lines().collect();
+                    let tail = lines[lines.len().saturating_sub(10)..].join("\n");
+                    format!("{}: failed\n{}", command, tail)
+                };
+
+                let show = !entries.is_empty();
+                self.set_quickfix(QuickfixKind::Make, command, entries);
+                self.show_quickfix = show;
+                self.set_message(Some(message));
+            },
+            TaskOutput::Make { result: Err(err), .. } => self.set_message(Some(err)),
+            TaskOutput::Files { root, query, files } => {
+                let items = files.into_iter()
+                    .enumerate()
+                    .map(|(id, label)| Item { label, id })
+                    .collect();
+                self.show_picker(PickerKind::Files { root }, items, &query);
+            },
+            TaskOutput::Lsp { command, root, event } => self.lsp_event(&command, &root, event),
+            TaskOutput::Format { id, revision, then, result } => {
+                let idx = match self.buffers.iter().position(|b| b.id == id) {
+                    Some(idx) => idx,
+                    None => return,
+                };
+
+                // a formatter that fails stops the write, text typed while it ran is written as it is
+                match result {
+                    Ok(formatted) => {
+                        let changed = self.buffers[idx].apply_format(revision, formatted);
+                        self.after_format(idx, then, changed);
+                    },
+                    Err(err) => self.set_message(Some(err)),
+                }
+            },
+        }
+    }
+
+    // NOTE: completion functions
+
+    // keys that work the popup, false when the key is for the buffer
+    fn completion_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match (key.code, self.completion.as_mut()) {
+            (KeyCode::Char('n'), None) if ctrl => self.start_completion(true),
+            (KeyCode::Char('p'), None) if ctrl => {
+                self.start_completion(true);
+                if let Some(completion) = self.completion.as_mut() {
+                    completion.prev();
+                }
+            },
+            (KeyCode::Char('n'), Some(completion)) if ctrl => completion.next(),
+            (KeyCode::Char('p'), Some(completion)) if ctrl => completion.prev(),
+            (KeyCode::Down, Some(completion)) => completion.next(),
+            (KeyCode::Up, Some(completion)) => completion.prev(),
+            (KeyCode::Enter | KeyCode::Tab, Some(completion)) if !completion.matches.is_empty() => self.accept_completion(),
+            (KeyCode::Char('y'), Some(_)) if ctrl => self.accept_completion(),
+            (KeyCode::Char('e'), Some(_)) if ctrl => self.completion = None,
+            _ => return false,
+        }
+        true
+    }
+
+    // where the text a completion replaces starts, a path is completed from its last slash
+    // and anything else from the start of the word before the cursor
+    fn completion_start(&self) -> Option<(usize, Option<String>)> {
+        let buffer = self.current_buffer()?;
+        let rope = &buffer.lines.rope;
+        let idx = buffer.cursor_char();
+        let line_start = rope.line_to_char(rope.char_to_line(idx));
+        let before = rope.slice(line_start..idx).to_string();
+
+        Some(match completion::path_before(&before) {
+            Some((dir, name_start)) => (line_start + name_start, Some(dir)),
+            None => (line_start + completion::word_start(&before), None),
+        })
+    }
+
+    // opens the popup, asked for it gets the words in the buffers too
+    fn start_completion(&mut self, asked: bool) {
+        let (start, dir) = match self.completion_start() {
+            Some(start) => start,
+            None => return,
+        };
+        let buffer = &self.buffers[self.buf_ptr];
+        let rope = &buffer.lines.rope;
+        let query = rope.slice(start..buffer.cursor_char()).to_string();
+
+        let items = match &dir {
+            Some(dir) => completion::path_items(buffer.parent_dir.as_deref().unwrap_or(Path::new(".")), dir),
+            None if asked => {
+                let others = self.buffers.iter()
+                    .filter(|b| b.id != buffer.id && b.buffer_type == BufferType::File)
+                    .map(|b| &b.lines.rope);
+                let snippets = buffer.file_type.map_or(&[][..], |ft| ft.snippets).iter().map(snippet_item);
+                snippets.chain(completion::buffer_words(std::iter::once(rope).chain(others), &query)).collect()
+            },
+            None => vec![],
+        };
+
+        // the server has its own idea of what fits, except in paths
+        let asked_server = dir.is_none() && self.lsp_completion();
+        if items.is_empty() && !asked_server {
+            if asked {
+                self.set_message(Some(String::from("No completions")));
+            }
+            return;
+        }
+        self.completion = Some(Completion::new(start, &query, items));
+    }
+
+    // follows what is typed, the popup closes once the cursor leaves the text it started on
+    fn after_insert_key(&mut self, key: KeyEvent) {
+        let typed = match key.code {
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => Some(c),
+            KeyCode::Backspace => None,
+            _ => {
+                self.completion = None;
+                return;
+            },
+        };
+
+        if let Some(start) = self.completion.as_ref().map(|c| c.start) {
+            match self.completion_start() {
+                Some((now, _)) if now == start => {
+                    let buffer = &self.buffers[self.buf_ptr];
+                    let query = buffer.lines.rope.slice(start..buffer.cursor_char()).to_string();
+                    if let Some(completion) = self.completion.as_mut() {
+                        completion.set_query(&query);
+                    }
+                },
+                _ => self.completion = None,
+            }
+        }
+
+        if let (None, Some(c)) = (&self.completion, typed) {
+            if self.is_trigger(c) {
+                self.start_completion(false);
+            }
+        }
+    }
+
+    // a slash in a path or a char the language server completes after
+    fn is_trigger(&self, c: char) -> bool {
+        if c == '/' {
+            return self.completion_start().is_some_and(|(_, dir)| dir.is_some());
+        }
+
+        self.current_server().is_some_and(|(server, _)| {
+            self.servers[server].trigger_characters().iter().any(|t| t.ends_with(c))
+        })
+    }
+
+    fn accept_completion(&mut self) {
+        let completion = match self.completion.take() {
+            Some(completion) => completion,
+            None => return,
+        };
+        let item = match completion.selected_item() {
+            Some(item) => item.clone(),
+            None => return,
+        };
+
+        let (start, after) = item.replace.unwrap_or((completion.start, 0));
+        if let Some(buffer) = self.current_buffer_mut() {
+            match item.snippet {
+                true => buffer.insert_snippet(start, after, &item.insert),
+                false => {
+                    buffer.replace_around_cursor(start, after, &item.insert);
+                    buffer.sync_snippet();
+                },
+            }
+        }
+
+        // a directory goes on to what is in it
+        if item.source == completion::Source::Paths && item.insert.ends_with('/') {
+            self.start_completion(false);
+        } else if item.snippet {
+            self.show_snippet_choices();
+        }
+    }
+
+    // NOTE: snippet functions
+
+    // Tab goes on to the next tabstop of a snippet, or expands the one named by the word before
+    // the cursor, Shift-Tab goes back, false when there is nothing to do for the key
+    fn snippet_key(&mut self, key: KeyEvent) -> bool {
+        let forward = match key.code {
+            KeyCode::Tab => true,
+            KeyCode::BackTab => false,
+            _ => return false,
+        };
+
+        let buffer = &mut self.buffers[self.buf_ptr];
+        if !buffer.snippet_jump(forward) {
+            let found = match forward {
+                true => snippet_before_cursor(buffer),
+                false => None,
+            };
+            match found {
+                Some((start, body)) => buffer.insert_snippet(start, 0, body),
+                None => return false,
+            }
+        }
+
+        self.completion = None;
+        self.show_snippet_choices();
+        true
+    }
+
+    // a choice tabstop is picked from the popup
+    fn show_snippet_choices(&mut self) {
+        let (start, choices) = match self.current_buffer().and_then(|b| b.snippet_choices()) {
+            Some(choices) => choices,
+            None => return,
+        };
+        let items = choices.iter()
+            .map(|choice| completion::Item::new(choice, completion::Source::Snippets))
+            .collect();
+        self.completion = Some(Completion::new(start, "", items));
+    }
+
+    // asks the buffer's server for completions at the cursor, false when there isn't one to ask
+    fn lsp_completion(&mut self) -> bool {
+        let (server, attached) = match self.current_server() {
+            Some(server) if self.servers[server.0].supports("completionProvider") => server,
+            _ => return false,
+        };
+        let buffer = &self.buffers[self.buf_ptr];
+        let position = Position::from_char(&buffer.lines.rope, buffer.cursor_char());
+
+        self.sync_lsp();
+        self.servers[server].completion(&attached.path, position);
+        true
+    }
+
+    // NOTE: language server functions
+
+    // a sink that hands a server's events to the main loop
+    pub fn lsp_sink(&self, command: &str, root: &Path) -> Sink {
+        let sender = self.task_sender.clone();
+        let (command, root) = (command.to_string(), root.to_path_buf());
+
+        Arc::new(move |event| {
+            let _ = sender.send(TaskOutput::Lsp { command: command.clone(), root: root.clone(), event });
+        })
+    }
+
+    fn server_idx(&self, command: &str, root: &Path) -> Option<usize> {
+        self.servers.iter().position(|s| s.command == command && s.root == root)
+    }
+
+    // adds a server the editor didn't start itself, like a mock, and sends it the buffers it covers
+    pub fn attach_server(&mut self, client: Client) {
+        self.servers.push(client);
+        for idx in 0..self.buffers.len() {
+            self.attach_buffer(idx);
+        }
+    }
+
+    // starts a server and opens the buffers it covers on it
+    fn start_server(&mut self, command: &str, root: &Path) {
+        if !self.start_servers || !shell::installed(command) || tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+
+        match Client::start(command, root, self.lsp_sink(command, root)) {
+            Ok(client) => self.attach_server(client),
+            Err(err) => self.set_message(Some(format!("Can't start {}: {}", command, err.kind()))),
+        }
+    }
+
+    // opens the buffer on the language server for its file type and project,
+    // starting the server when there isn't one yet
+    fn attach_buffer(&mut self, idx: usize) {
+        let buffer = match self.buffers.get(idx) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let id = buffer.id;
+
+        let target = match (&buffer.file, buffer.file_type) {
+            (Some(file), Some(file_type)) if buffer.buffer_type != BufferType::Directory => {
+                file_type.language_server.map(|command| (file_type, Attached {
+                    command: command.to_string(),
+                    root: find_root(file, file_type.root_markers),
+                    path: file.clone(),
+                    revision: buffer.revision,
+                }))
+            },
+            _ => None,
+        };
+
+        let key = |attached: &Attached| (attached.command.clone(), attached.root.clone(), attached.path.clone());
+        if target.as_ref().map(|(_, t)| key(t)) == self.attached.get(&id).map(key) {
+            return;
+        }
+        self.detach_buffer(id);
+
+        let (file_type, target) = match target {
+            Some(target) => target,
+            None => return,
+        };
+        let server = match self.server_idx(&target.command, &target.root) {
+            Some(server) => server,
+            None => {
+                // the new server sends for this buffer itself
+                self.start_server(&target.command, &target.root);
+                return;
+            },
+        };
+
+        let rope = &self.buffers[idx].lines.rope;
+        self.servers[server].did_open(&target.path, language_id(file_type.name), rope);
+        self.attached.insert(id, target);
+    }
+
+    fn detach_buffer(&mut self, id: usize) {
+        if let Some(attached) = self.attached.remove(&id) {
+            if let Some(server) = self.server_idx(&attached.command, &attached.root) {
+                self.servers[server].did_close(&attached.path);
+            }
+        }
+    }
+
+    // sends the servers whatever changed in the buffers they have open
+    pub fn sync_lsp(&mut self) {
+        for buffer in self.buffers.iter() {
+            // the revision only moves when the text does
+            let attached = match self.attached.get_mut(&buffer.id) {
+                Some(attached) if buffer.file.as_ref() == Some(&attached.path) && buffer.revision != attached.revision => attached,
+                _ => continue,
+            };
+
+            let server = self.servers.iter_mut().find(|s| s.command == attached.command && s.root == attached.root);
+            if let Some(server) = server {
+                server.did_change(&attached.path, &buffer.lines.rope);
+                attached.revision = buffer.revision;
+            }
+        }
+    }
+
+    // asks every language server to stop and gives them a moment to exit, the ones
+    // that take longer are killed along with the runtime
+    pub async fn shutdown_servers(&mut self) {
+        self.servers.iter_mut().for_each(|server| server.shutdown());
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(1);
+        for server in &mut self.servers {
+            let _ = tokio::time::timeout_at(deadline, server.wait()).await;
+        }
+        self.servers.clear();
+    }
+
+    fn lsp_saved(&mut self, id: usize) {
+        let attached = match self.attached.get(&id) {
+            Some(attached) => attached.clone(),
+            None => return,
+        };
+
+        self.sync_lsp();
+        if let Some(server) = self.server_idx(&attached.command, &attached.root) {
+            self.servers[server].did_save(&attached.path);
+        }
+    }
+
+    fn current_server(&self) -> Option<(usize, Attached)> {
+        let attached = self.attached.get(&self.current_buffer()?.id)?;
+        Some((self.server_idx(&attached.command, &attached.root)?, attached.clone()))
+    }
+
+    // the server, file and position a request about the cursor goes to,
+    // with the server brought up to date first
+    fn lsp_target(&mut self, capability: &str, action: &str) -> Option<(usize, PathBuf, Position)> {
+        let buffer = self.current_buffer()?;
+        let rope = &buffer.lines.rope;
+        let line = buffer.ptr_y + buffer.cursor.current.1;
+        let idx = rope.line_to_char(line.min(rope.len_lines() - 1)) + buffer.cursor.current.0;
+        let position = Position::from_char(rope, idx);
+
+        let (server, attached) = match self.current_server() {
+            Some(server) => server,
+            None => {
+                self.set_message(Some(String::from("No language server for this buffer")));
+                return None;
+            },
+        };
+
+        if !self.servers[server].supports(capability) {
+            self.set_message(Some(format!("{} can't {}", attached.command, action)));
+            return None;
+        }
+
+        self.sync_lsp();
+        Some((server, attached.path, position))
+    }
+
+    pub fn lsp_hover(&mut self) {
+        if let Some((server, path, position)) = self.lsp_target("hoverProvider", "hover") {
+            self.servers[server].hover(&path, position);
+        }
+    }
+
+    pub fn lsp_definition(&mut self) {
+        if let Some((server, path, position)) = self.lsp_target("definitionProvider", "find definitions") {
+            self.servers[server].definition(&path, position);
+        }
+    }
+
+    pub fn lsp_references(&mut self) {
+        if let Some((server, path, position)) = self.lsp_target("referencesProvider", "find references") {
+            self.servers[server].references(&path, position);
+        }
+    }
+
+    pub fn lsp_rename(&mut self, name: &str) {
+        if name.is_empty() {
+            self.set_message(Some(String::from("Argument required")));
+            return;
+        }
+
+        if let Some((server, path, position)) = self.lsp_target("renameProvider", "rename") {
+            self.servers[server].rename(&path, position, name);
+        }
+    }
+
+    // asks for the actions at the cursor, or runs one of the last ones listed by number
+    pub fn lsp_code_action(&mut self, arg: &str) {
+        if arg.is_empty() {
+            if let Some((server, path, position)) = self.lsp_target("codeActionProvider", "run code actions") {
+                let range = LspRange { start: position, end: position };
+                self.servers[server].code_actions(&path, range);
+            }
+            return;
+        }
+
+        let chosen = arg.parse::<usize>().ok().and_then(|n| {
+            let (command, root, actions) = self.code_actions.as_ref()?;
+            Some((command.clone(), root.clone(), actions.get(n.checked_sub(1)?)?.clone()))
+        });
+        let (command, root, action) = match chosen {
+            Some(chosen) => chosen,
+            None => {
+                self.set_message(Some(format!("No code action {}", arg)));
+                return;
+            },
+        };
+
+        if let Some(edit) = action.edit {
+            if let Err(err) = self.apply_workspace_edit(edit) {
+                self.set_message(Some(err));
+                return;
+            }
+        }
+        if let (Some(cmd), Some(server)) = (action.command, self.server_idx(&command, &root)) {
+            self.servers[server].execute_command(&cmd);
+        }
+        self.set_message(Some(action.title));
+    }
+
+    // what the servers have found in a file, they keep it so there is only one copy
+    fn lsp_diagnostics(&self, path: &Path) -> Vec<Diagnostic> {
+        self.servers.iter().flat_map(|s| s.diagnostics(path)).collect()
+    }
+
+    // every diagnostic the servers have sent, in a quickfix list
+    pub fn diagnostics_list(&mut self) {
+        let mut paths: Vec<PathBuf> = self.servers.iter().flat_map(|s| s.diagnostic_paths()).collect();
+        paths.sort();
+        paths.dedup();
+
+        let mut entries = vec![];
+        for path in &paths {
+            let rope = self.file_rope(path);
+            for diagnostic in &self.lsp_diagnostics(path) {
+                let mut entry = location_entry(&rope, path, diagnostic.range.start);
+                entry.text = diagnostic.message.lines().next().unwrap_or("").to_string();
+                entry.severity = Some(severity(diagnostic));
+                entries.push(entry);
+            }
+        }
+
+        if entries.is_empty() {
+            self.set_message(Some(String::from("No diagnostics")));
+            return;
+        }
+        self.set_quickfix(QuickfixKind::Diagnostics, String::from("diagnostics"), entries);
+    }
+
+    // hands what the servers found in a file to its buffers
+    fn show_lsp_diagnostics(&mut self, path: &Path) {
+        let found = self.lsp_diagnostics(path);
+
+        for buffer in self.buffers.iter_mut().filter(|b| b.file.as_deref() == Some(path)) {
+            let rope = &buffer.lines.rope;
+            let converted = found.iter()
+                .map(|d| diagnostic::Diagnostic {
+                    start: d.range.start.to_char(rope),
+                    end: d.range.end.to_char(rope),
+                    severity: severity(d),
+                    message: d.message.clone(),
+                    source: d.source.clone(),
+                })
+                .collect();
+            buffer.set_diagnostics("lsp", converted);
+        }
+    }
+
+    // the text of a file, from its buffer when it is open
+    fn file_rope(&self, path: &Path) -> Rope {
+        match self.buffers.iter().find(|b| b.file.as_deref() == Some(path)) {
+            Some(buffer) => buffer.lines.rope.clone(),
+            None => Rope::from(std::fs::read_to_string(path).unwrap_or_default()),
+        }
+    }
+
+    fn location_entries(&self, locations: &[Location]) -> Vec<Entry> {
+        let mut ropes: HashMap<&Path, Rope> = HashMap::new();
+
+        locations.iter()
+            .map(|location| {
+                let rope = ropes.entry(&location.path).or_insert_with(|| self.file_rope(&location.path));
+                location_entry(rope, &location.path, location.range.start)
+            })
+            .collect()
+    }
+
+    // makes the edits in each file, opening the ones that aren't open yet,
+    // the current buffer stays the same
+    pub fn apply_workspace_edit(&mut self, edit: WorkspaceEdit) -> Result<usize, String> {
+        let current = self.buf_ptr;
+        let alt = self.alt_buf;
+        let files = edit.changes.len();
+
+        for (path, edits) in edit.changes {
+            let idx = match self.buffers.iter().position(|b| b.file.as_ref() == Some(&path)) {
+                Some(idx) => idx,
+                None => {
+                    let buffer = Buffer::new(&path.to_string_lossy().to_string(), self.size)
+                        .map_err(|err| format!("Can't open \"{}\": {}", display_name(&path), err))?;
+                    self.add_buffer(buffer);
+                    self.buffers.len() - 1
+                },
+            };
+
+            let buffer = &mut self.buffers[idx];
+            let rope = &buffer.lines.rope;
+            let changes = edits.into_iter()
+                .map(|e| (e.range.start.to_char(rope), e.range.end.to_char(rope), e.new_text))
+                .collect();
+            buffer.apply_edits(changes);
+        }
+
+        self.buf_ptr = current;
+        self.alt_buf = alt;
+        self.sync_lsp();
+        Ok(files)
+    }
+
+    fn lsp_event(&mut self, command: &str, root: &Path, event: LspEvent) {
+        match event {
+            LspEvent::Ready => {},
+            LspEvent::Diagnostics { path, .. } => self.show_lsp_diagnostics(&path),
+            LspEvent::Hover(text) if text.is_empty() => self.set_message(Some(String::from("No information"))),
+            LspEvent::Hover(text) => self.set_message(Some(text)),
+            LspEvent::Definition(locations) if locations.is_empty() => {
+                self.set_message(Some(String::from("No definition found")));
+            },
+            LspEvent::Definition(locations) => {
+                let entries = self.location_entries(&locations);
+                if entries.len() > 1 {
+                    self.set_quickfix(QuickfixKind::Locations, String::from("definitions"), entries);
+                    self.jump_to_quickfix();
+                } else {
+                    self.edit(&entries[0].path.to_string_lossy());
+                    if let Some(buffer) = self.current_buffer_mut() {
+                        buffer.jump_to(entries[0].line, entries[0].col);
+                    }
+                }
+            },
+            LspEvent::References(locations) if locations.is_empty() => {
+                self.set_message(Some(String::from("No references found")));
+            },
+            LspEvent::References(locations) => {
+                let entries = self.location_entries(&locations);
+                self.set_message(Some(format!("{} references", entries.len())));
+                self.set_quickfix(QuickfixKind::Locations, String::from("references"), entries);
+            },
+            LspEvent::Rename(edit) if edit.changes.is_empty() => self.set_message(Some(String::from("Nothing to rename"))),
+            LspEvent::Rename(edit) => {
+                let message = match self.apply_workspace_edit(edit) {
+                    Ok(1) => String::from("Renamed in 1 file"),
+                    Ok(files) => format!("Renamed in {} files", files),
+                    Err(err) => err,
+                };
+                self.set_message(Some(message));
+            },
+            LspEvent::CodeActions(actions) if actions.is_empty() => self.set_message(Some(String::from("No code actions"))),
+            LspEvent::CodeActions(actions) => {
+                let lines: Vec<String> = actions.iter()
+                    .enumerate()
+                    .map(|(i, action)| format!("{}: {}", i + 1, action.title))
+                    .collect();
+                self.set_message(Some(lines.join("\n")));
+                self.code_actions = Some((command.to_string(), root.to_path_buf(), actions));
+            },
+            LspEvent::Completion(items) => {
+                // a textEdit range is in the buffer as it is now, the popup is open on it
+                let replace = |range: Option<LspRange>| {
+                    let buffer = self.current_buffer()?;
+                    let rope = &buffer.lines.rope;
+                    let cursor = buffer.cursor_char();
+                    let range = range?;
+                    Some((range.start.to_char(rope), range.end.to_char(rope).saturating_sub(cursor)))
+                };
+                let items = items.into_iter()
+                    .map(|item| completion::Item {
+                        replace: replace(item.range),
+                        label: item.label,
+                        insert: item.insert_text,
+                        source: completion::Source::Lsp,
+                        detail: item.detail,
+                        documentation: item.documentation,
+                        snippet: item.snippet,
+                    })
+                    .collect();
+                if let Some(completion) = self.completion.as_mut() {
+                    completion.add(items);
+                }
+            },
+            LspEvent::ApplyEdit(edit) => {
+                if let Err(err) = self.apply_workspace_edit(edit) {
+                    self.set_message(Some(err));
+                }
+            },
+            LspEvent::Message(text) | LspEvent::Error(text) => self.set_message(Some(format!("{}: {}", command, text))),
+            LspEvent::Exited => {
+                let paths = self.server_idx(command, root).map_or(vec![], |i| self.servers[i].diagnostic_paths());
+                self.servers.retain(|s| s.command != command || s.root != root);
+                self.attached.retain(|_, a| a.command != command || a.root != root);
+
+                // what it found goes away with it
+                for path in paths {
+                    self.show_lsp_diagnostics(&path);
+                }
+                self.set_message(Some(format!("{} exited", command)));
+            },
+        }
+    }
+
     pub async fn next_motion(&mut self) -> Result<Vec<String>> {
         let event = self.motion_listener.recv().await.ok_or(color_eyre::eyre::eyre!("Unable to get action"));
         event
@@ -656,15 +3519,48 @@ impl Drop for Editor {
     fn drop(&mut self) {
         drop(self.clear_sender.to_owned());
         drop(self.motion_sender.to_owned());
-        match std::mem::replace(&mut self.logger, None) {
-            Some(stream) => {
-                drop(stream);
-            },
-            None => {},
+        if let Some(stream) = self.logger.take() {
+            drop(stream);
         }
     }
 }
 
+// what ctrl-a and ctrl-x come to the motions as
+const CTRL_A: &str = "\u{1}";
+const CTRL_X: &str = "\u{18}";
+
+// actions that take the next key as their argument instead of a motion
+fn takes_char(action: &str) -> bool {
+    matches!(action, "f" | "g" | "m" | "'" | "`" | "]" | "[" | "r")
+}
+
+// first lines of a file, or a note when it can't be shown
+fn preview_file(path: &std::path::Path, max_lines: usize) -> Vec<String> {
+    use std::io::Read;
+
+    let mut bytes = vec![];
+    let read = std::fs::File::open(path)
+        .and_then(|file| file.take(64 * 1024).read_to_end(&mut bytes));
+
+    match read {
+        Err(err) => vec![format!("Can't read file: {}", err.kind())],
+        Ok(_) if bytes.contains(&0) => vec![String::from("Binary file")],
+        Ok(_) => String::from_utf8_lossy(&bytes)
+            .lines()
+            .take(max_lines)
+            .map(|l| l.replace('\t', "    "))
+            .collect(),
+    }
+}
+
+fn unsaved_message(buffer: &Buffer) -> String {
+    format!(
+        "No write since last change for buffer {} \"{}\" (add ! to override)",
+        buffer.id,
+        buffer.name()
+    )
+}
+
 fn make_motion_string(input: &Vec<String>) -> String {
     let mut s = String::new();
     for i in input {
@@ -683,3 +3579,49 @@ fn test_make_motion_string(){
     assert_eq!(make_motion_string(&vec![":".to_string()]), String::from(":"));
     assert_eq!(make_motion_string(&vec!["2".to_string(), ":".to_string()]), String::from("2:"));
 }
+
+// a quickfix entry for a position in a file, its text is the line it is on
+fn location_entry(rope: &Rope, path: &Path, position: Position) -> Entry {
+    let idx = position.to_char(rope);
+    let line = rope.char_to_line(idx);
+    let cwd = std::env::current_dir().unwrap_or_default();
+
+    Entry {
+        path: path.strip_prefix(&cwd).unwrap_or(path).to_path_buf(),
+        line,
+        col: idx - rope.line_to_char(line),
+        text: rope.get_line(line).map_or(String::new(), |l| l.to_string().trim().to_string()),
+        severity: None,
+    }
+}
+
+fn severity(diagnostic: &Diagnostic) -> Severity {
+    match diagnostic.severity {
+        editor_lsp::protocol::Severity::Error => Severity::Error,
+        editor_lsp::protocol::Severity::Warning => Severity::Warning,
+        _ => Severity::Info,
+    }
+}
+
+// a snippet of the buffer's file type in the completion popup
+fn snippet_item(snippet: &Snippet) -> completion::Item {
+    let mut item = completion::Item::new(snippet.prefix, completion::Source::Snippets);
+    item.insert = snippet.body.to_string();
+    item.detail = Some(snippet.description.to_string());
+    item.documentation = Some(snippet::expand(snippet.body, &|_| None).text);
+    item.snippet = true;
+    item
+}
+
+// the snippet named by the word right before the cursor, with where the word starts
+fn snippet_before_cursor(buffer: &Buffer) -> Option<(usize, &'static str)> {
+    let rope = &buffer.lines.rope;
+    let idx = buffer.cursor_char();
+    let line_start = rope.line_to_char(rope.char_to_line(idx));
+    let before = rope.slice(line_start..idx).to_string();
+    let start = completion::word_start(&before);
+    let word: String = before.chars().skip(start).collect();
+
+    let snippet = buffer.file_type?.snippets.iter().find(|s| !word.is_empty() && s.prefix == word)?;
+    Some((line_start + start, snippet.body))
+}
@@ -3,6 +3,8 @@ mod test;
 
 mod colors;
 mod editor;
+mod prompt;
+mod task;
 mod tui;
 mod ui;
 mod widgets;
@@ -14,9 +16,8 @@ use crate::{
     ui::{ui, update},
 };
 use editor_motion::MotionHandler;
-use tokio::select;
+use tokio::{select, sync::mpsc};
 use color_eyre::eyre::Result;
-use editor_core::X_OFFSET;
 
 /* ====================
  Map to improve event loop: 
@@ -37,8 +38,10 @@ async fn run() -> Result<()> {
 
     let mut tui = Tui::new()?.tick_rate(1.0).render_rate(30.0);
     let (mut motion, motion_sender, clear_sender, motion_buffer_listener) = MotionHandler::new();
-    let mut editor = Editor::new(motion_sender, clear_sender, motion_buffer_listener)?;
+    let (task_sender, mut task_listener) = mpsc::unbounded_channel();
+    let mut editor = Editor::new(motion_sender, clear_sender, motion_buffer_listener, task_sender)?;
 
+    editor.start_servers = true;
     editor.new_buffer(&filename);
 
     tui.enter()?; 
@@ -92,6 +95,13 @@ async fn run() -> Result<()> {
                 }
             },
 
+            // results of background work like :grep
+            task = task_listener.recv() => {
+                if let Some(task) = task {
+                    editor.finish_task(task);
+                }
+            },
+
             // recv char from editor
             c = motion.listener.recv() => {
                 motion.handle_char(c);
@@ -105,9 +115,14 @@ async fn run() -> Result<()> {
         if editor.should_quit {
             break;
         }
+
+        // diagnostics follow the edits and language servers see them before the next request
+        editor.sync_diagnostics();
+        editor.sync_lsp();
     }
 
     tui.exit()?; 
+    editor.shutdown_servers().await;
     Ok(())
 }
 
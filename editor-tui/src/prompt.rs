@@ -0,0 +1,28 @@
+use editor_core::explorer::Change;
+use std::path::PathBuf;
+
+// actions that wait on a yes/no answer from the user
+#[derive(Clone, Debug, PartialEq)]
+pub enum PromptAction {
+    // create the missing directories of the current buffer's file, then write it
+    CreateDirs { force: bool, quit: bool },
+    // move an entry of the current directory buffer to the trash
+    Trash { path: PathBuf },
+    // carry out the edits made to a directory listing
+    ApplyListing { changes: Vec<Change>, quit: bool },
+}
+
+pub struct Prompt {
+    pub message: String,
+    pub action: PromptAction,
+}
+
+impl Prompt {
+    pub fn new(message: String, action: PromptAction) -> Self {
+        Prompt { message, action }
+    }
+
+    pub fn text(&self) -> String {
+        format!("{} [y/N]", self.message)
+    }
+}
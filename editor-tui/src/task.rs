@@ -0,0 +1,24 @@
+use editor_core::quickfix::Entry;
+use std::path::PathBuf;
+
+// results of work done off of the main loop, handed back through Editor::finish_task
+pub enum TaskOutput {
+    // entries for a new quickfix list, or why they couldn't be found
+    Quickfix { title: String, result: Result<Vec<Entry>, String> },
+    // the files of the project at root for the picker, which opens with query typed in
+    Files { root: PathBuf, query: String, files: Vec<String> },
+    // a finished :make, with what it printed when nothing could be read from it
+    Make { command: String, success: bool, result: Result<(Vec<Entry>, String), String> },
+    // something from the language server running command for the project at root
+    Lsp { command: String, root: PathBuf, event: editor_lsp::Event },
+    // the formatter's output for a revision of the buffer with id, then carries on with the write
+    Format { id: usize, revision: usize, then: AfterFormat, result: Result<String, String> },
+}
+
+// what a buffer is formatted for
+pub enum AfterFormat {
+    Nothing,
+    Write { force: bool, quit: bool },
+    WriteTo { path: String, force: bool },
+    SaveAs { path: String, force: bool },
+}
@@ -1,10 +1,67 @@
+use crate::editor::Editor;
+use crate::task::TaskOutput;
+use editor_motion::MotionHandler;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::mpsc;
+
+// a directory for one test, removed again when it goes out of scope
+struct TempDir(PathBuf);
+
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// a new temp dir with the files in it, a name ending in / is made as a directory
+fn temp_dir_with(files: &[(&str, &str)]) -> TempDir {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!("editor-test-{}-{}", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed));
+    let dir = TempDir(std::env::temp_dir().join(name));
+    let _ = std::fs::remove_dir_all(&dir.0);
+    std::fs::create_dir_all(&dir.0).unwrap();
+
+    for (name, text) in files {
+        let path = dir.join(name);
+        if name.ends_with('/') {
+            std::fs::create_dir_all(&path).unwrap();
+            continue;
+        }
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, text).unwrap();
+    }
+    dir
+}
+
+// an 80x24 editor, what it runs in the background comes back on the receiver
+fn new_editor() -> (Editor, mpsc::UnboundedReceiver<TaskOutput>) {
+    let (_motion, motion_sender, clear_sender, motion_buffer_listener) = MotionHandler::new();
+    let (task_sender, task_listener) = mpsc::unbounded_channel();
+    let mut editor = Editor::new(motion_sender, clear_sender, motion_buffer_listener, task_sender).unwrap();
+    editor.resize((80, 24));
+    (editor, task_listener)
+}
+
+// an editor next to a temp dir with the files in it
+fn editor_in(files: &[(&str, &str)]) -> (Editor, TempDir, mpsc::UnboundedReceiver<TaskOutput>) {
+    let dir = temp_dir_with(files);
+    let (editor, task_listener) = new_editor();
+    (editor, dir, task_listener)
+}
+
 #[tokio::test]
 async fn test_editor(){
-    use crate::editor::Editor;
-    use editor_motion::MotionHandler;
-
     let (_motion, motion_sender, clear_sender, motion_buffer_listener) = MotionHandler::new();
-    let editor_res = Editor::new(motion_sender, clear_sender, motion_buffer_listener);
+    let editor_res = Editor::new(motion_sender, clear_sender, motion_buffer_listener, mpsc::unbounded_channel().0);
 
     assert!(editor_res.is_ok());
 
@@ -53,6 +110,1346 @@ fn test_buffer() {
 }
 
 
+#[tokio::test]
+async fn test_buffer_list() {
+    use editor_core::command::CommandKey;
+
+    let (mut editor, _) = new_editor();
+
+    editor.new_buffer(&"./src/main.rs".to_string());
+    editor.new_buffer(&"./src/test.rs".to_string());
+    assert_eq!(editor.buf_ptr, 1);
+    assert!(editor.list_buffers().contains("\"src/main.rs\" line 1"));
+
+    editor.handle_command(Some(CommandKey::Buffer("main".to_string())));
+    assert_eq!(editor.buf_ptr, 0);
+    editor.alternate_buf();
+    assert_eq!(editor.buf_ptr, 1);
+
+    // modified buffers stay around unless forced
+    editor.buffers[1].begin_edit();
+    assert!(editor.delete_buffer(1, false).is_err());
+    assert_eq!(editor.buffers.len(), 2);
+    assert!(editor.delete_buffer(1, true).is_ok());
+    assert_eq!(editor.buffers.len(), 1);
+    assert_eq!(editor.buf_ptr, 0);
+}
+
+#[test]
+fn test_undo() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_core::buffer::{Buffer, Mode};
+
+    let mut b = Buffer::new(&"./src/main.rs".to_string(), (10, 10)).unwrap();
+    let original = b.lines.rope.to_string();
+
+    b.change_mode(Mode::Insert);
+    b.insert_key_file(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE), (10, 10));
+    b.insert_key_file(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE), (10, 10));
+    b.change_mode(Mode::Normal);
+    assert!(b.is_modified());
+    assert!(b.lines.rope.to_string().starts_with("xy"));
+
+    // the whole insert is one step, and undoing it gets back to the saved text
+    assert!(b.undo());
+    assert_eq!(b.lines.rope.to_string(), original);
+    assert!(!b.is_modified());
+    assert!(!b.undo());
+
+    assert!(b.redo());
+    assert!(b.lines.rope.to_string().starts_with("xy"));
+
+    // an edit that doesn't go in is no new revision or undo step
+    assert!(b.undo());
+    b.cursor.current = (10_000, 0);
+    b.paste(String::from("z"));
+    assert_eq!(b.lines.rope.to_string(), original);
+    assert!(!b.is_modified());
+    assert!(!b.undo());
+
+    // backspace at the start of a line joins it to the end of the one above
+    let first = b.lines.rope.line(0).len_chars() - 1;
+    b.change_mode(Mode::Insert);
+    b.jump_to(1, 0);
+    b.insert_key_file(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE), (10, 10));
+    assert_eq!(b.cursor.current, (first, 0));
+    assert_eq!(b.lines.rope.len_lines(), original.lines().count());
+}
+
+#[tokio::test]
+async fn test_quit_modified() {
+
+    let (mut editor, _) = new_editor();
+
+    editor.new_buffer(&"./src/main.rs".to_string());
+    editor.new_buffer(&"./src/test.rs".to_string());
+    editor.buffers[0].begin_edit();
+    assert!(editor.file_display().ends_with("test.rs"));
+
+    // the modified buffer is named and becomes current
+    let err = editor.quit_all(false).unwrap_err();
+    assert!(err.contains("src/main.rs"));
+    assert!(!editor.should_quit);
+    assert_eq!(editor.buf_ptr, 0);
+    assert!(editor.file_display().ends_with("[+]"));
+
+    assert!(editor.quit(false).is_err());
+    assert_eq!(editor.buffers.len(), 2);
+
+    assert!(editor.quit_all(true).is_ok());
+    assert!(editor.should_quit);
+}
+
+#[tokio::test]
+async fn test_new_file() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_core::{buffer::BufferType, command::CommandKey};
+
+    let (mut editor, dir, _) = editor_in(&[]);
+    let path = dir.join("nested/new.rs");
+
+    editor.handle_command(Some(CommandKey::Edit { path: Some(path.display().to_string()), force: false }));
+    assert_eq!(editor.buffers[0].buffer_type, BufferType::Empty);
+    assert_eq!(editor.buffers[0].file.as_ref(), Some(&path));
+
+    // rustfmt would run on the write when it is installed
+    editor.handle_command(Some(CommandKey::FormatPrg(String::from("off"))));
+
+    // typing works the same as in a file
+    editor.motion_func("i");
+    editor.key_press(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+    assert_eq!(editor.buffers[0].lines.rope.to_string(), "x\n");
+
+    // missing directories are only made after confirming
+    editor.write_buffer(false, false);
+    assert!(editor.prompt.is_some());
+    editor.key_press(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::NONE));
+    assert!(!path.exists());
+
+    editor.write_buffer(false, false);
+    editor.key_press(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "x\n");
+    assert_eq!(editor.buffers[0].buffer_type, BufferType::File);
+    assert!(!editor.buffers[0].is_modified());
+}
+
+#[test]
+fn test_empty_file() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_core::buffer::{Buffer, Mode};
+
+    let dir = temp_dir_with(&[("empty.txt", ""), ("blank.txt", "\n")]);
+    let mut b = Buffer::new(&dir.join("empty.txt").display().to_string(), (10, 10)).unwrap();
+    assert_eq!(b.lines.rope.to_string(), "\n");
+
+    // it is shown with one empty line but written back as nothing
+    b.save().unwrap();
+    assert_eq!(std::fs::read_to_string(dir.join("empty.txt")).unwrap(), "");
+
+    b.change_mode(Mode::Insert);
+    b.insert_key_file(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE), (10, 10));
+    b.change_mode(Mode::Normal);
+    b.save().unwrap();
+    assert_eq!(std::fs::read_to_string(dir.join("empty.txt")).unwrap(), "x\n");
+
+    // a file that really is one empty line keeps it
+    let mut b = Buffer::new(&dir.join("blank.txt").display().to_string(), (10, 10)).unwrap();
+    b.save().unwrap();
+    assert_eq!(std::fs::read_to_string(dir.join("blank.txt")).unwrap(), "\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_open_links_and_special_files() {
+    use editor_core::buffer::{Buffer, BufferType};
+    use std::os::unix::fs::symlink;
+
+    let dir = temp_dir_with(&[("target.rs", "fn main() {}\n")]);
+    let target = dir.join("target.rs");
+    symlink(&target, dir.join("link.rs")).unwrap();
+    symlink(dir.join("missing.rs"), dir.join("broken.rs")).unwrap();
+
+    let mut b = Buffer::new(&dir.join("link.rs").display().to_string(), (10, 10)).unwrap();
+    assert_eq!(b.buffer_type, BufferType::File);
+    assert_eq!(b.file.as_ref(), Some(&dir.join("link.rs")));
+    assert_eq!(b.link_target.as_ref(), Some(&target));
+
+    // saving goes through to the target and keeps the link
+    b.begin_edit();
+    b.lines.rope.insert(0, "// hi\n");
+    b.save().unwrap();
+    assert!(dir.join("link.rs").is_symlink());
+    assert!(std::fs::read_to_string(&target).unwrap().starts_with("// hi"));
+
+    let err = Buffer::new(&dir.join("broken.rs").display().to_string(), (10, 10)).err().unwrap();
+    assert!(err.to_string().contains("broken symbolic link"));
+
+    // a failed open leaves the buffer alone
+    assert!(b.open(&"/dev/null".to_string()).is_err());
+    assert_eq!(b.file.as_ref(), Some(&dir.join("link.rs")));
+}
+
+#[tokio::test]
+async fn test_explorer() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_core::command::CommandKey;
+
+    let (mut editor, dir, _) = editor_in(&[("sub/", ""), ("b.txt", "b\n"), (".hidden", "")]);
+    editor.new_buffer(&dir.display().to_string());
+
+    let listing = |editor: &Editor| editor.buffers[0].lines.rope.to_string();
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    assert_eq!(listing(&editor), "./\n../\nsub/\nb.txt\n");
+
+    editor.key_press(key('.'));
+    assert_eq!(listing(&editor), "./\n../\nsub/\n.hidden\nb.txt\n");
+    editor.key_press(key('.'));
+
+    // new entries get selected
+    editor.handle_command(Some(CommandKey::Create(String::from("a.txt"))));
+    assert!(dir.join("a.txt").exists());
+    assert_eq!(editor.buffers[0].hovered_entry(), Some(String::from("a.txt")));
+
+    editor.handle_command(Some(CommandKey::Rename(String::from("sub/c.txt"))));
+    assert!(dir.join("sub/c.txt").exists());
+    assert_eq!(editor.buffers[0].hovered_entry(), Some(String::from("sub")));
+
+    editor.buffers[0].select_entry("b.txt");
+    editor.handle_command(Some(CommandKey::Copy(String::from("d.txt"))));
+    assert_eq!(std::fs::read_to_string(dir.join("d.txt")).unwrap(), "b\n");
+
+    // deleting asks first
+    editor.key_press(key('D'));
+    assert!(editor.prompt.is_some());
+    editor.key_press(key('n'));
+    assert!(dir.join("d.txt").exists());
+
+    #[cfg(unix)]
+    {
+        editor.handle_command(Some(CommandKey::Chmod(String::from("755"))));
+        assert_eq!(editor_core::explorer::mode(&dir.join("d.txt")), Some(0o755));
+    }
+
+    // going up selects the directory we came from
+    editor.key_press(key('-'));
+    let name = dir.file_name().unwrap().to_string_lossy().to_string();
+    assert_eq!(editor.buffers[0].hovered_entry(), Some(name));
+}
+
+#[tokio::test]
+async fn test_edit_listing() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_core::command::CommandKey;
+
+    let (mut editor, dir, _) = editor_in(&[("a.txt", "a\n"), ("b.txt", "b\n"), ("c.txt", "c\n")]);
+    let trash = temp_dir_with(&[]);
+    editor.trash = Some(trash.to_path_buf());
+    editor.new_buffer(&dir.display().to_string());
+
+    // rename a.txt by typing in front of it
+    editor.buffers[0].select_entry("a.txt");
+    editor.motion_func("i");
+    editor.key_press(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+    editor.key_press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+
+    // drop b.txt and add a directory
+    editor.buffers[0].select_entry("b.txt");
+    editor.parse(vec![String::from("d"), String::from("d")]).unwrap();
+    let end = editor.buffers[0].lines.rope.len_chars();
+    editor.buffers[0].begin_edit();
+    editor.buffers[0].lines.rope.insert(end, "new/\n");
+    editor.buffers[0].end_edit();
+    assert_eq!(editor.buffers[0].lines.rope.to_string(), "./\n../\nxa.txt\nc.txt\nnew/\n");
+    assert!(editor.buffers[0].is_modified());
+
+    // the listing can't be left while it has edits
+    editor.key_press(KeyEvent::new(KeyCode::Char('-'), KeyModifiers::NONE));
+    assert!(editor.buffers[0].parent_dir.as_deref() == Some(&*dir));
+
+    editor.handle_command(Some(CommandKey::Save { path: None, force: false }));
+    let summary = editor.status_text().unwrap();
+    assert!(summary.contains("delete b.txt\nrename a.txt -> xa.txt\ncreate new/\nApply 3 changes?"));
+
+    editor.key_press(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+    assert!(dir.join("xa.txt").exists());
+    assert!(dir.join("new").is_dir());
+    assert!(!dir.join("a.txt").exists());
+    assert!(!dir.join("b.txt").exists());
+    assert!(!editor.buffers[0].is_modified());
+    assert_eq!(editor.buffers[0].lines.rope.to_string(), "./\n../\nnew/\nc.txt\nxa.txt\n");
+    assert!(trash.join("files/b.txt").exists());
+}
+
+#[tokio::test]
+async fn test_file_tree() {
+    use crate::editor::Focus;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_core::command::CommandKey;
+
+    let (mut editor, _) = new_editor();
+    editor.new_buffer(&"./src/main.rs".to_string());
+    editor.new_buffer(&"./Cargo.toml".to_string());
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+    editor.handle_command(Some(CommandKey::ToggleTree));
+    assert!(editor.show_tree && editor.focus == Focus::Tree);
+    assert_eq!(editor.resize_tree(100), 25);
+
+    // the buffer doesn't see keys while the tree has focus
+    editor.key_press(key('j'));
+    assert_eq!(editor.tree.as_ref().unwrap().cursor, 1);
+
+    editor.buf_ptr = 0;
+    editor.handle_command(Some(CommandKey::RevealInTree));
+    let main = editor.buffers[0].file.clone().unwrap();
+    assert_eq!(editor.tree.as_ref().unwrap().selected().unwrap().path, main);
+
+    // files open in the buffer area, which then gets the focus
+    editor.buf_ptr = 1;
+    editor.key_press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    assert_eq!(editor.buf_ptr, 0);
+    assert_eq!(editor.focus, Focus::Buffer);
+
+    editor.key_press(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL));
+    editor.key_press(key('h'));
+    assert_eq!(editor.focus, Focus::Tree);
+
+    editor.key_press(key('q'));
+    assert!(!editor.show_tree && editor.focus == Focus::Buffer);
+    assert_eq!(editor.resize_tree(100), 0);
+}
+
+#[tokio::test]
+async fn test_picker() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_core::{command::CommandKey, picker::PickerKind};
+
+    let (mut editor, mut task_listener) = new_editor();
+    editor.new_buffer(&"./src/main.rs".to_string());
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+
+    // files come from the buffer's parent directory, listed in the background
+    editor.handle_command(Some(CommandKey::Find(String::new())));
+    assert!(editor.picker.is_none());
+    editor.finish_task(task_listener.recv().await.unwrap());
+    for c in "edito".chars() {
+        editor.key_press(key(c));
+    }
+    let picker = editor.picker.as_ref().unwrap();
+    assert_eq!(picker.selected_item().unwrap().label, "src/editor.rs");
+    assert!(picker.preview.as_ref().unwrap().1.iter().any(|l| l.contains("editor_core")));
+
+    editor.key_press(enter);
+    assert!(editor.picker.is_none());
+    assert_eq!(editor.buffers.len(), 2);
+    assert!(editor.buffers[editor.buf_ptr].name().ends_with("editor.rs"));
+
+    editor.open_picker(PickerKind::Buffers);
+    editor.key_press(key('m'));
+    editor.key_press(key('a'));
+    editor.key_press(key('i'));
+    editor.key_press(enter);
+    assert!(editor.buffers[editor.buf_ptr].name().ends_with("main.rs"));
+
+    // marks keep their spot and can be picked
+    editor.parse(vec![String::from("j")]).unwrap();
+    editor.parse(vec![String::from("m"), String::from("a")]).unwrap();
+    editor.parse(vec![String::from("k")]).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].mode, editor_core::buffer::Mode::Normal);
+    editor.parse(vec![String::from("'"), String::from("a")]).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current.1, 1);
+
+    editor.parse(vec![String::from("k")]).unwrap();
+    editor.open_picker(PickerKind::Marks);
+    editor.key_press(enter);
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current.1, 1);
+
+    // commands from the history run again
+    editor.command.text = String::from("bufcount");
+    let command = editor.command.confirm();
+    editor.handle_command(command);
+    editor.set_message(None);
+    editor.open_picker(PickerKind::History);
+    editor.key_press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+    assert!(editor.picker.is_none() && editor.message.is_none());
+    editor.open_picker(PickerKind::History);
+    editor.key_press(enter);
+    assert_eq!(editor.message.as_deref(), Some("2 open buffers"));
+}
+
+#[tokio::test]
+async fn test_grep_quickfix() {
+    use crate::editor::Focus;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_core::command::Command;
+
+    let (mut editor, dir, mut task_listener) = editor_in(&[("a.txt", "foo one\nbar\n  foo two foo\n"), ("b.txt", "foo three\n")]);
+
+    let grep = |editor: &mut Editor, text: &str| {
+        editor.handle_command(Command::parse(&format!("grep {} {}", text, dir.display())));
+    };
+
+    grep(&mut editor, "nothing");
+    editor.finish_task(task_listener.recv().await.unwrap());
+    assert!(editor.quickfix.is_none());
+    assert_eq!(editor.message.as_deref(), Some("No matches for /nothing/"));
+
+    grep(&mut editor, "fo+");
+    editor.finish_task(task_listener.recv().await.unwrap());
+    assert_eq!(editor.message.as_deref(), Some("3 matches for /fo+/"));
+    assert!(editor.show_quickfix);
+    let list = editor.quickfix.as_ref().unwrap();
+    assert_eq!((list.entries[1].line, list.entries[1].col), (2, 2));
+    assert!(editor.buffers[editor.buf_ptr].name().ends_with("a.txt"));
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (0, 0));
+
+    editor.handle_command(Command::parse("cn"));
+    let buffer = &editor.buffers[editor.buf_ptr];
+    assert!(buffer.name().ends_with("a.txt"));
+    assert_eq!(buffer.cursor.current, (2, 2));
+
+    editor.handle_command(Command::parse("cn"));
+    assert!(editor.buffers[editor.buf_ptr].name().ends_with("b.txt"));
+    editor.handle_command(Command::parse("cn"));
+    assert_eq!(editor.message.as_deref(), Some("No more items"));
+    editor.handle_command(Command::parse("cprev"));
+    assert!(editor.buffers[editor.buf_ptr].name().ends_with("a.txt"));
+
+    // the list can be focused and walked like a window
+    editor.handle_command(Command::parse("copen"));
+    assert_eq!(editor.focus, Focus::Quickfix);
+    editor.key_press(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE));
+    editor.key_press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    assert_eq!(editor.focus, Focus::Buffer);
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (0, 0));
+
+    editor.handle_command(Command::parse("cdo s/foo/baz/g"));
+    assert_eq!(editor.message.as_deref(), Some("Ran \"s/foo/baz/g\" on 3 entries"));
+    let text = |editor: &Editor, name: &str| {
+        let buffer = editor.buffers.iter().find(|b| b.name().ends_with(name)).unwrap();
+        buffer.lines.rope.to_string()
+    };
+    assert_eq!(text(&editor, "a.txt"), "baz one\nbar\n  baz two baz\n");
+    assert_eq!(text(&editor, "b.txt"), "baz three\n");
+
+    editor.handle_command(Command::parse("cclose"));
+    assert!(!editor.show_quickfix);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_make() {
+    use editor_core::command::Command;
+    use editor_core::quickfix::Severity;
+
+    let (mut editor, dir, mut task_listener) = editor_in(&[("main.rs", "fn main() {\n    let x = y;\n}\n")]);
+    let file = dir.join("main.rs");
+    editor.edit(&file.to_string_lossy());
+
+    // stands in for the compiler
+    let output = format!("{0}:2:13: error[E0425]: cannot find value `y`\n{0}:2:9: warning: unused variable: `x`", file.display());
+    editor.handle_command(Command::parse(&format!("makeprg printf '{}\\n'", output)));
+    editor.handle_command(Command::parse("make"));
+    editor.finish_task(task_listener.recv().await.unwrap());
+
+    assert!(editor.message.as_deref().unwrap().ends_with(": 1 error, 1 warning"));
+    assert_eq!(editor.quickfix.as_ref().unwrap().entries.len(), 2);
+    let signs = editor.buffers[editor.buf_ptr].diagnostics.signs();
+    assert_eq!(signs.get(&1), Some(&Severity::Error));
+    assert_eq!(signs.len(), 1);
+
+    editor.handle_command(Command::parse("cnext"));
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (8, 1));
+
+    // a clean build clears the marks, a failed one shows what it printed
+    editor.handle_command(Command::parse("makeprg true"));
+    editor.handle_command(Command::parse("make"));
+    editor.finish_task(task_listener.recv().await.unwrap());
+    assert_eq!(editor.message.as_deref(), Some("true: finished"));
+    assert!(editor.buffers[editor.buf_ptr].diagnostics.is_empty() && !editor.show_quickfix);
+
+    editor.handle_command(Command::parse("makeprg echo broken; false"));
+    editor.handle_command(Command::parse("make"));
+    editor.finish_task(task_listener.recv().await.unwrap());
+    assert_eq!(editor.message.as_deref(), Some("echo broken; false: failed\nbroken"));
+
+    let output = format!("{0}:1:1: error: one\n{0}:2:1: error: two", file.display());
+    editor.handle_command(Command::parse(&format!("makeprg printf '{}\\n'", output)));
+    editor.handle_command(Command::parse("make"));
+    editor.finish_task(task_listener.recv().await.unwrap());
+    assert!(editor.message.as_deref().unwrap().ends_with(": 2 errors, 0 warnings"));
+
+    editor.handle_command(Command::parse("efm %m"));
+    assert_eq!(editor.message.as_deref(), Some("Errorformat needs %f and %l: %m"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_terminal() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_core::buffer::Mode;
+    use editor_core::command::Command;
+
+    let (mut editor, _) = new_editor();
+    editor.resize((40, 10));
+
+    let wait = |editor: &Editor, done: &dyn Fn(&editor_core::terminal::Terminal) -> bool| {
+        for _ in 0..300 {
+            if editor.buffers[editor.buf_ptr].terminal.as_ref().is_some_and(done) {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("terminal never got there");
+    };
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+
+    editor.handle_command(Command::parse("term printf 'one\\ntwo\\n'; read x; echo got $x"));
+    assert!(editor.buffers[editor.buf_ptr].name().starts_with("term://printf"));
+    assert_eq!(editor.buffers[editor.buf_ptr].mode, Mode::Insert);
+    wait(&editor, &|t| t.text().0.contains("two"));
+
+    // ctrl-\ ctrl-n leaves the terminal, the output can be moved through and yanked
+    editor.key_press(ctrl('\\'));
+    editor.key_press(ctrl('n'));
+    let buffer = &mut editor.buffers[editor.buf_ptr];
+    assert_eq!(buffer.mode, Mode::Normal);
+    assert!(buffer.lines.rope.to_string().starts_with("one\ntwo\n"));
+    assert!(!buffer.is_modified());
+    buffer.jump_to(1, 0);
+    editor.parse(vec![String::from("y"), String::from("y")]).unwrap();
+    assert_eq!(editor.register.as_deref(), Some("two\n"));
+
+    editor.parse(vec![String::from("i")]).unwrap();
+    for c in "hi".chars() {
+        editor.key_press(key(c));
+    }
+    editor.key_press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    wait(&editor, &|t| t.has_exited());
+
+    editor.key_press(key('x'));
+    assert_eq!(editor.buffers[editor.buf_ptr].mode, Mode::Normal);
+    assert_eq!(editor.message.as_deref(), Some("Process exited with 0"));
+    assert!(editor.buffers[editor.buf_ptr].lines.rope.to_string().contains("got hi\n"));
+    assert!(editor.file_display().ends_with("[exited]"));
+
+    // yanked lines go into files
+    editor.handle_command(Some(editor_core::command::CommandKey::EditNew));
+    editor.parse(vec![String::from("p")]).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.to_string(), "\ntwo\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (0, 1));
+}
+
+#[tokio::test]
+async fn test_shell_filters() {
+    use editor_core::buffer::Mode;
+    use editor_core::command::Command;
+
+    let (mut editor, dir, _) = editor_in(&[("list.txt", "c\nb\na\nd\n")]);
+    let file = dir.join("list.txt");
+    editor.edit(&file.to_string_lossy());
+    let text = |editor: &Editor| editor.buffers[editor.buf_ptr].lines.rope.to_string();
+
+    // :! only reports what the command printed
+    editor.handle_command(Command::parse("!echo one; echo two"));
+    assert_eq!(editor.message.as_deref(), Some("one\ntwo\n"));
+    assert_eq!(text(&editor), "c\nb\na\nd\n");
+
+    editor.handle_command(Command::parse("1,3!sort"));
+    assert_eq!(text(&editor), "a\nb\nc\nd\n");
+    assert_eq!(editor.message.as_deref(), Some("3 lines filtered"));
+    assert!(editor.buffers[editor.buf_ptr].undo());
+    assert_eq!(text(&editor), "c\nb\na\nd\n");
+
+    // a failing command leaves the lines alone
+    editor.handle_command(Command::parse("%!echo oops >&2; exit 3"));
+    assert_eq!(text(&editor), "c\nb\na\nd\n");
+    assert_eq!(editor.message.as_deref(), Some("oops\nshell returned 3"));
+
+    // a read goes below the cursor's line, or the last line of a range
+    editor.handle_command(Command::parse("1r !echo e"));
+    assert_eq!(text(&editor), "c\ne\nb\na\nd\n");
+    assert!(editor.buffers[editor.buf_ptr].undo());
+    editor.handle_command(Command::parse("9r !echo e"));
+    assert_eq!(editor.message.as_deref(), Some("Invalid range"));
+    assert_eq!(text(&editor), "c\nb\na\nd\n");
+    editor.buffers[editor.buf_ptr].jump_to(3, 0);
+    editor.handle_command(Command::parse("r !echo e"));
+    assert_eq!(text(&editor), "c\nb\na\nd\ne\n");
+
+    // !{motion} fills in the range of lines it moved over
+    editor.buffers[editor.buf_ptr].jump_to(0, 0);
+    editor.parse(vec![String::from("!"), String::from("j")]).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].mode, Mode::Command);
+    assert_eq!(editor.command.text, ".,.+1!");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (0, 0));
+
+    editor.change_mode(Mode::Normal);
+    editor.parse(vec![String::from("!"), String::from("!")]).unwrap();
+    assert_eq!(editor.command.text, ".!");
+    editor.change_mode(Mode::Normal);
+    editor.handle_command(Command::parse(".,.+1!tr a-z A-Z"));
+    assert_eq!(text(&editor), "C\nB\na\nd\ne\n");
+}
+
+#[tokio::test]
+async fn test_format_on_save() {
+    use editor_core::command::Command;
+
+    let (mut editor, dir, mut task_listener) = editor_in(&[("notes.txt", "one   two\nthree\n")]);
+    let file = dir.join("notes.txt");
+    editor.edit(&file.to_string_lossy());
+
+    editor.handle_command(Command::parse("formatprg"));
+    assert_eq!(editor.message.as_deref(), Some("formatprg=off"));
+    editor.handle_command(Command::parse("format"));
+    assert_eq!(editor.message.as_deref(), Some("No formatter for this file"));
+
+    // the cursor stays on the same word after the spaces are squeezed
+    editor.handle_command(Command::parse("formatprg tr -s ' '"));
+    editor.buffers[editor.buf_ptr].jump_to(0, 7);
+    editor.handle_command(Command::parse("w"));
+    editor.finish_task(task_listener.recv().await.unwrap());
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "one two\nthree\n");
+    let buffer = &mut editor.buffers[editor.buf_ptr];
+    assert_eq!(buffer.cursor.current, (5, 0));
+    assert!(!buffer.is_modified());
+
+    // formatting is a single undo step
+    assert!(buffer.undo());
+    assert_eq!(buffer.lines.rope.to_string(), "one   two\nthree\n");
+    assert!(!buffer.undo());
+
+    // a failing formatter stops the write
+    editor.handle_command(Command::parse("formatprg echo 'bad input' >&2; exit 2"));
+    editor.handle_command(Command::parse("w"));
+    editor.finish_task(task_listener.recv().await.unwrap());
+    assert_eq!(editor.message.as_deref(), Some("bad input\nformatter returned 2"));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "one two\nthree\n");
+    assert!(editor.buffers[editor.buf_ptr].is_modified());
+
+    editor.handle_command(Command::parse("formatprg off"));
+    editor.handle_command(Command::parse("w"));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "one   two\nthree\n");
+
+    // writing to another file formats as well, with that file's path for %
+    editor.handle_command(Command::parse("formatprg sh -c 'basename \"$1\"' sh %"));
+    editor.handle_command(Command::parse(&format!("w {}", dir.join("copy.txt").display())));
+    editor.finish_task(task_listener.recv().await.unwrap());
+    assert_eq!(std::fs::read_to_string(dir.join("copy.txt")).unwrap(), "copy.txt\n");
+
+    // text typed while the formatter runs is written without its output
+    editor.handle_command(Command::parse("formatprg tr a-z A-Z"));
+    editor.handle_command(Command::parse("w"));
+    editor.buffers[editor.buf_ptr].begin_edit();
+    editor.buffers[editor.buf_ptr].lines.rope.insert(0, "x");
+    editor.buffers[editor.buf_ptr].end_edit();
+    editor.finish_task(task_listener.recv().await.unwrap());
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "xcopy.txt\n");
+}
+
+#[tokio::test]
+async fn test_file_type() {
+    use editor_core::command::Command;
+
+    let (mut editor, dir, _) = editor_in(&[("run", "#!/usr/bin/env python3\nprint('hi')\n# vim: sw=2\n")]);
+    let file = dir.join("run");
+    editor.edit(&file.to_string_lossy());
+
+    editor.handle_command(Command::parse("filetype"));
+    assert_eq!(editor.message.as_deref(), Some("filetype=python"));
+    let options = &editor.buffers[editor.buf_ptr].options;
+    assert_eq!((options.indent_width, options.expand_tab, options.comment.as_deref()), (2, true, Some("#")));
+
+    // setting the type by hand brings its options along
+    editor.handle_command(Command::parse("setf make"));
+    let options = &editor.buffers[editor.buf_ptr].options;
+    assert_eq!((options.indent_width, options.expand_tab), (8, false));
+    editor.handle_command(Command::parse("ft cobol"));
+    assert_eq!(editor.message.as_deref(), Some("Unknown filetype: cobol"));
+
+    // a new name is looked at again
+    editor.handle_command(Command::parse(&format!("saveas {}", dir.join("Cargo.lock").display())));
+    editor.handle_command(Command::parse("ft"));
+    assert_eq!(editor.message.as_deref(), Some("filetype=toml"));
+
+    // what was set by hand stays through a change of type
+    editor.handle_command(Command::parse("set sw=3"));
+    editor.handle_command(Command::parse("formatprg cat"));
+    editor.handle_command(Command::parse("setf rust"));
+    let options = &editor.buffers[editor.buf_ptr].options;
+    assert_eq!((options.indent_width, options.formatter.as_deref(), options.comment.as_deref()), (3, Some("cat"), Some("//")));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_diagnostics() {
+    use editor_core::command::Command;
+    use editor_core::quickfix::Severity;
+
+    let (mut editor, dir, mut task_listener) = editor_in(&[("main.rs", "fn main() {\n    let x = y;\n    let z = w;\n}\n")]);
+    let file = dir.join("main.rs");
+    editor.edit(&file.to_string_lossy());
+
+    editor.action_func("]", "d");
+    assert_eq!(editor.message.as_deref(), Some("No diagnostics"));
+
+    let output = format!("{0}:2:13: error: cannot find value `y`\n{0}:3:9: warning: unused variable: `z`", file.display());
+    editor.handle_command(Command::parse(&format!("makeprg printf '{}\\n'", output)));
+    editor.handle_command(Command::parse("make"));
+    editor.finish_task(task_listener.recv().await.unwrap());
+
+    // the ranges cover the word each message points at
+    let buffer = &editor.buffers[editor.buf_ptr];
+    let rope = &buffer.lines.rope;
+    let all = buffer.diagnostics.all();
+    assert_eq!(rope.slice(all[0].start..all[0].end), "y");
+    assert_eq!(rope.slice(all[1].start..all[1].end), "z");
+
+    // ]d and [d go around the buffer and show the message
+    editor.action_func("]", "d");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (12, 1));
+    assert_eq!(editor.float.as_deref(), Some("E error: cannot find value `y`"));
+    editor.action_func("]", "d");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (8, 2));
+    editor.action_func("]", "d");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (12, 1));
+    editor.action_func("[", "d");
+    assert_eq!(editor.float.as_deref(), Some("W warning: unused variable: `z`"));
+
+    // edits above move them along
+    let buffer = &mut editor.buffers[editor.buf_ptr];
+    buffer.begin_edit();
+    buffer.lines.rope.insert(0, "// top\n");
+    buffer.end_edit();
+    editor.sync_diagnostics();
+    let signs = editor.buffers[editor.buf_ptr].diagnostics.signs();
+    assert_eq!(signs.into_iter().collect::<Vec<_>>(), vec![(2, Severity::Error), (3, Severity::Warning)]);
+}
+
+#[tokio::test]
+async fn test_language_server() {
+    use editor_core::command::Command;
+    use editor_core::buffer::Mode;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use editor_lsp::{mock::MockServer, protocol::find_root};
+    use serde_json::json;
+
+    let (mut editor, dir, mut tasks) = editor_in(&[("Cargo.toml", "[package]\nname = \"demo\"\n"), ("src/main.rs", "fn main() {\n    helper();\n}\n"), ("src/lib.rs", "pub fn helper() {}\n")]);
+    let main_uri = format!("file://{}", dir.join("src/main.rs").display());
+    let lib_uri = format!("file://{}", dir.join("src/lib.rs").display());
+    editor.edit(&dir.join("src/main.rs").to_string_lossy());
+
+    editor.handle_command(Command::parse("hover"));
+    assert_eq!(editor.message.as_deref(), Some("No language server for this buffer"));
+
+    // the open buffer is sent to a server for its project once there is one
+    let root = find_root(&dir.join("src/main.rs"), &["Cargo.toml"]);
+    assert_eq!(root, *dir);
+    let (client, mut server) = MockServer::connect("rust-analyzer", &root, editor.lsp_sink("rust-analyzer", &root));
+    editor.attach_server(client);
+
+    let init = server.expect("initialize").await;
+    let capabilities = json!({ "textDocumentSync": 2, "hoverProvider": true, "referencesProvider": true, "renameProvider": true,
+        "completionProvider": { "triggerCharacters": ["."] } });
+    server.respond(&init, json!({ "capabilities": capabilities })).await;
+    editor.finish_task(tasks.recv().await.unwrap());
+    server.expect("initialized").await;
+    let open = server.expect("textDocument/didOpen").await;
+    assert_eq!(open["params"]["textDocument"]["languageId"], "rust");
+
+    // what the server finds shows up in the buffer
+    server.notify("textDocument/publishDiagnostics", json!({ "uri": main_uri, "diagnostics": [{
+        "range": { "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 10 } },
+        "severity": 1, "source": "rustc", "message": "cannot find function `helper`",
+    }] })).await;
+    editor.finish_task(tasks.recv().await.unwrap());
+    let diagnostics = &editor.buffers[editor.buf_ptr].diagnostics;
+    assert_eq!(diagnostics.all()[0].display(), "E rustc: cannot find function `helper`");
+    assert_eq!((diagnostics.all()[0].start, diagnostics.all()[0].end), (16, 22));
+
+    editor.handle_command(Command::parse("hover"));
+    let hover = server.expect("textDocument/hover").await;
+    server.respond(&hover, json!({ "contents": "fn main()" })).await;
+    editor.finish_task(tasks.recv().await.unwrap());
+    assert_eq!(editor.message.as_deref(), Some("fn main()"));
+
+    // references go in the quickfix list
+    editor.handle_command(Command::parse("refs"));
+    let refs = server.expect("textDocument/references").await;
+    server.respond(&refs, json!([
+        { "uri": main_uri, "range": { "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 10 } } },
+        { "uri": lib_uri, "range": { "start": { "line": 0, "character": 7 }, "end": { "line": 0, "character": 13 } } },
+    ])).await;
+    editor.finish_task(tasks.recv().await.unwrap());
+    assert_eq!(editor.message.as_deref(), Some("2 references"));
+    let list = editor.quickfix.as_ref().unwrap();
+    assert_eq!((list.entries[0].line, list.entries[0].col, list.entries[0].text.as_str()), (1, 4, "helper();"));
+    assert_eq!((list.entries[1].line, list.entries[1].col), (0, 7));
+
+    // a rename edits files that aren't open too, without leaving the current one
+    editor.handle_command(Command::parse("lsprename assist"));
+    let rename = server.expect("textDocument/rename").await;
+    assert_eq!(rename["params"]["newName"], "assist");
+    server.respond(&rename, json!({ "changes": {
+        main_uri.clone(): [{ "range": { "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 10 } }, "newText": "assist" }],
+        lib_uri.clone(): [{ "range": { "start": { "line": 0, "character": 7 }, "end": { "line": 0, "character": 13 } }, "newText": "assist" }],
+    } })).await;
+    editor.finish_task(tasks.recv().await.unwrap());
+    assert_eq!(editor.message.as_deref(), Some("Renamed in 2 files"));
+    assert_eq!(editor.buffers.len(), 2);
+    assert_eq!(editor.buffers[editor.buf_ptr].file.as_deref(), Some(dir.join("src/main.rs").as_path()));
+    assert_eq!(editor.buffers[0].lines.rope.to_string(), "fn main() {\n    assist();\n}\n");
+    assert_eq!(editor.buffers[1].lines.rope.to_string(), "pub fn assist() {}\n");
+
+    // the new buffer is opened on the server and both edits are sent
+    server.expect("textDocument/didOpen").await;
+    let change = server.expect("textDocument/didChange").await;
+    assert_eq!(change["params"]["contentChanges"][0]["text"], "assist");
+
+    // a trigger char asks the server for completions, which fill the popup when they come
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    editor.buffers[editor.buf_ptr].jump_to(1, 10);
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Insert);
+    editor.key_press(key(KeyCode::Char('.')));
+    let completion = loop {
+        let message = server.recv().await.unwrap();
+        if message["method"] == "textDocument/completion" {
+            break message;
+        }
+    };
+    assert_eq!(completion["params"]["position"], json!({ "line": 1, "character": 11 }));
+    server.respond(&completion, json!([{ "label": "len", "detail": "fn() -> usize", "documentation": "Returns the length" }])).await;
+    editor.finish_task(tasks.recv().await.unwrap());
+    let item = editor.completion.as_ref().and_then(|c| c.selected_item()).unwrap();
+    assert_eq!(item.documentation.as_deref(), Some("Returns the length"));
+
+    editor.key_press(key(KeyCode::Char('l')));
+    editor.key_press(key(KeyCode::Tab));
+    assert!(editor.completion.is_none());
+    assert_eq!(editor.buffers[0].lines.rope.line(1).to_string(), "    assist.len();\n");
+
+    // an item's textEdit says what it replaces, here from before the word to past the cursor
+    editor.key_press(key(KeyCode::Char('.')));
+    let completion = loop {
+        let message = server.recv().await.unwrap();
+        if message["method"] == "textDocument/completion" {
+            break message;
+        }
+    };
+    let range = json!({ "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 17 } });
+    server.respond(&completion, json!([{ "label": "not", "textEdit": { "range": range, "newText": "!assist.len()" } }])).await;
+    editor.finish_task(tasks.recv().await.unwrap());
+    editor.key_press(key(KeyCode::Tab));
+    assert_eq!(editor.buffers[0].lines.rope.line(1).to_string(), "    !assist.len();\n");
+
+    // quitting stops the server
+    editor.shutdown_servers().await;
+    let shutdown = loop {
+        let message = server.recv().await.unwrap();
+        if message["method"] == "shutdown" {
+            break message;
+        }
+    };
+    server.respond(&shutdown, json!(null)).await;
+    server.expect("exit").await;
+}
+
+#[tokio::test]
+async fn test_completion() {
+    use editor_core::buffer::Mode;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let (mut editor, dir, _) = editor_in(&[
+        ("main.rs", "fn main() {\n    let counter = 1;\n    \n}\n"),
+        ("other.rs", "fn compute_total() {}\n"),
+        ("src/editor.rs", ""),
+        ("src/explorer.rs", ""),
+    ]);
+    editor.edit(&dir.join("other.rs").to_string_lossy());
+    editor.edit(&dir.join("main.rs").to_string_lossy());
+
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    editor.buffers[editor.buf_ptr].jump_to(2, 4);
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Insert);
+
+    // words come from every open buffer
+    editor.key_press(key('c'));
+    editor.key_press(key('o'));
+    editor.key_press(ctrl('n'));
+    let completion = editor.completion.as_ref().unwrap();
+    let labels: Vec<&str> = completion.matches.iter().map(|m| completion.items[m.item].label.as_str()).collect();
+    assert_eq!(labels, vec!["counter", "compute_total"]);
+
+    // typing narrows them, ctrl-n and ctrl-p pick one
+    editor.key_press(key('m'));
+    assert_eq!(editor.completion.as_ref().unwrap().matches.len(), 1);
+    editor.key_press(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+    editor.key_press(ctrl('n'));
+    editor.key_press(ctrl('p'));
+    editor.key_press(ctrl('p'));
+    editor.key_press(ctrl('y'));
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(2).to_string(), "    compute_total\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (17, 2));
+
+    // a slash after a directory lists what is in it, relative to the buffer's directory, which
+    // for a file is the working directory, so the test moves it
+    editor.buffers[editor.buf_ptr].parent_dir = Some(dir.to_path_buf());
+    editor.key_press(key(' '));
+    assert!(editor.completion.is_none());
+    for c in "src/ed".chars() {
+        editor.key_press(key(c));
+    }
+    let completion = editor.completion.as_ref().unwrap();
+    assert_eq!(completion.selected_item().unwrap().label, "editor.rs");
+    editor.key_press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(2).to_string(), "    compute_total src/editor.rs\n");
+
+    // escape closes it along with insert mode
+    editor.key_press(ctrl('n'));
+    editor.key_press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+    assert!(editor.completion.is_none());
+}
+
+#[tokio::test]
+async fn test_snippet() {
+    use editor_core::buffer::Mode;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let (mut editor, dir, _) = editor_in(&[("main.rs", "impl Point {\n    \n}\n")]);
+    editor.edit(&dir.join("main.rs").to_string_lossy());
+
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+    let type_text = |editor: &mut Editor, text: &str| text.chars().for_each(|c| editor.key_press(key(c)));
+    let text = |editor: &Editor| editor.buffers[editor.buf_ptr].lines.rope.to_string();
+    editor.buffers[editor.buf_ptr].jump_to(1, 4);
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Insert);
+
+    // tab expands the word before the cursor, indented like the line it is on
+    type_text(&mut editor, "fn");
+    editor.key_press(tab);
+    assert_eq!(text(&editor), "impl Point {\n    fn name() -> () {\n        \n    }\n}\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (7, 1));
+
+    // typing replaces a placeholder, tab goes on to the next tabstop
+    type_text(&mut editor, "add");
+    editor.key_press(tab);
+    type_text(&mut editor, "x: i32");
+    editor.key_press(tab);
+    editor.key_press(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+    assert_eq!(text(&editor), "impl Point {\n    fn add(x: i32) {\n        \n    }\n}\n");
+    editor.key_press(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT));
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (11, 1));
+    editor.key_press(tab);
+    editor.key_press(tab);
+    editor.key_press(tab);
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (8, 2));
+    assert!(editor.buffers[editor.buf_ptr].snippet.is_none());
+
+    // mirrors follow what is typed in the tabstop they copy
+    type_text(&mut editor, "new");
+    editor.key_press(tab);
+    type_text(&mut editor, "id");
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(3).to_string(), "            Self { id }\n");
+
+    // a choice is picked from the popup, snippets are in it along with words
+    editor.key_press(tab);
+    editor.key_press(tab);
+    editor.key_press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    type_text(&mut editor, "der");
+    editor.key_press(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL));
+    assert_eq!(editor.completion.as_ref().unwrap().selected_item().unwrap().label, "derive");
+    editor.key_press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    let completion = editor.completion.as_ref().unwrap();
+    assert_eq!(completion.matches.len(), 5);
+    editor.key_press(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL));
+    editor.key_press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    assert!(text(&editor).contains("Self { id }\n        }\n        #[derive(Clone)]\n"));
+}
+
+#[tokio::test]
+async fn test_indent() {
+    use editor_core::{buffer::Mode, command::Command};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let (mut editor, dir, _) = editor_in(&[("main.rs", "fn main() {\nlet x = 1;\n\tlet y = 2;\n}\n")]);
+    editor.edit(&dir.join("main.rs").to_string_lossy());
+
+    let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+    let backspace = KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE);
+    let line = |editor: &Editor, n: usize| editor.buffers[editor.buf_ptr].lines.rope.line(n).to_string();
+    let keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<String>>();
+
+    // tabs are expanded to the next soft tab stop and taken out again a stop at a time
+    editor.buffers[editor.buf_ptr].jump_to(1, 0);
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Insert);
+    editor.key_press(tab);
+    editor.key_press(tab);
+    assert_eq!(line(&editor, 1), "        let x = 1;\n");
+    editor.key_press(backspace);
+    assert_eq!(line(&editor, 1), "    let x = 1;\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (4, 1));
+    editor.key_press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+
+    // a tab char takes up to the next tab stop on screen
+    editor.buffers[editor.buf_ptr].jump_to(2, 1);
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor_col(), 8);
+    editor.buffers[editor.buf_ptr].jump_to(2, 0);
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor_col(), 7);
+
+    editor.parse(keys(&[">", ">"])).unwrap();
+    assert_eq!(line(&editor, 2), "            let y = 2;\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (12, 2));
+    editor.parse(keys(&["<", "<"])).unwrap();
+    assert_eq!(line(&editor, 2), "        let y = 2;\n");
+
+    // with tabs kept, = indents for the code above with them and counts take more lines
+    editor.handle_command(Command::parse("set noet ts=4 sw=4 sts=0"));
+    editor.handle_command(Command::parse("set ts? et?"));
+    assert_eq!(editor.message.as_deref(), Some("ts=4 noexpandtab"));
+    editor.parse(keys(&["=", "j"])).unwrap();
+    assert_eq!((line(&editor, 2), line(&editor, 3)), (String::from("\tlet y = 2;\n"), String::from("}\n")));
+    editor.buffers[editor.buf_ptr].jump_to(0, 0);
+    editor.parse(keys(&["3", ">", ">"])).unwrap();
+    assert_eq!(editor.message.as_deref(), Some("3 lines indented"));
+    assert_eq!(line(&editor, 1), "\t\tlet x = 1;\n");
+    editor.parse(keys(&["<", "j"])).unwrap();
+    assert_eq!((line(&editor, 0), line(&editor, 1)), (String::from("fn main() {\n"), String::from("\tlet x = 1;\n")));
+
+    // each operator is one undo step
+    editor.parse(keys(&["u"])).unwrap();
+    assert_eq!(line(&editor, 0), "\tfn main() {\n");
+
+    editor.buffers[editor.buf_ptr].jump_to(3, 0);
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Insert);
+    editor.key_press(tab);
+    assert_eq!(line(&editor, 3), "\t}\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor_col(), 4);
+}
+
+#[tokio::test]
+async fn test_auto_indent() {
+    use editor_core::{buffer::Mode, command::Command};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let (mut editor, dir, _) = editor_in(&[("main.rs", "fn main() {}\n"), ("lib.rs", "fn a() {\nlet x = [\n1,\n    ];\n        }\n\nfn b() {\n}\n"), ("run.py", "def f():\n    pass\n")]);
+    editor.edit(&dir.join("main.rs").to_string_lossy());
+
+    let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+    let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+    let type_text = |editor: &mut Editor, text: &str| text.chars().for_each(|c| editor.key_press(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)));
+    let text = |editor: &Editor| editor.buffers[editor.buf_ptr].lines.rope.to_string();
+    let keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<String>>();
+
+    // closers are typed by hand here
+    editor.handle_command(Command::parse("set noap"));
+
+    // enter between brackets puts the closer a line down and the cursor a level in
+    editor.buffers[editor.buf_ptr].jump_to(0, 11);
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Insert);
+    editor.key_press(enter);
+    assert_eq!(text(&editor), "fn main() {\n    \n}\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (4, 1));
+
+    // a closer typed on a line of its own goes back a level
+    type_text(&mut editor, "if x {");
+    editor.key_press(enter);
+    type_text(&mut editor, "y();");
+    editor.key_press(enter);
+    type_text(&mut editor, "}");
+    assert_eq!(text(&editor), "fn main() {\n    if x {\n        y();\n    }\n}\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (5, 3));
+    editor.key_press(esc);
+
+    // o and O open lines indented for where they go
+    editor.buffers[editor.buf_ptr].jump_to(1, 0);
+    editor.parse(keys(&["o"])).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(2).to_string(), "        \n");
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (8, 2));
+    editor.key_press(esc);
+    editor.buffers[editor.buf_ptr].jump_to(5, 0);
+    editor.parse(keys(&["O"])).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(5).to_string(), "    \n");
+
+    editor.key_press(esc);
+    editor.edit(&dir.join("run.py").to_string_lossy());
+    editor.parse(keys(&["o"])).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].cursor.current, (4, 1));
+    editor.key_press(esc);
+
+    // =ip indents the paragraph under the cursor, == just the line
+    editor.edit(&dir.join("lib.rs").to_string_lossy());
+    editor.buffers[editor.buf_ptr].jump_to(1, 0);
+    editor.parse(keys(&["=", "="])).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(1).to_string(), "    let x = [\n");
+    editor.parse(keys(&["=", "ip"])).unwrap();
+    assert_eq!(text(&editor), "fn a() {\n    let x = [\n        1,\n    ];\n}\n\nfn b() {\n}\n");
+    assert_eq!(editor.message.as_deref(), Some("5 lines reindented"));
+}
+
+#[tokio::test]
+async fn test_auto_pairs() {
+    use editor_core::buffer::Mode;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let (mut editor, dir, _) = editor_in(&[("main.rs", "\n"), ("main.py", "\n")]);
+    editor.edit(&dir.join("main.rs").to_string_lossy());
+
+    let backspace = KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE);
+    let type_text = |editor: &mut Editor, text: &str| text.chars().for_each(|c| editor.key_press(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)));
+    let text = |editor: &Editor| editor.buffers[editor.buf_ptr].lines.rope.to_string();
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Insert);
+
+    // closers come with their openers and are typed over
+    type_text(&mut editor, "fn f(x: &[u8]");
+    assert_eq!(text(&editor), "fn f(x: &[u8])\n");
+    type_text(&mut editor, ") {");
+    editor.key_press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    type_text(&mut editor, "g(\"a");
+    assert_eq!(text(&editor), "fn f(x: &[u8]) {\n    g(\"a\")\n}\n");
+
+    // backspace in an empty pair takes out both halves, elsewhere just the one char
+    type_text(&mut editor, "\", \"");
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(1).to_string(), "    g(\"a\", \"\")\n");
+    editor.key_press(backspace);
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(1).to_string(), "    g(\"a\", )\n");
+    (0..6).for_each(|_| editor.key_press(backspace));
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(1).to_string(), "    g\n");
+
+    // rust lifetimes don't get a closing quote, other types do but not in a word
+    type_text(&mut editor, "::<'a>");
+    assert_eq!(editor.buffers[editor.buf_ptr].lines.rope.line(1).to_string(), "    g::<'a>\n");
+    editor.key_press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+    editor.edit(&dir.join("main.py").to_string_lossy());
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Insert);
+    type_text(&mut editor, "x = f'it's");
+    editor.key_press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    type_text(&mut editor, "y = '");
+    assert_eq!(text(&editor), "x = f'it's\ny = ''\n");
+}
+
+#[tokio::test]
+async fn test_match_pairs() {
+    use editor_core::buffer::Mode;
+
+    let (mut editor, dir, _) = editor_in(&[("main.c", "int f(int a[2]) {\n#if X\n    g((a), b);\n#endif\n}\n")]);
+    editor.edit(&dir.join("main.c").to_string_lossy());
+
+    let keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<String>>();
+    let at = |editor: &Editor| {
+        let buffer = &editor.buffers[editor.buf_ptr];
+        (buffer.cursor.current.0, buffer.ptr_y + buffer.cursor.current.1)
+    };
+
+    // % goes from the first bracket on the line to its match and back
+    editor.parse(keys(&["%"])).unwrap();
+    assert_eq!(at(&editor), (14, 0));
+    editor.parse(keys(&["%"])).unwrap();
+    assert_eq!(at(&editor), (5, 0));
+    editor.buffers[editor.buf_ptr].jump_to(0, 16);
+    editor.parse(keys(&["%"])).unwrap();
+    assert_eq!(at(&editor), (0, 4));
+
+    // and between #if and #endif
+    editor.buffers[editor.buf_ptr].jump_to(1, 2);
+    editor.parse(keys(&["%"])).unwrap();
+    assert_eq!(at(&editor), (0, 3));
+    editor.parse(keys(&["%"])).unwrap();
+    assert_eq!(at(&editor), (0, 1));
+
+    // the pair under the cursor is marked, while inserting the bracket before it counts too
+    editor.buffers[editor.buf_ptr].jump_to(2, 5);
+    let start = editor.buffers[editor.buf_ptr].lines.rope.line_to_char(2);
+    assert_eq!(editor.buffers[editor.buf_ptr].bracket_pair(), Some((start + 5, start + 12)));
+    editor.buffers[editor.buf_ptr].jump_to(2, 6);
+    assert_eq!(editor.buffers[editor.buf_ptr].bracket_pair(), Some((start + 6, start + 8)));
+    editor.buffers[editor.buf_ptr].jump_to(2, 7);
+    assert_eq!(editor.buffers[editor.buf_ptr].bracket_pair(), None);
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Insert);
+    editor.buffers[editor.buf_ptr].jump_to(2, 7);
+    assert_eq!(editor.buffers[editor.buf_ptr].bracket_pair(), Some((start + 6, start + 8)));
+    editor.buffers[editor.buf_ptr].change_mode(Mode::Normal);
+
+    // [( and ]} go out to the brackets around the cursor, with a count further out
+    editor.buffers[editor.buf_ptr].jump_to(2, 7);
+    editor.parse(keys(&["2", "[", "("])).unwrap();
+    assert_eq!(at(&editor), (5, 2));
+    editor.parse(keys(&["]", "}"])).unwrap();
+    assert_eq!(at(&editor), (0, 4));
+    editor.parse(keys(&["[", "{"])).unwrap();
+    assert_eq!(at(&editor), (16, 0));
+    editor.parse(keys(&["[", "{"])).unwrap();
+    assert_eq!(editor.status_text().as_deref(), Some("No unmatched {"));
+
+    // a count makes % go that far through the file
+    editor.parse(keys(&["60", "%"])).unwrap();
+    assert_eq!(at(&editor), (0, 2));
+
+    // the range looked in reaches the end of the file
+    editor.buffers[editor.buf_ptr].lines.rope = ropey::Rope::from("x\n(y)\n");
+    editor.buffers[editor.buf_ptr].jump_to(1, 0);
+    assert_eq!(editor.buffers[editor.buf_ptr].bracket_pair(), Some((2, 4)));
+}
+
+#[tokio::test]
+async fn test_comment() {
+    use editor_core::buffer::Mode;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let code = "fn main() {\n    let x = 1;\n\n\tlet y = 2;\n}\n";
+
+    let (mut editor, dir, _) = editor_in(&[("main.rs", code), ("main.css", "a {}\n"), ("data.json", "{}\n")]);
+    editor.edit(&dir.join("main.rs").to_string_lossy());
+
+    let keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<String>>();
+    let text = |editor: &Editor| editor.buffers[editor.buf_ptr].lines.rope.to_string();
+
+    // gcc toggles the line, in and back out
+    editor.parse(keys(&["gc", "c"])).unwrap();
+    assert_eq!(text(&editor), code.replacen("fn", "// fn", 1));
+    editor.parse(keys(&["gc", "c"])).unwrap();
+    assert_eq!(text(&editor), code);
+
+    // a block lines up at its least indent over tabs and spaces, blank lines stay blank
+    editor.buffers[editor.buf_ptr].jump_to(1, 0);
+    editor.parse(keys(&["3", "gc", "c"])).unwrap();
+    assert_eq!(text(&editor), "fn main() {\n    // let x = 1;\n\n\t// let y = 2;\n}\n");
+    assert_eq!(editor.status_text().as_deref(), Some("3 lines commented"));
+    editor.buffers[editor.buf_ptr].jump_to(0, 0);
+    editor.parse(keys(&["gc", "ip"])).unwrap();
+    assert_eq!(text(&editor), "// fn main() {\n//     // let x = 1;\n\n\t// let y = 2;\n}\n");
+
+    // each toggle is one undo step
+    editor.parse(keys(&["u"])).unwrap();
+    assert_eq!(text(&editor), "fn main() {\n    // let x = 1;\n\n\t// let y = 2;\n}\n");
+    editor.parse(keys(&["u"])).unwrap();
+    assert_eq!(text(&editor), code);
+
+    // V selects lines from there to the cursor, gc comments them and leaves visual mode
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    editor.buffers[editor.buf_ptr].jump_to(1, 0);
+    editor.parse(keys(&["V"])).unwrap();
+    editor.parse(keys(&["2", "j"])).unwrap();
+    editor.parse(keys(&["k"])).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].visual_lines(), Some((1, 2)));
+    editor.key_press(key('g'));
+    editor.key_press(key('c'));
+    assert_eq!(text(&editor), "fn main() {\n    // let x = 1;\n\n\tlet y = 2;\n}\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].mode, Mode::Normal);
+    editor.parse(keys(&["u"])).unwrap();
+    assert_eq!(text(&editor), code);
+
+    // types without line comments get a block comment around each line
+    editor.edit(&dir.join("main.css").to_string_lossy());
+    editor.parse(keys(&["gc", "c"])).unwrap();
+    assert_eq!(text(&editor), "/* a {} */\n");
+
+    editor.edit(&dir.join("data.json").to_string_lossy());
+    editor.parse(keys(&["gc", "c"])).unwrap();
+    assert_eq!(text(&editor), "{}\n");
+    assert_eq!(editor.status_text().as_deref(), Some("No comment string for this file type"));
+}
+
+#[tokio::test]
+async fn test_text_changes() {
+    use editor_core::buffer::Mode;
+    use editor_core::{diagnostic::Diagnostic, quickfix::Severity};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let (mut editor, dir, _) = editor_in(&[("main.rs", "let Some_Word = 0x0f;\nlet y = f(\n    a,\n);\n")]);
+    editor.edit(&dir.join("main.rs").to_string_lossy());
+
+    let keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<String>>();
+    let key = |c: char| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    let line = |editor: &Editor, n: usize| editor.buffers[editor.buf_ptr].lines.rope.line(n).to_string();
+    let col = |editor: &Editor| editor.buffers[editor.buf_ptr].cursor.current.0;
+
+    // ~ flips count chars and moves past them, the case operators take motions and lines
+    editor.parse(keys(&["2", "~"])).unwrap();
+    assert_eq!((line(&editor, 0).as_str(), col(&editor)), ("LEt Some_Word = 0x0f;\n", 2));
+    editor.buffers[editor.buf_ptr].jump_to(0, 4);
+    editor.parse(keys(&["gU", "e"])).unwrap();
+    assert_eq!((line(&editor, 0).as_str(), col(&editor)), ("LEt SOME_Word = 0x0f;\n", 4));
+    editor.parse(keys(&["gu", "u"])).unwrap();
+    assert_eq!(line(&editor, 0), "let some_word = 0x0f;\n");
+    editor.parse(keys(&["g~", "~"])).unwrap();
+    assert_eq!(line(&editor, 0), "LET SOME_WORD = 0X0F;\n");
+    editor.parse(keys(&["u"])).unwrap();
+    assert_eq!(line(&editor, 0), "let some_word = 0x0f;\n");
+
+    // ctrl-a and ctrl-x go to the number under or after the cursor, counts add that much
+    editor.buffers[editor.buf_ptr].jump_to(0, 0);
+    editor.parse(keys(&["\u{1}"])).unwrap();
+    assert_eq!((line(&editor, 0).as_str(), col(&editor)), ("let some_word = 0x10;\n", 19));
+    editor.parse(keys(&["3", "\u{18}"])).unwrap();
+    assert_eq!(line(&editor, 0), "let some_word = 0x0d;\n");
+
+    // r swaps count chars under the cursor
+    editor.buffers[editor.buf_ptr].jump_to(0, 4);
+    editor.parse(keys(&["4", "r", "x"])).unwrap();
+    assert_eq!((line(&editor, 0).as_str(), col(&editor)), ("let xxxx_word = 0x0d;\n", 7));
+    editor.parse(keys(&["40", "r", "x"])).unwrap();
+    assert_eq!(line(&editor, 0), "let xxxx_word = 0x0d;\n");
+
+    // J takes out the indent and puts in a space, but not before a ), gJ keeps it all
+    editor.buffers[editor.buf_ptr].jump_to(1, 0);
+    editor.parse(keys(&["3", "J"])).unwrap();
+    assert_eq!((line(&editor, 1).as_str(), col(&editor)), ("let y = f( a,);\n", 13));
+    editor.parse(keys(&["u"])).unwrap();
+    editor.buffers[editor.buf_ptr].jump_to(1, 0);
+    editor.parse(keys(&["g", "J"])).unwrap();
+    assert_eq!(line(&editor, 1), "let y = f(    a,\n");
+
+    // R types over the text, past the end it adds to it, backspace puts back what was there
+    editor.buffers[editor.buf_ptr].jump_to(1, 14);
+    editor.parse(keys(&["R"])).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].mode, Mode::Replace);
+    "b, c".chars().for_each(|c| editor.key_press(key(c)));
+    assert_eq!(line(&editor, 1), "let y = f(    b, c\n");
+
+    // what backspace puts back or takes out moves the diagnostics after it
+    let below = |editor: &Editor| editor.buffers[editor.buf_ptr].lines.rope.line_to_char(2);
+    let found = vec![Diagnostic { start: below(&editor), end: below(&editor) + 1, severity: Severity::Error, message: String::from("e"), source: None }];
+    editor.buffers[editor.buf_ptr].set_diagnostics("test", found);
+    editor.key_press(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+    editor.key_press(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+    editor.key_press(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+    assert_eq!(line(&editor, 1), "let y = f(    b,\n");
+    editor.buffers[editor.buf_ptr].sync_diagnostics();
+    assert_eq!(editor.buffers[editor.buf_ptr].diagnostics.all()[0].start, below(&editor));
+    editor.key_press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+    assert_eq!(editor.buffers[editor.buf_ptr].mode, Mode::Normal);
+    editor.parse(keys(&["u"])).unwrap();
+    assert_eq!(line(&editor, 1), "let y = f(    a,\n");
+
+    // g ctrl-a in a visual block numbers the lines that have one in it, one step further each
+    let ctrl = |c: char| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    let text = |editor: &Editor| editor.buffers[editor.buf_ptr].lines.rope.to_string();
+    editor.buffers[editor.buf_ptr].lines.rope = ropey::Rope::from("x 1 0\nx 1 0\nx 1 -\nx 1 0\n");
+    editor.buffers[editor.buf_ptr].jump_to(0, 4);
+    editor.key_press(ctrl('v'));
+    editor.parse(keys(&["3", "j"])).unwrap();
+    assert_eq!(editor.buffers[editor.buf_ptr].visual_cols(), Some((4, 4)));
+    editor.key_press(key('g'));
+    editor.key_press(ctrl('a'));
+    assert_eq!(text(&editor), "x 1 1\nx 1 2\nx 1 -\nx 1 3\n");
+    assert_eq!(editor.buffers[editor.buf_ptr].mode, Mode::Normal);
+    assert_eq!((editor.buffers[editor.buf_ptr].cursor.current, col(&editor)), ((4, 0), 4));
+    editor.parse(keys(&["u"])).unwrap();
+    assert_eq!(text(&editor), "x 1 0\nx 1 0\nx 1 -\nx 1 0\n");
+
+    // without g every line goes the same way, a linewise selection takes the first number
+    editor.buffers[editor.buf_ptr].jump_to(0, 4);
+    editor.parse(keys(&["V"])).unwrap();
+    editor.parse(keys(&["j"])).unwrap();
+    editor.key_press(ctrl('x'));
+    assert_eq!(text(&editor), "x 0 0\nx 0 0\nx 1 -\nx 1 0\n");
+}
+
 #[test]
 fn test_start_and_close() {
     /*
@@ -31,7 +31,7 @@ use crossterm::terminal::{
 
 // combining the terminal and event
 // TODO: add these events
-#[warn(dead_code)]
+#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub enum Event{
     Init,
@@ -108,11 +108,10 @@ impl Tui {
                         match maybe_event {
                             Some(Ok(evt)) => {
                                 match evt {
-                                    crossterm::event::Event::Key(key) => {
-                                        if key.kind == crossterm::event::KeyEventKind::Press{
+                                    crossterm::event::Event::Key(key)
+                                        if key.kind == crossterm::event::KeyEventKind::Press=> {
                                             let _ = _event_tx.send(Event::Key(key));
-                                        }
-                                    },
+                                        },
                                     crossterm::event::Event::Resize(x,y) => {
                                         let _ = _event_tx.send(Event::Resize(x, y));
                                     },
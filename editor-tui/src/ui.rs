@@ -1,9 +1,14 @@
 use std::rc::Rc;
-use crate::{editor::Editor, Event, Tui};
+use crate::{
+    editor::{Editor, Focus},
+    widgets::{picker_rows, CompletionWidget, FloatWidget, PickerWidget, QuickfixWidget, TreeWidget},
+    Event,
+    Tui,
+};
 use editor_core::X_OFFSET;
 use editor_motion::MotionHandler;
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     widgets::{Block, Borders, Paragraph},
     prelude::Style,
     style::Stylize,
@@ -11,37 +16,69 @@ use ratatui::{
 };
 
 
-fn get_layouts(f: &mut Frame<'_>) -> (Rc<[Rect]>, Rc<[Rect]>) {
+type Layouts = (Rc<[Rect]>, Rc<[Rect]>, Rc<[Rect]>);
+
+fn get_layouts(f: &mut Frame<'_>, status_height: u16, tree_width: u16, quickfix_height: u16) -> Layouts {
     // wrapper_layout[0] is for the text and line numbers
-    // wrapper_layout[1] is for the command view
+    // wrapper_layout[1] is the quickfix list, zero high while it is closed
+    // wrapper_layout[2] is for the command view
     let wrapper_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints(vec![
                      Constraint::Min(1),
-                     Constraint::Length(2)
+                     Constraint::Length(quickfix_height),
+                     Constraint::Length(status_height + 1)
         ])
         .split(f.size());
 
+    // tree_layout[0] is the file tree, zero wide while it is hidden
+    let tree_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+                     Constraint::Length(tree_width),
+                     Constraint::Min(1)
+        ])
+        .split(wrapper_layout[0]);
+
     let num_text_layout = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(vec![
                      Constraint::Length((X_OFFSET - 1).try_into().unwrap()),
                      Constraint::Min(1)
         ])
-        .split(wrapper_layout[0]);
+        .split(tree_layout[1]);
 
-    return (wrapper_layout, num_text_layout);
+    (wrapper_layout, tree_layout, num_text_layout)
 }
 
 
 // TODO: fix this to allow motions
 pub fn ui(f: &mut Frame<'_>, editor: &mut Editor, motion: &mut MotionHandler){
-    let (wrapper_layout, num_text_layout) = get_layouts(f);
+    // multi line messages grow the command view, up to half the screen
+    let status_height = editor.status_text()
+        .map_or(1, |m| m.lines().count().max(1))
+        .min(usize::from(f.size().height / 2).max(1));
+    let tree_width = editor.resize_tree(f.size().width);
+    let quickfix_height = editor.quickfix_height(f.size().height);
+    let (wrapper_layout, tree_layout, num_text_layout) = get_layouts(f, status_height as u16, tree_width, quickfix_height);
     editor.resize((num_text_layout[1].width, num_text_layout[1].height));
 
+    let file_info = editor.file_display();
     let status = editor.mode_display();
     let motion = motion.get_text();
 
+    // file name and modified flag sit at the right of the status line
+    let status_file = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+                     Constraint::Min(1),
+                     Constraint::Length((file_info.len() + 1).try_into().unwrap_or(u16::MAX))
+        ])
+        .split(wrapper_layout[2]);
+    let file_info = Paragraph::new(file_info)
+        .alignment(Alignment::Right)
+        .block(Block::default().borders(Borders::TOP));
+
     match motion {
         Some(motion) => {
 
@@ -55,20 +92,61 @@ pub fn ui(f: &mut Frame<'_>, editor: &mut Editor, motion: &mut MotionHandler){
                              Constraint::Percentage(50),
                              Constraint::Percentage(50)
                 ])
-                .split(wrapper_layout[1]);
+                .split(status_file[0]);
 
             f.render_widget(status.to_owned(), status_motion[0]);
             f.render_widget(motion, status_motion[1]);
         },
         None => {
-            f.render_widget(status.to_owned(), wrapper_layout[1]);
+            f.render_widget(status.to_owned(), status_file[0]);
         }
     }
+    f.render_widget(file_info, status_file[1]);
 
     let (line_par, text_par) = editor.buffer_display();
 
     f.render_widget(line_par, num_text_layout[0]);
     f.render_widget(text_par, num_text_layout[1]);
+
+    if let (Some(text), Some(buffer)) = (&editor.float, editor.current_buffer()) {
+        // the text starts one column in from its area
+        let (x, y) = (buffer.cursor_col(), buffer.cursor.current.1);
+        let anchor = (
+            num_text_layout[1].x.saturating_add(x as u16 + 1),
+            num_text_layout[1].y.saturating_add(y as u16),
+        );
+        f.render_widget(FloatWidget::new(text, anchor), num_text_layout[1]);
+    }
+
+    if let (Some(completion), Some(buffer)) = (&editor.completion, editor.current_buffer()) {
+        // lined up with the start of the text being completed
+        let (x, y) = (buffer.cursor_col(), buffer.cursor.current.1);
+        let x = x.saturating_sub(completion.query.chars().count());
+        let anchor = (
+            num_text_layout[1].x.saturating_add(x as u16),
+            num_text_layout[1].y.saturating_add(y as u16),
+        );
+        f.render_widget(CompletionWidget::new(completion, anchor), num_text_layout[1]);
+    }
+
+    if let (true, Some(tree)) = (tree_width > 0, &editor.tree) {
+        let block = Block::default()
+            .borders(Borders::RIGHT)
+            .border_style(Style::new().dark_gray());
+        let tree_area = block.inner(tree_layout[0]);
+
+        f.render_widget(block, tree_layout[0]);
+        f.render_widget(TreeWidget::new(tree, editor.focus == Focus::Tree), tree_area);
+    }
+
+    if let (true, Some(list)) = (quickfix_height > 0, &editor.quickfix) {
+        f.render_widget(QuickfixWidget::new(list, editor.focus == Focus::Quickfix), wrapper_layout[1]);
+    }
+
+    if let Some(picker) = &mut editor.picker {
+        picker.rows = picker_rows(f.size());
+        f.render_widget(PickerWidget::new(picker), f.size());
+    }
 }
 
 
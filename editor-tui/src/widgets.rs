@@ -1,58 +1,64 @@
 use ratatui::{
-    prelude::{Alignment, Style},
-    style::Stylize,
-    widgets::{
-        Block,
-        Borders,
-        Paragraph,
-        Widget
-    },
-    Frame,
+    prelude::{Color, Modifier, Rect, Style},
+    widgets::{Block, Borders, Clear, Paragraph, Widget, Wrap},
 };
 use crate::editor::Editor;
-use editor_core::buffer::{Buffer, Mode};
+use editor_core::{
+    buffer::{Buffer, Mode},
+    completion::{Completion, MAX_ROWS},
+    explorer::EntryKind,
+    git::GitStatus,
+    picker::Picker,
+    quickfix::Quickfix,
+    tree::FileTree,
+};
 
+#[allow(dead_code)]
 struct BufferWidget {
     text: String,
 }
 
 // might be too much data
+#[allow(dead_code)]
 impl BufferWidget {
     fn new(b: &Buffer) -> Self {
-        return BufferWidget {
+        BufferWidget {
             text: b.lines.rope.to_string()
-        };
+        }
     }
 }
 
 // text within view of current buffer
 impl Widget for BufferWidget {
-    fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+    fn render(self, _area: ratatui::prelude::Rect, _buf: &mut ratatui::prelude::Buffer) {
 
     }
 }
 
+#[allow(dead_code)]
 struct EditorWidget {
     status_line: StatuslineWidget,
     buffer: BufferWidget,
 }
 
+#[allow(dead_code)]
 impl EditorWidget {
     fn new(e: &Editor) -> Self {
-        return EditorWidget {
+        EditorWidget {
             status_line: StatuslineWidget::new(e),
             buffer: BufferWidget::new(&e.buffers[e.buf_ptr]),
-        };
+        }
     }
 }
 
 
 impl Widget for EditorWidget {
-    fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+    fn render(self, _area: ratatui::prelude::Rect, _buf: &mut ratatui::prelude::Buffer) {
 
     }
 }
 
+#[allow(dead_code)]
 struct StatuslineWidget {
     command: String,
     motion: String,
@@ -60,9 +66,10 @@ struct StatuslineWidget {
     location: (u16, u16),
 }
 
+#[allow(dead_code)]
 impl StatuslineWidget {
     pub fn new(e: &Editor) -> Self {
-        return StatuslineWidget {
+        StatuslineWidget {
             command: "".to_string(),
             motion: "".to_string(),
             mode: e.buffers[e.buf_ptr].mode,
@@ -79,3 +86,351 @@ impl Widget for StatuslineWidget {
         buf.set_string(area.right(), area.top(), location, Style::default());
     }
 }
+
+// file tree side panel, the selected row is highlighted while it has focus
+pub struct TreeWidget<'a> {
+    tree: &'a FileTree,
+    focused: bool,
+}
+
+impl<'a> TreeWidget<'a> {
+    pub fn new(tree: &'a FileTree, focused: bool) -> Self {
+        TreeWidget { tree, focused }
+    }
+}
+
+impl Widget for TreeWidget<'_> {
+    fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+        let rows = self.tree.nodes.iter()
+            .enumerate()
+            .skip(self.tree.offset)
+            .take(usize::from(area.height));
+
+        for (y, (i, node)) in (area.top()..).zip(rows) {
+            let icon = match (node.is_dir, node.expanded) {
+                (true, true) => '▾',
+                (true, false) => '▸',
+                _ => ' ',
+            };
+            let color = match node.kind {
+                EntryKind::Directory => Color::Blue,
+                EntryKind::Executable => Color::Green,
+                EntryKind::Symlink => Color::Cyan,
+                EntryKind::Other => Color::Yellow,
+                EntryKind::File => Color::Reset,
+            };
+
+            let mut style = Style::default().fg(color);
+            if i == self.tree.cursor {
+                style = if self.focused {
+                    style.bg(Color::DarkGray)
+                } else {
+                    style.add_modifier(Modifier::BOLD)
+                };
+                buf.set_style(ratatui::prelude::Rect::new(area.left(), y, area.width, 1), style);
+            }
+
+            let text = format!("{}{} {}", "  ".repeat(node.depth), icon, node.name);
+            buf.set_stringn(area.left(), y, text, usize::from(area.width.saturating_sub(2)), style);
+
+            if let Some(status) = self.tree.git_status(node) {
+                let color = match status {
+                    GitStatus::Untracked => Color::DarkGray,
+                    GitStatus::Renamed | GitStatus::Modified => Color::Yellow,
+                    GitStatus::Added => Color::Green,
+                    GitStatus::Deleted | GitStatus::Conflicted => Color::Red,
+                };
+                let x = area.right().saturating_sub(1);
+                buf.set_string(x, y, status.marker().to_string(), style.fg(color));
+            }
+        }
+    }
+}
+
+// where the picker pops up over the screen
+pub fn picker_area(area: Rect) -> Rect {
+    let width = (area.width * 4 / 5).max(20).min(area.width);
+    let height = (area.height * 7 / 10).max(6).min(area.height);
+
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+// number of matches that fit in the picker
+pub fn picker_rows(area: Rect) -> usize {
+    usize::from(picker_area(area).height.saturating_sub(3))
+}
+
+// query on top, matches on the left and a preview of the selected one on the right
+pub struct PickerWidget<'a> {
+    picker: &'a Picker,
+}
+
+impl<'a> PickerWidget<'a> {
+    pub fn new(picker: &'a Picker) -> Self {
+        PickerWidget { picker }
+    }
+}
+
+impl Widget for PickerWidget<'_> {
+    fn render(self, area: Rect, buf: &mut ratatui::prelude::Buffer) {
+        let area = picker_area(area);
+        let picker = self.picker;
+
+        Clear.render(area, buf);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} ", picker.kind.title()));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        if inner.height < 2 {
+            return;
+        }
+
+        let count = format!("{}/{}", picker.matches.len(), picker.items.len());
+        buf.set_stringn(inner.x, inner.y, format!("> {}", picker.query), usize::from(inner.width), Style::default());
+        buf.set_string(
+            inner.right().saturating_sub(count.len() as u16),
+            inner.y,
+            count,
+            Style::default().fg(Color::DarkGray),
+        );
+
+        let list_width = if picker.preview.as_ref().is_some_and(|(_, l)| !l.is_empty()) {
+            inner.width * 11 / 20
+        } else {
+            inner.width
+        };
+        let rows = usize::from(inner.height - 1);
+
+        for (y, (i, m)) in (inner.y + 1..).zip(picker.matches.iter().enumerate().skip(picker.offset).take(rows)) {
+            let selected = i == picker.selected;
+            let base = if selected {
+                Style::default().bg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            if selected {
+                buf.set_style(Rect::new(inner.x, y, list_width, 1), base);
+            }
+
+            // matched characters stand out
+            let label = &picker.items[m.item].label;
+            for (x, (n, c)) in (inner.x..inner.x + list_width).zip(label.chars().enumerate()) {
+                let style = if m.positions.contains(&n) {
+                    base.fg(Color::Yellow).add_modifier(Modifier::BOLD)
+                } else {
+                    base
+                };
+                buf.set_string(x, y, c.to_string(), style);
+            }
+        }
+
+        if let Some((_, lines)) = &picker.preview {
+            if list_width < inner.width {
+                let preview_area = Rect::new(inner.x + list_width, inner.y + 1, inner.width - list_width, inner.height - 1);
+                let block = Block::default()
+                    .borders(Borders::LEFT)
+                    .border_style(Style::default().fg(Color::DarkGray));
+                let text_area = block.inner(preview_area);
+                block.render(preview_area, buf);
+
+                for (y, line) in (text_area.y..text_area.bottom()).zip(lines) {
+                    buf.set_stringn(text_area.x + 1, y, line, usize::from(text_area.width.saturating_sub(1)), Style::default());
+                }
+            }
+        }
+    }
+}
+
+// a bordered box of text next to a point in the area, under it when there is room
+pub struct FloatWidget<'a> {
+    text: &'a str,
+    anchor: (u16, u16),
+}
+
+impl<'a> FloatWidget<'a> {
+    pub fn new(text: &'a str, anchor: (u16, u16)) -> Self {
+        FloatWidget { text, anchor }
+    }
+}
+
+impl Widget for FloatWidget<'_> {
+    fn render(self, area: Rect, buf: &mut ratatui::prelude::Buffer) {
+        if area.width < 3 || area.height < 3 {
+            return;
+        }
+
+        let longest = self.text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let width = (longest as u16).saturating_add(2).clamp(3, area.width.min(80));
+        let inner_width = usize::from(width - 2);
+        let rows: usize = self.text.lines()
+            .map(|l| l.chars().count().div_ceil(inner_width).max(1))
+            .sum();
+        let height = (rows as u16).saturating_add(2).min(area.height);
+
+        let x = self.anchor.0.min(area.right() - width).max(area.x);
+        let y = if self.anchor.1 + 1 + height <= area.bottom() {
+            self.anchor.1 + 1
+        } else {
+            self.anchor.1.saturating_sub(height).max(area.y)
+        };
+        let float = Rect::new(x, y, width, height);
+
+        Clear.render(float, buf);
+        Paragraph::new(self.text)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::DarkGray)))
+            .render(float, buf);
+    }
+}
+
+// the completion popup under the start of the completed text, with the
+// selected item's documentation beside it
+pub struct CompletionWidget<'a> {
+    completion: &'a Completion,
+    anchor: (u16, u16),
+}
+
+impl<'a> CompletionWidget<'a> {
+    pub fn new(completion: &'a Completion, anchor: (u16, u16)) -> Self {
+        CompletionWidget { completion, anchor }
+    }
+}
+
+impl Widget for CompletionWidget<'_> {
+    fn render(self, area: Rect, buf: &mut ratatui::prelude::Buffer) {
+        let completion = self.completion;
+        if completion.matches.is_empty() || area.width < 4 || area.height < 2 {
+            return;
+        }
+
+        let rows: Vec<_> = completion.matches.iter()
+            .enumerate()
+            .skip(completion.offset)
+            .take(MAX_ROWS)
+            .collect();
+        let kind = |i: usize| {
+            let item = &completion.items[i];
+            item.detail.clone().unwrap_or_else(|| item.source.name().to_string())
+        };
+        let widest = rows.iter()
+            .map(|(_, m)| completion.items[m.item].label.chars().count() + kind(m.item).chars().count() + 3)
+            .max()
+            .unwrap_or(0);
+        let width = (widest as u16).clamp(10, 50).min(area.width);
+        let height = (rows.len() as u16).min(area.height - 1);
+
+        let x = self.anchor.0.min(area.right().saturating_sub(width)).max(area.x);
+        let y = if self.anchor.1 + 1 + height <= area.bottom() {
+            self.anchor.1 + 1
+        } else {
+            self.anchor.1.saturating_sub(height).max(area.y)
+        };
+        let list = Rect::new(x, y, width, height);
+        Clear.render(list, buf);
+        buf.set_style(list, Style::default().bg(Color::Black));
+
+        for (row, (i, m)) in (list.y..list.bottom()).zip(rows) {
+            let style = match i == completion.selected {
+                true => Style::default().bg(Color::DarkGray),
+                false => Style::default().bg(Color::Black),
+            };
+            buf.set_style(Rect::new(list.x, row, list.width, 1), style);
+
+            let item = &completion.items[m.item];
+            for (col, (n, c)) in (list.x + 1..list.right()).zip(item.label.chars().enumerate()) {
+                let style = match m.positions.contains(&n) {
+                    true => style.fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                    false => style,
+                };
+                buf.set_string(col, row, c.to_string(), style);
+            }
+
+            let kind = kind(m.item);
+            let label_width = item.label.chars().count() as u16 + 2;
+            let kind_width = (kind.chars().count() as u16).min(list.width.saturating_sub(label_width + 1));
+            if kind_width > 0 {
+                let kind_x = list.right() - kind_width - 1;
+                buf.set_stringn(kind_x, row, &kind, usize::from(kind_width), style.fg(Color::DarkGray));
+            }
+        }
+
+        // documentation goes to the right of the list, or the left when it doesn't fit
+        let documentation = match completion.selected_item().and_then(|i| i.documentation.as_ref()) {
+            Some(documentation) => documentation,
+            None => return,
+        };
+        let doc_width = 42.min(area.width.saturating_sub(width));
+        if doc_width < 12 {
+            return;
+        }
+        let doc_x = match list.right() + doc_width <= area.right() {
+            true => list.right(),
+            false => list.x.saturating_sub(doc_width).max(area.x),
+        };
+        let doc_height = (documentation.lines().count() as u16 + 2).clamp(3, 12).min(area.bottom() - list.y);
+        let doc = Rect::new(doc_x, list.y, doc_width, doc_height);
+
+        Clear.render(doc, buf);
+        Paragraph::new(documentation.as_str())
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::DarkGray)))
+            .render(doc, buf);
+    }
+}
+
+// quickfix list under the buffer, a title line then one entry per row
+pub struct QuickfixWidget<'a> {
+    list: &'a Quickfix,
+    focused: bool,
+}
+
+impl<'a> QuickfixWidget<'a> {
+    pub fn new(list: &'a Quickfix, focused: bool) -> Self {
+        QuickfixWidget { list, focused }
+    }
+}
+
+impl Widget for QuickfixWidget<'_> {
+    fn render(self, area: Rect, buf: &mut ratatui::prelude::Buffer) {
+        if area.height == 0 {
+            return;
+        }
+
+        let list = self.list;
+        let title = format!(" Quickfix: {} ({}) ", list.title, list.entries.len());
+        let title_style = Style::default().fg(Color::Black).bg(Color::Blue);
+        buf.set_style(Rect::new(area.x, area.y, area.width, 1), title_style);
+        buf.set_stringn(area.x, area.y, title, usize::from(area.width), title_style);
+
+        let rows = list.entries.iter()
+            .enumerate()
+            .skip(list.offset)
+            .take(usize::from(area.height - 1));
+
+        for (y, (i, entry)) in (area.y + 1..).zip(rows) {
+            let mut style = Style::default();
+            if i == list.current {
+                style = if self.focused {
+                    style.bg(Color::DarkGray)
+                } else {
+                    style.add_modifier(Modifier::BOLD)
+                };
+                buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+            }
+
+            let location = format!("{}:{}:{}", entry.path.display(), entry.line + 1, entry.col + 1);
+            let width = location.chars().count() as u16;
+            buf.set_stringn(area.x, y, &location, usize::from(area.width), style.fg(Color::Cyan));
+            if width + 2 < area.width {
+                buf.set_stringn(area.x + width + 2, y, &entry.text, usize::from(area.width - width - 2), style);
+            }
+        }
+    }
+}
@@ -1,21 +1,23 @@
 use editor_core::buffer::Buffer;
 use color_eyre::eyre::Result;
 
+#[allow(dead_code)]
 pub struct Window {
     pub size: (u16, u16),
     pub buffers: Vec<Buffer>,
     pub buf_ptr: usize, // will point to the buffer to be typed in
 }
 
+#[allow(dead_code)]
 impl Window {
     #[inline]
     pub fn new() -> Self {
         // FIX: return empty vec with ptr 0 is bad
-        return Window {
+        Window {
             size: (0, 0),
             buffers: vec![],
             buf_ptr: 0
-        };
+        }
     }
 
     #[inline]